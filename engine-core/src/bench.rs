@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    chess_consts::fen_strings,
+    fen_parser,
+    move_ordering::MoveOrderingState,
+    searching::{self, StopToken},
+    transposition_table::{DEFAULT_HASH_MB, TranspositionTable},
+};
+
+pub(crate) const BENCH_DEPTH: u32 = 5;
+
+/// Positions of the benchmark, the perft test positions are among them
+const BENCH_FENS: [&str; 8] = [
+    fen_strings::START_POS_FEN,
+    fen_strings::TRICKY_POS_FEN,
+    fen_strings::KILLER_POS_FEN,
+    fen_strings::CMK_POS_FEN,
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BenchResult {
+    pub(crate) nodes: usize,
+    pub(crate) elapsed: Duration,
+}
+
+impl BenchResult {
+    pub(crate) fn nps(&self) -> u64 {
+        let micros = self.elapsed.as_micros().max(1);
+
+        (self.nodes as u128 * 1_000_000 / micros) as u64
+    }
+}
+
+/// Searches every position of the suite to the depth on a single thread. Each search
+/// starts with a fresh move ordering state and transposition table, so the node count
+/// only changes when the search itself does
+pub(crate) fn run_bench(depth: u32) -> BenchResult {
+    let mut nodes = 0;
+
+    let start = Instant::now();
+
+    for fen in BENCH_FENS {
+        let mut board = fen_parser::parse_fen_string(fen).unwrap();
+
+        let result = searching::search_bestmove(
+            &mut board,
+            depth,
+            &StopToken::new(),
+            &mut MoveOrderingState::new(),
+            &TranspositionTable::new(DEFAULT_HASH_MB).unwrap(),
+        );
+
+        nodes += result.map_or(0, |result| result.nodes);
+    }
+
+    BenchResult {
+        nodes,
+        elapsed: start.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_fens_are_valid() {
+        for fen in BENCH_FENS {
+            assert!(fen_parser::parse_fen_string(fen).is_ok(), "{fen}");
+        }
+
+        assert!(run_bench(1).nodes > 0);
+    }
+}
@@ -1,14 +1,17 @@
-use std::fmt::Display;
+use std::{fmt::Display, sync::Arc};
 
 use crate::{
     chess_consts,
-    enums::{CastlingSide, Piece, Side, Square},
+    enums::{CastlingSide, File, Move, MoveFlags, Piece, Side, Square},
+    evaluation::{self, EvalParams, TaperedScore},
     fen_parser, helpers,
     history::History,
     king_attack_table::get_king_attacks_mask,
     knight_attack_table::get_knight_attacks_mask,
+    line_table,
     pawn_attack_table::get_pawn_attacks_mask,
     sliding_piece_attack_table::{get_bishop_attacks_mask, get_rook_attacks_mask},
+    zobrist,
 };
 
 #[derive(Clone, Debug, Default)]
@@ -18,6 +21,14 @@ pub struct Board {
     pub(crate) global_occupancy: u64,
     pub(crate) game_state: GameState,
     pub(crate) history: History,
+    /// Starting squares of the kings and the castling rooks
+    pub(crate) castling_config: CastlingConfig,
+    /// Chess960 mode, castling moves are written as the king capturing its own rook
+    pub(crate) chess960: bool,
+    /// Weights the incremental material and PST score is computed with
+    pub(crate) eval_params: Arc<EvalParams>,
+    /// Material and PST score from white's perspective, updated in add_piece/remove_piece
+    pub(crate) psqt_score: TaperedScore,
 }
 
 impl PartialEq for Board {
@@ -26,7 +37,8 @@ impl PartialEq for Board {
             && self.side_occupancies == other.side_occupancies
             && self.global_occupancy == other.global_occupancy
             && self.game_state == other.game_state
-            && self.history.len() == other.history.len()
+            && self.castling_config == other.castling_config
+            && self.history == other.history
     }
 }
 
@@ -37,6 +49,10 @@ pub(crate) struct GameState {
     pub(crate) castling_state: CastlingState,
     pub(crate) half_move_clock: u8,
     pub(crate) full_moves_count: u16,
+    /// Zobrist hash of the position, kept here so that unmake_move restores it
+    pub(crate) hash: u64,
+    /// Zobrist hash of the pawns alone, the key of the pawn hash table
+    pub(crate) pawn_hash: u64,
 }
 
 impl Board {
@@ -54,6 +70,28 @@ impl Board {
         self.side_occupancies[side.index() as usize]
     }
 
+    /// Number of the side's pieces of the kind on the board
+    pub fn piece_count(&self, side: Side, piece: Piece) -> u32 {
+        self.get_bb(side, piece).count_ones()
+    }
+
+    /// Material of white minus the material of black in centipawns, the kings left out
+    /// # Examples
+    /// Start position -> 0
+    /// White is up a knight for a pawn -> 200
+    pub fn material_balance(&self) -> i32 {
+        Side::all()
+            .flat_map(|side| Piece::all().map(move |piece| (side, piece)))
+            .filter(|&(_, piece)| piece != Piece::King)
+            .map(|(side, piece)| {
+                let value = self.eval_params.midgame_piece_values[piece.index() as usize];
+                let value = if side == Side::White { value } else { -value };
+
+                self.piece_count(side, piece) as i32 * value
+            })
+            .sum()
+    }
+
     pub(crate) fn get_occupancy_bb_mut(&mut self, side: Side) -> &mut u64 {
         &mut self.side_occupancies[side.index() as usize]
     }
@@ -111,11 +149,254 @@ impl Board {
         false
     }
 
+    /// Returns the pieces of the side attacking the square
+    /// # Examples
+    /// White knights on c3 and g3, e4 -> the c3 and g3 squares
+    #[allow(dead_code)]
+    pub(crate) fn attackers_to(&self, square: Square, side: Side) -> u64 {
+        self.get_attackers_bb(square, self.global_occupancy) & self.get_occupancy_bb(side)
+    }
+
+    /// Tells whether the en passant capture leaves the own king safe. Both pawns leave
+    /// their squares at once, which may open the rank for an enemy rook or queen, and
+    /// get_pinned_bb sees only one piece on the line
+    /// # Examples
+    /// 8/8/8/K2pP2r/8/8/8/8 w - d6, e5d6 -> false, the h5 rook attacks the king then
+    pub(crate) fn is_en_passant_legal(&self, side: Side, from: Square, to: Square) -> bool {
+        let king_sq = self.get_king_square(side);
+        let captured_sq = to.backward(side);
+        let occupancy = (self.global_occupancy ^ from.bit() ^ captured_sq.bit()) | to.bit();
+
+        // The captured pawn is gone from the occupancy, so it doesn't attack either
+        self.get_attackers_bb(king_sq, occupancy)
+            & self.get_occupancy_bb(side.opposite())
+            & occupancy
+            == 0
+    }
+
+    /// Returns the pieces of the side that can't leave the line between their king and
+    /// an enemy slider without exposing the king
+    pub(crate) fn get_pinned_bb(&self, side: Side) -> u64 {
+        let king_sq = self.get_king_square(side);
+        let enemy = side.opposite();
+        let enemy_occupancy = self.get_occupancy_bb(enemy);
+        let enemy_queens = self.get_bb(enemy, Piece::Queen);
+
+        // Sliders that would attack the king if only their own pieces could block them
+        let mut snipers = (get_rook_attacks_mask(king_sq, enemy_occupancy)
+            & (self.get_bb(enemy, Piece::Rook) | enemy_queens))
+            | (get_bishop_attacks_mask(king_sq, enemy_occupancy)
+                & (self.get_bb(enemy, Piece::Bishop) | enemy_queens));
+
+        let mut pinned = 0;
+
+        while snipers != 0 {
+            let sniper = unsafe { Square::from_u8_unchecked(snipers.trailing_zeros() as u8) };
+            snipers &= snipers - 1;
+
+            let blockers = line_table::get_between_mask(king_sq, sniper) & self.global_occupancy;
+            if blockers.count_ones() == 1 {
+                pinned |= blockers & self.get_occupancy_bb(side);
+            }
+        }
+
+        pinned
+    }
+
+    /// Returns the pieces of both sides attacking the square, sliders are computed
+    /// against the given occupancy so removed pieces reveal x-ray attackers
+    pub(crate) fn get_attackers_bb(&self, square: Square, occupancy: u64) -> u64 {
+        let bishop_attacks_bb = get_bishop_attacks_mask(square, occupancy);
+        let rook_attacks_bb = get_rook_attacks_mask(square, occupancy);
+
+        let queens_bb =
+            self.get_bb(Side::White, Piece::Queen) | self.get_bb(Side::Black, Piece::Queen);
+        let bishops_bb =
+            self.get_bb(Side::White, Piece::Bishop) | self.get_bb(Side::Black, Piece::Bishop);
+        let rooks_bb =
+            self.get_bb(Side::White, Piece::Rook) | self.get_bb(Side::Black, Piece::Rook);
+        let knights_bb =
+            self.get_bb(Side::White, Piece::Knight) | self.get_bb(Side::Black, Piece::Knight);
+        let kings_bb =
+            self.get_bb(Side::White, Piece::King) | self.get_bb(Side::Black, Piece::King);
+
+        (get_pawn_attacks_mask(Side::Black, square) & self.get_bb(Side::White, Piece::Pawn))
+            | (get_pawn_attacks_mask(Side::White, square) & self.get_bb(Side::Black, Piece::Pawn))
+            | (get_knight_attacks_mask(square) & knights_bb)
+            | (get_king_attacks_mask(square) & kings_bb)
+            | (bishop_attacks_bb & (bishops_bb | queens_bb))
+            | (rook_attacks_bb & (rooks_bb | queens_bb))
+    }
+
+    /// Static exchange evaluation: the net material gain of the move for the side
+    /// to move when both sides keep recapturing on the target square with their
+    /// least valuable attacker. Non capturing moves give 0 unless they promote
+    pub(crate) fn see(&self, mv: Move) -> i32 {
+        let Move::Normal {
+            from,
+            to,
+            piece,
+            captured,
+            promo,
+            flags,
+        } = mv
+        else {
+            return 0;
+        };
+
+        let value = |piece: Piece| self.eval_params.midgame_piece_values[piece.index() as usize];
+
+        let mut side = self.game_state.side_to_move;
+        let mut occupancy = self.global_occupancy;
+
+        if flags.contains(MoveFlags::EN_PASSANT) {
+            occupancy &= !to.backward(side).bit();
+        }
+
+        let mut gain = [0i32; 32];
+        let mut depth = 0;
+
+        gain[0] = captured.map_or(0, value);
+        let mut attacker_value = value(piece);
+
+        if let Some(promo_piece) = promo {
+            gain[0] += value(promo_piece) - value(Piece::Pawn);
+            attacker_value = value(promo_piece);
+        }
+
+        let mut attacker_sq = from;
+
+        loop {
+            depth += 1;
+            gain[depth] = attacker_value - gain[depth - 1];
+
+            if depth + 1 == gain.len() {
+                break;
+            }
+
+            side = side.opposite();
+            occupancy &= !attacker_sq.bit();
+
+            let attackers_bb = self.get_attackers_bb(to, occupancy) & occupancy;
+            let side_attackers_bb = attackers_bb & self.get_occupancy_bb(side);
+
+            let Some((next_piece, next_sq)) = Piece::all().find_map(|p| {
+                let bb = side_attackers_bb & self.get_bb(side, p);
+
+                (bb != 0).then(|| {
+                    (p, unsafe {
+                        Square::from_u8_unchecked(bb.trailing_zeros() as u8)
+                    })
+                })
+            }) else {
+                break;
+            };
+
+            // The king can't recapture on a square that is still defended
+            if next_piece == Piece::King
+                && attackers_bb & self.get_occupancy_bb(side.opposite()) != 0
+            {
+                break;
+            }
+
+            attacker_sq = next_sq;
+            attacker_value = value(next_piece);
+        }
+
+        while depth > 1 {
+            depth -= 1;
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        }
+
+        gain[0]
+    }
+
     pub(crate) fn is_in_check(&self, side: Side) -> bool {
         let king_sq = self.get_king_square(side);
         self.is_square_attacked(king_sq, side.opposite())
     }
 
+    /// Tells whether the move of the side to move checks the enemy king, without making
+    /// it. The moved piece may check directly, or a slider behind it through the vacated
+    /// square, the en passant captured pawn's square or the castling rook's path
+    pub(crate) fn gives_check(&self, mv: Move) -> bool {
+        let side = self.game_state.side_to_move;
+        let enemy_king_sq = self.get_king_square(side.opposite());
+
+        let mut rooks = self.get_bb(side, Piece::Rook) | self.get_bb(side, Piece::Queen);
+        let mut bishops = self.get_bb(side, Piece::Bishop) | self.get_bb(side, Piece::Queen);
+        let mut occupancy = self.global_occupancy;
+
+        match mv {
+            Move::Normal {
+                from,
+                to,
+                piece,
+                promo,
+                flags,
+                ..
+            } => {
+                let from_bb = helpers::set_bit(0, from);
+                let to_bb = helpers::set_bit(0, to);
+
+                occupancy = (occupancy & !from_bb) | to_bb;
+                rooks &= !from_bb;
+                bishops &= !from_bb;
+
+                if flags.contains(MoveFlags::EN_PASSANT) {
+                    occupancy =
+                        helpers::pop_bit(occupancy, Square::from_file_rank(to.file(), from.rank()));
+                }
+
+                let direct_attacks = match promo.unwrap_or(piece) {
+                    Piece::Pawn => get_pawn_attacks_mask(side, to),
+                    Piece::Knight => get_knight_attacks_mask(to),
+                    Piece::Bishop => {
+                        bishops |= to_bb;
+                        0
+                    }
+                    Piece::Rook => {
+                        rooks |= to_bb;
+                        0
+                    }
+                    Piece::Queen => {
+                        rooks |= to_bb;
+                        bishops |= to_bb;
+                        0
+                    }
+                    Piece::King => 0,
+                };
+
+                if helpers::is_bit_set(direct_attacks, enemy_king_sq) {
+                    return true;
+                }
+            }
+            Move::Castle {
+                from: king_from,
+                to: king_to,
+                side: castling_side,
+            } => {
+                let (rook_from, rook_to) =
+                    self.castling_config
+                        .get_castling_positions(side, Piece::Rook, castling_side);
+
+                occupancy = helpers::pop_bit(occupancy, king_from);
+                occupancy = helpers::pop_bit(occupancy, rook_from);
+                occupancy = helpers::set_bit(occupancy, king_to);
+                occupancy = helpers::set_bit(occupancy, rook_to);
+                rooks = helpers::set_bit(helpers::pop_bit(rooks, rook_from), rook_to);
+            }
+        }
+
+        get_rook_attacks_mask(enemy_king_sq, occupancy) & rooks != 0
+            || get_bishop_attacks_mask(enemy_king_sq, occupancy) & bishops != 0
+    }
+
+    /// Fifty moves of each side passed without a capture or a pawn move
+    pub(crate) fn can_claim_fifty_move_draw(&self) -> bool {
+        self.game_state.half_move_clock >= chess_consts::MAX_HALF_MOVES_COUNT
+    }
+
     pub(crate) fn get_king_square(&self, side: Side) -> Square {
         debug_assert!(
             self.get_bb(side, Piece::King) != 0,
@@ -141,7 +422,7 @@ impl Board {
             }
         }
 
-        return None;
+        None
     }
 
     pub(crate) fn get_start_position() -> Board {
@@ -153,6 +434,12 @@ impl Board {
         *self.get_bb_mut(side, piece) |= mask;
         *self.get_occupancy_bb_mut(side) |= mask;
         self.global_occupancy |= mask;
+        self.psqt_score += evaluation::get_psqt_score(&self.eval_params, side, piece, square);
+        self.game_state.hash ^= zobrist::get_piece_key(side, piece, square);
+
+        if piece == Piece::Pawn {
+            self.game_state.pawn_hash ^= zobrist::get_piece_key(side, piece, square);
+        }
     }
 
     pub(crate) fn remove_piece(&mut self, side: Side, piece: Piece, square: Square) {
@@ -160,10 +447,27 @@ impl Board {
         *self.get_bb_mut(side, piece) &= !mask;
         *self.get_occupancy_bb_mut(side) &= !mask;
         self.global_occupancy &= !mask;
+        self.psqt_score -= evaluation::get_psqt_score(&self.eval_params, side, piece, square);
+        self.game_state.hash ^= zobrist::get_piece_key(side, piece, square);
+
+        if piece == Piece::Pawn {
+            self.game_state.pawn_hash ^= zobrist::get_piece_key(side, piece, square);
+        }
+    }
+
+    /// Recomputes the incremental material and PST score, must be called after
+    /// the bitboards were changed directly
+    pub(crate) fn recalc_psqt_score(&mut self) {
+        self.psqt_score = evaluation::calc_psqt_score(self, &self.eval_params);
     }
-    pub(crate) fn move_piece(&mut self, side: Side, piece: Piece, from: Square, to: Square) {
-        self.remove_piece(side, piece, from);
-        self.add_piece(side, piece, to);
+
+    pub(crate) fn set_eval_params(&mut self, eval_params: Arc<EvalParams>) {
+        if Arc::ptr_eq(&self.eval_params, &eval_params) {
+            return;
+        }
+
+        self.eval_params = eval_params;
+        self.recalc_psqt_score();
     }
 }
 
@@ -180,7 +484,7 @@ impl Display for Board {
         for side in Side::all() {
             for piece in Piece::all() {
                 let bb = self.get_bb(side, piece);
-                set(bb, helpers::get_ascii_piece_char(side, piece));
+                set(bb, piece.to_char(side));
             }
         }
 
@@ -237,18 +541,21 @@ impl CastlingState {
         }
     }
 
-    pub fn remove_rook(&mut self, side: Side, square: Square) {
-        match side {
-            Side::White => match square {
-                Square::A1 => self.remove(CastlingState::WHITE_QUEENSIDE),
-                Square::H1 => self.remove(CastlingState::WHITE_KINGSIDE),
-                _ => {}
-            },
-            Side::Black => match square {
-                Square::A8 => self.remove(CastlingState::BLACK_QUEENSIDE),
-                Square::H8 => self.remove(CastlingState::BLACK_KINGSIDE),
-                _ => {}
-            },
+    /// Removes the castling right of the rook that started on the square
+    pub fn remove_rook(&mut self, side: Side, square: Square, config: &CastlingConfig) {
+        for castling_side in CastlingSide::all() {
+            if config.get_rook_square(side, castling_side) == square {
+                self.remove(CastlingState::get_flag(side, castling_side));
+            }
+        }
+    }
+
+    pub(crate) fn get_flag(side: Side, castling_side: CastlingSide) -> CastlingState {
+        match (side, castling_side) {
+            (Side::White, CastlingSide::KingSide) => CastlingState::WHITE_KINGSIDE,
+            (Side::White, CastlingSide::QueenSide) => CastlingState::WHITE_QUEENSIDE,
+            (Side::Black, CastlingSide::KingSide) => CastlingState::BLACK_KINGSIDE,
+            (Side::Black, CastlingSide::QueenSide) => CastlingState::BLACK_QUEENSIDE,
         }
     }
 }
@@ -274,6 +581,100 @@ impl CastlingState {
     }
 }
 
+/// Starting files of the kings and the castling rooks. Standard chess uses the e file for
+/// the king and the a and h files for the rooks, Chess960 setups may use any of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct CastlingConfig {
+    king_files: [File; chess_consts::SIDES_COUNT],
+    /// Indexed by side and castling side
+    rook_files: [[File; 2]; chess_consts::SIDES_COUNT],
+}
+
+impl Default for CastlingConfig {
+    fn default() -> Self {
+        Self {
+            king_files: [File::E; chess_consts::SIDES_COUNT],
+            rook_files: [[File::H, File::A]; chess_consts::SIDES_COUNT],
+        }
+    }
+}
+
+impl CastlingConfig {
+    pub(crate) fn set_king_file(&mut self, side: Side, file: File) {
+        self.king_files[side.index() as usize] = file;
+    }
+
+    pub(crate) fn set_rook_file(&mut self, side: Side, castling_side: CastlingSide, file: File) {
+        self.rook_files[side.index() as usize][castling_side.index() as usize] = file;
+    }
+
+    pub(crate) fn get_king_square(&self, side: Side) -> Square {
+        Square::from_file_rank(self.king_files[side.index() as usize], side.get_back_rank())
+    }
+
+    pub(crate) fn get_rook_square(&self, side: Side, castling_side: CastlingSide) -> Square {
+        Square::from_file_rank(
+            self.rook_files[side.index() as usize][castling_side.index() as usize],
+            side.get_back_rank(),
+        )
+    }
+
+    /// Returns the from and to squares of the king or the rook taking part in the castling,
+    /// the destination squares are the same as in standard chess
+    pub(crate) fn get_castling_positions(
+        &self,
+        side: Side,
+        piece: Piece,
+        castling_side: CastlingSide,
+    ) -> (Square, Square) {
+        let back_rank = side.get_back_rank();
+
+        match (piece, castling_side) {
+            (Piece::King, CastlingSide::KingSide) => (
+                self.get_king_square(side),
+                Square::from_file_rank(File::G, back_rank),
+            ),
+            (Piece::King, CastlingSide::QueenSide) => (
+                self.get_king_square(side),
+                Square::from_file_rank(File::C, back_rank),
+            ),
+            (Piece::Rook, CastlingSide::KingSide) => (
+                self.get_rook_square(side, castling_side),
+                Square::from_file_rank(File::F, back_rank),
+            ),
+            (Piece::Rook, CastlingSide::QueenSide) => (
+                self.get_rook_square(side, castling_side),
+                Square::from_file_rank(File::D, back_rank),
+            ),
+            _ => panic!("Wrong piece type"),
+        }
+    }
+
+    pub(crate) fn get_castling_move(&self, side: Side, castling_side: CastlingSide) -> Move {
+        let (from, to) = self.get_castling_positions(side, Piece::King, castling_side);
+
+        Move::Castle {
+            from,
+            to,
+            side: castling_side,
+        }
+    }
+
+    /// Returns the squares that must be empty (except for the castling king and rook)
+    /// and the squares that must not be attacked for the castling to be legal
+    pub(crate) fn get_castling_masks(&self, side: Side, castling_side: CastlingSide) -> (u64, u64) {
+        let (king_from, king_to) = self.get_castling_positions(side, Piece::King, castling_side);
+        let (rook_from, rook_to) = self.get_castling_positions(side, Piece::Rook, castling_side);
+
+        let king_path_bb = helpers::rank_span_mask(king_from, king_to);
+        let rook_path_bb = helpers::rank_span_mask(rook_from, rook_to);
+
+        let empty_bb = (king_path_bb | rook_path_bb) & !(king_from.bit() | rook_from.bit());
+
+        (empty_bb, king_path_bb)
+    }
+}
+
 impl Display for CastlingState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -329,6 +730,91 @@ mod tests {
         println!("{board}");
     }
 
+    fn see_of(mut board: Board, from: Square, to: Square) -> i32 {
+        let mv = board
+            .generate_all_legal_moves_to_vec(board.game_state.side_to_move)
+            .into_iter()
+            .find(|mv| mv.get_from_to() == (from, to))
+            .unwrap();
+
+        board.see(mv)
+    }
+
+    #[test]
+    fn test_see() {
+        let see = |fen: &str, from: Square, to: Square| {
+            see_of(fen_parser::parse_fen_string(fen).unwrap(), from, to)
+        };
+
+        // Pawn takes a defended queen
+        assert_eq!(
+            see(
+                "4k3/2p5/3q4/4P3/8/8/8/4K3 w - - 0 1",
+                Square::E5,
+                Square::D6
+            ),
+            900
+        );
+
+        // Queen takes a pawn defended by a pawn
+        assert_eq!(
+            see(
+                "4k3/2p5/3p4/8/8/8/3Q4/4K3 w - - 0 1",
+                Square::D2,
+                Square::D6
+            ),
+            -900
+        );
+
+        // Undefended piece
+        assert_eq!(
+            see("4k3/8/3n4/8/8/8/3R4/4K3 w - - 0 1", Square::D2, Square::D6),
+            300
+        );
+
+        // Rook takes a pawn defended by a rook without support
+        assert_eq!(
+            see("3rk3/8/8/3p4/8/8/3R4/4K3 w - - 0 1", Square::D2, Square::D5),
+            -400
+        );
+
+        // The same capture with a rook battery behind, the second rook is an x-ray attacker
+        assert_eq!(
+            see(
+                "3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1",
+                Square::D2,
+                Square::D5
+            ),
+            100
+        );
+
+        // The king recaptures an undefended knight but not a defended one
+        assert_eq!(
+            see("4k3/8/8/8/8/1n6/3P4/4K3 b - - 0 1", Square::B3, Square::D2),
+            -200
+        );
+        assert_eq!(
+            see(
+                "4k3/8/8/6b1/8/1n6/3P4/4K3 b - - 0 1",
+                Square::B3,
+                Square::D2
+            ),
+            100
+        );
+    }
+
+    #[test]
+    fn test_material_values_come_from_the_eval_params() {
+        let mut board =
+            fen_parser::parse_fen_string("4k3/2p5/3q4/4P3/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut params = EvalParams::default();
+        params.midgame_piece_values[Piece::Queen.index() as usize] = 2000;
+        board.set_eval_params(Arc::new(params));
+
+        assert_eq!(board.material_balance(), -2000);
+        assert_eq!(see_of(board, Square::E5, Square::D6), 1900);
+    }
+
     #[test]
     fn test_is_square_attacked() {
         // ─────────────────────────────────────────────
@@ -349,7 +835,7 @@ mod tests {
 
         // ─────────────────────────────────────────────
         // Knight attacks (single knight on g2)
-        let board = fen_parser::parse_fen_string("8/8/8/8/8/8/6N1/8 w - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("8/8/8/8/8/8/6N1/8 w - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::E1, Side::White));
         assert!(board.is_square_attacked(Square::E3, Side::White));
         assert!(board.is_square_attacked(Square::F4, Side::White));
@@ -359,20 +845,21 @@ mod tests {
         // ─────────────────────────────────────────────
         // Bishop blocked by piece
         // Bishop d1, pawn e2 blocks diagonal to f3
-        let board = fen_parser::parse_fen_string("8/8/8/8/8/8/4P3/3B4 w - - 0 1").unwrap();
+        let board =
+            fen_parser::parse_fen_string_unchecked("8/8/8/8/8/8/4P3/3B4 w - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::C2, Side::White));
         assert!(!board.is_square_attacked(Square::G4, Side::White));
 
         // ─────────────────────────────────────────────
         // Rook blocked by own piece
         // Rook a1, pawn a2 blocks file
-        let board = fen_parser::parse_fen_string("8/8/8/8/8/8/P7/R7 w - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("8/8/8/8/8/8/P7/R7 w - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::A2, Side::White));
         assert!(!board.is_square_attacked(Square::A3, Side::White));
 
         // ─────────────────────────────────────────────
         // Queen attacks (center)
-        let board = fen_parser::parse_fen_string("8/8/8/8/4Q3/8/8/8 w - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("8/8/8/8/4Q3/8/8/8 w - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::E8, Side::White));
         assert!(board.is_square_attacked(Square::A4, Side::White));
         assert!(board.is_square_attacked(Square::H1, Side::White));
@@ -380,14 +867,15 @@ mod tests {
 
         // ─────────────────────────────────────────────
         // King attacks
-        let board = fen_parser::parse_fen_string("8/8/8/8/4K3/8/8/8 w - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("8/8/8/8/4K3/8/8/8 w - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::E5, Side::White));
         assert!(board.is_square_attacked(Square::D4, Side::White));
         assert!(!board.is_square_attacked(Square::E6, Side::White));
 
         // ─────────────────────────────────────────────
         // Mixed attackers: queen d1 + knight f3
-        let board = fen_parser::parse_fen_string("8/8/8/8/8/5N2/8/3Q4 w - - 0 1").unwrap();
+        let board =
+            fen_parser::parse_fen_string_unchecked("8/8/8/8/8/5N2/8/3Q4 w - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::H4, Side::White)); // knight from f3
         assert!(board.is_square_attacked(Square::D7, Side::White)); // queen up the file
         assert!(board.is_square_attacked(Square::A1, Side::White));
@@ -397,7 +885,7 @@ mod tests {
         // Black pieces: symmetry + edge cases
 
         // Black knight attacks (single knight on g7)
-        let board = fen_parser::parse_fen_string("8/6n1/8/8/8/8/8/8 b - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("8/6n1/8/8/8/8/8/8 b - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::E6, Side::Black));
         assert!(board.is_square_attacked(Square::F5, Side::Black));
         assert!(board.is_square_attacked(Square::H5, Side::Black));
@@ -405,24 +893,25 @@ mod tests {
         assert!(!board.is_square_attacked(Square::G6, Side::Black));
 
         // Black bishop blocked by piece (bishop d8, pawn e7 blocks diagonal)
-        let board = fen_parser::parse_fen_string("3b4/4p3/8/8/8/8/8/8 b - - 0 1").unwrap();
+        let board =
+            fen_parser::parse_fen_string_unchecked("3b4/4p3/8/8/8/8/8/8 b - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::C7, Side::Black)); // bishop attacks c7
         assert!(!board.is_square_attacked(Square::G5, Side::Black)); // would be on diagonal, but blocked by e7
 
         // Black rook blocked by own piece (rook a8, pawn a7 blocks file)
-        let board = fen_parser::parse_fen_string("r7/p7/8/8/8/8/8/8 b - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("r7/p7/8/8/8/8/8/8 b - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::A7, Side::Black));
         assert!(!board.is_square_attacked(Square::A6, Side::Black));
 
         // Black queen attacks (center-ish)
-        let board = fen_parser::parse_fen_string("8/8/8/8/8/4q3/8/8 b - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("8/8/8/8/8/4q3/8/8 b - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::E1, Side::Black)); // file down
         assert!(board.is_square_attacked(Square::A3, Side::Black)); // rank
         assert!(board.is_square_attacked(Square::H6, Side::Black)); // diagonal
         assert!(!board.is_square_attacked(Square::F1, Side::Black)); // not attacked square
 
         // Black king attacks
-        let board = fen_parser::parse_fen_string("8/8/8/8/8/4k3/8/8 b - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("8/8/8/8/8/4k3/8/8 b - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::E2, Side::Black));
         assert!(board.is_square_attacked(Square::D3, Side::Black));
         assert!(!board.is_square_attacked(Square::F5, Side::Black));
@@ -431,13 +920,13 @@ mod tests {
         // Pawn edge files: A-file / H-file (only one capture direction)
 
         // White pawn on a2 attacks only b3
-        let board = fen_parser::parse_fen_string("8/8/8/8/8/8/P7/8 w - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("8/8/8/8/8/8/P7/8 w - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::B3, Side::White));
         assert!(!board.is_square_attacked(Square::A3, Side::White));
         assert!(!board.is_square_attacked(Square::C3, Side::White));
 
         // Black pawn on h7 attacks only g6
-        let board = fen_parser::parse_fen_string("8/7p/8/8/8/8/8/8 b - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("8/7p/8/8/8/8/8/8 b - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::G6, Side::Black));
         assert!(!board.is_square_attacked(Square::H6, Side::Black));
         assert!(!board.is_square_attacked(Square::F6, Side::Black));
@@ -446,13 +935,142 @@ mod tests {
         // Slider “stop at blocker” semantics (attacked up to blocker, not beyond)
 
         // White rook a1, black pawn a4 blocks: a4 attacked, a5 not attacked
-        let board = fen_parser::parse_fen_string("8/8/8/8/p7/8/8/R7 w - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("8/8/8/8/p7/8/8/R7 w - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::A4, Side::White));
         assert!(!board.is_square_attacked(Square::A5, Side::White));
 
         // Black bishop h8, white pawn f6 blocks diagonal: f6 attacked, e5 not attacked
-        let board = fen_parser::parse_fen_string("7b/8/5P2/8/8/8/8/8 b - - 0 1").unwrap();
+        let board = fen_parser::parse_fen_string_unchecked("7b/8/5P2/8/8/8/8/8 b - - 0 1").unwrap();
         assert!(board.is_square_attacked(Square::F6, Side::Black));
         assert!(!board.is_square_attacked(Square::E5, Side::Black));
     }
+
+    #[test]
+    fn test_gives_check() {
+        let fens = [
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            // Castling with check from the rook, en passant discovering a check
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "8/8/8/R2pP2k/8/8/8/4K3 w - d6 0 1",
+        ];
+
+        let assert_gives_check = |board: &mut Board, mv: Move, fen: &str| {
+            let side = board.game_state.side_to_move;
+            let gives_check = board.gives_check(mv);
+
+            board.make_move(mv);
+            assert_eq!(
+                gives_check,
+                board.is_in_check(side.opposite()),
+                "{fen}: {mv}"
+            );
+            board.unmake_move();
+        };
+
+        for fen in fens {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            let side = board.game_state.side_to_move;
+
+            for mv in board.generate_all_legal_moves_to_vec(side) {
+                assert_gives_check(&mut board, mv, fen);
+                board.make_move(mv);
+
+                for reply in board.generate_all_legal_moves_to_vec(side.opposite()) {
+                    assert_gives_check(&mut board, reply, fen);
+                }
+
+                board.unmake_move();
+            }
+        }
+
+        let mut board = fen_parser::parse_fen_string("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let castle = Move::parse("e1g1", &mut board).unwrap();
+        assert!(board.gives_check(castle));
+
+        let mut board = fen_parser::parse_fen_string("8/8/8/R2pP2k/8/8/8/4K3 w - d6 0 1").unwrap();
+        let en_passant = Move::parse("e5d6", &mut board).unwrap();
+        assert!(board.gives_check(en_passant));
+    }
+
+    #[test]
+    fn test_attackers_to() {
+        let board =
+            fen_parser::parse_fen_string("4k3/8/8/3p4/8/2N3N1/2B5/R3K2q w - - 0 1").unwrap();
+
+        // The knights and the bishop hit e4, so do the d5 pawn and the queen on the diagonal
+        assert_eq!(
+            board.attackers_to(Square::E4, Side::White),
+            Square::C3.bit() | Square::G3.bit() | Square::C2.bit()
+        );
+        assert_eq!(
+            board.attackers_to(Square::E4, Side::Black),
+            Square::D5.bit() | Square::H1.bit()
+        );
+
+        // Four white pieces defend d1, the black queen's ray stops at the king
+        assert_eq!(
+            board.attackers_to(Square::D1, Side::White),
+            Square::A1.bit() | Square::C2.bit() | Square::C3.bit() | Square::E1.bit()
+        );
+        assert_eq!(board.attackers_to(Square::D1, Side::Black), 0);
+        assert_eq!(
+            board.attackers_to(Square::F1, Side::Black),
+            Square::H1.bit()
+        );
+    }
+
+    #[test]
+    fn test_make_unmake_chess960_castling() {
+        // The king and the king side rook swap their squares when castling king side
+        let fen = "1r3kr1/1p4p1/8/8/8/8/1P4P1/1R3KR1 w GBgb - 0 1";
+        let mut board = fen_parser::parse_fen_string(fen).unwrap();
+        let original = board.clone();
+
+        for (castling_side, king_sq, rook_sq) in [
+            (CastlingSide::KingSide, Square::G1, Square::F1),
+            (CastlingSide::QueenSide, Square::C1, Square::D1),
+        ] {
+            let mv = board
+                .castling_config
+                .get_castling_move(Side::White, castling_side);
+            assert!(
+                board
+                    .generate_all_legal_moves_to_vec(Side::White)
+                    .contains(&mv)
+            );
+
+            board.make_move(mv);
+            assert_eq!(board.get_bb(Side::White, Piece::King), king_sq.bit());
+            assert_ne!(board.get_bb(Side::White, Piece::Rook) & rook_sq.bit(), 0);
+            assert_eq!(board.get_bb(Side::White, Piece::Rook).count_ones(), 2);
+
+            board.unmake_move();
+            assert_eq!(board, original);
+        }
+    }
+
+    #[test]
+    fn test_material() {
+        let board = Board::get_start_position();
+        assert_eq!(board.piece_count(Side::White, Piece::Pawn), 8);
+        assert_eq!(board.piece_count(Side::Black, Piece::Queen), 1);
+        assert_eq!(board.material_balance(), 0);
+
+        // White is a pawn down, then a knight and a pawn up
+        let board = fen_parser::parse_fen_string(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPP1/RNBQKBNR w KQkq - 0 1",
+        )
+        .unwrap();
+        assert_eq!(board.material_balance(), -100);
+
+        let board = fen_parser::parse_fen_string(
+            "r1bqkbnr/ppppppp1/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        )
+        .unwrap();
+        assert_eq!(board.piece_count(Side::Black, Piece::Knight), 1);
+        assert_eq!(board.material_balance(), 400);
+    }
 }
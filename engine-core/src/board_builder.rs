@@ -0,0 +1,176 @@
+use crate::{
+    board::Board,
+    enums::{CastlingSide, Piece, Side, Square},
+    fen_parser::{self, ParseFenError},
+};
+
+/// Sets up a position piece by piece instead of through a FEN string. build checks the
+/// position the same way parse_fen_string does
+/// # Examples
+/// let board = Board::empty()
+///     .set_piece(Side::White, Piece::King, Square::E1)
+///     .set_piece(Side::White, Piece::Rook, Square::H1)
+///     .set_piece(Side::Black, Piece::King, Square::E8)
+///     .set_castling(Side::White, CastlingSide::KingSide)
+///     .build()?; // 4k3/8/8/8/8/8/8/4K2R w K - 0 1
+#[derive(Debug, Clone)]
+pub struct BoardBuilder {
+    board: Board,
+    /// The castling rights in the X-FEN letters, the outermost rook on the side of the
+    /// king is the castling one
+    castling: String,
+}
+
+impl Board {
+    /// Starts setting up a position on an empty board, white to move
+    pub fn empty() -> BoardBuilder {
+        let mut board = Board::default();
+        board.game_state.full_moves_count = 1;
+
+        BoardBuilder {
+            board,
+            castling: String::new(),
+        }
+    }
+}
+
+impl BoardBuilder {
+    /// Puts the piece on the square, replacing the piece standing there
+    pub fn set_piece(mut self, side: Side, piece: Piece, square: Square) -> Self {
+        for bb in self.board.bitboards.iter_mut() {
+            *bb &= !square.bit();
+        }
+        *self.board.get_bb_mut(side, piece) |= square.bit();
+
+        self
+    }
+
+    pub fn set_side_to_move(mut self, side: Side) -> Self {
+        self.board.game_state.side_to_move = side;
+
+        self
+    }
+
+    pub fn set_castling(mut self, side: Side, castling_side: CastlingSide) -> Self {
+        let ch = match castling_side {
+            CastlingSide::KingSide => 'k',
+            CastlingSide::QueenSide => 'q',
+        };
+        let ch = match side {
+            Side::White => ch.to_ascii_uppercase(),
+            Side::Black => ch,
+        };
+
+        if !self.castling.contains(ch) {
+            self.castling.push(ch);
+        }
+
+        self
+    }
+
+    pub fn set_en_passant(mut self, square: Option<Square>) -> Self {
+        self.board.game_state.en_passant_square = square;
+
+        self
+    }
+
+    /// Finishes the position, fails on the positions parse_fen_string rejects
+    pub fn build(self) -> Result<Board, ParseFenError> {
+        let mut board = self.board;
+
+        board.recalc_occupancies();
+        board.recalc_psqt_score();
+
+        if !self.castling.is_empty() {
+            fen_parser::parse_castling_rights(&mut board, &self.castling)?;
+        }
+
+        if board
+            .game_state
+            .en_passant_square
+            .is_some_and(|square| !square.can_be_en_passant())
+        {
+            return Err(ParseFenError::EnPassantSquareParse);
+        }
+
+        board.game_state.hash = board.calc_hash();
+        board.game_state.pawn_hash = board.calc_pawn_hash();
+
+        fen_parser::validate_position(&board)?;
+
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_builder() {
+        let board = Board::empty()
+            .set_piece(Side::White, Piece::King, Square::E1)
+            .set_piece(Side::White, Piece::Rook, Square::A1)
+            .set_piece(Side::White, Piece::Rook, Square::H1)
+            .set_piece(Side::White, Piece::Pawn, Square::E5)
+            .set_piece(Side::Black, Piece::King, Square::E8)
+            .set_piece(Side::Black, Piece::Pawn, Square::D5)
+            .set_castling(Side::White, CastlingSide::KingSide)
+            .set_castling(Side::White, CastlingSide::QueenSide)
+            .set_en_passant(Some(Square::D6))
+            .build()
+            .unwrap();
+        let expected =
+            fen_parser::parse_fen_string("4k3/8/8/3pP3/8/8/8/R3K2R w KQ d6 0 1").unwrap();
+
+        assert_eq!(board, expected);
+        assert_eq!(board.psqt_score, expected.psqt_score);
+        assert_eq!(board.to_fen(), expected.to_fen());
+
+        // A piece set on an occupied square replaces the one standing there
+        let board = Board::empty()
+            .set_piece(Side::White, Piece::King, Square::G1)
+            .set_piece(Side::Black, Piece::King, Square::G8)
+            .set_piece(Side::White, Piece::Queen, Square::D4)
+            .set_piece(Side::Black, Piece::Knight, Square::D4)
+            .set_side_to_move(Side::Black)
+            .build()
+            .unwrap();
+        assert_eq!(board.to_fen(), "6k1/8/8/8/3n4/8/8/6K1 b - - 0 1");
+    }
+
+    #[test]
+    fn test_board_builder_rejects_invalid_positions() {
+        let kings = Board::empty()
+            .set_piece(Side::White, Piece::King, Square::E1)
+            .set_piece(Side::Black, Piece::King, Square::E8);
+
+        assert!(matches!(
+            Board::empty().build(),
+            Err(ParseFenError::InvalidKingCount)
+        ));
+        assert!(matches!(
+            kings
+                .clone()
+                .set_piece(Side::White, Piece::Pawn, Square::A8)
+                .build(),
+            Err(ParseFenError::PawnOnBackRank)
+        ));
+        assert!(matches!(
+            kings
+                .clone()
+                .set_piece(Side::White, Piece::Rook, Square::E4)
+                .build(),
+            Err(ParseFenError::OppositeSideInCheck)
+        ));
+        assert!(matches!(
+            kings.clone().set_en_passant(Some(Square::E4)).build(),
+            Err(ParseFenError::EnPassantSquareParse)
+        ));
+
+        assert_eq!(
+            kings.build().unwrap().to_fen(),
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1"
+        );
+    }
+}
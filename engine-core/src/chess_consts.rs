@@ -14,6 +14,9 @@ pub(crate) const MAX_HALF_MOVES_COUNT: u8 = 100;
 
 pub(crate) const EMPTY_BB: u64 = 0u64;
 
+/// Bitboard of the dark squares, a1 is one of them
+pub(crate) const DARK_SQUARES_BB: u64 = 0xAA55_AA55_AA55_AA55;
+
 #[allow(dead_code)]
 pub(crate) mod fen_strings {
     pub(crate) const EMPTY_BOARD_FEN: &str = "8/8/8/8/8/8/8/8 w - -";
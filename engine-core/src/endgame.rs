@@ -0,0 +1,182 @@
+use crate::{
+    board::Board,
+    enums::{File, Piece, Side, Square},
+};
+
+/// Added to the evaluation of the endgames that are won for sure, far above any material
+/// balance and well below the mate scores
+pub(crate) const KNOWN_WIN: i32 = 10_000;
+
+/// Mop-up weights: driving the lone king to the edge and walking the own king up to it
+const EDGE_DISTANCE_WEIGHT: i32 = 20;
+const KINGS_DISTANCE_WEIGHT: i32 = 10;
+const MAX_KINGS_MANHATTAN_DISTANCE: i32 = 14;
+
+/// Corrects the evaluation of the endgames against a lone king, where the general terms
+/// don't know how to make progress or call a draw a win. score and the result are
+/// from white's perspective, None if the position is not one of these endgames
+/// # Examples
+/// KQ vs K, KR vs K and more material with a rook or a queen -> won, the closer the
+/// lone king is to the edge and to the other king the better
+/// KP vs K with the pawn out of the lone king's square -> won
+/// KP vs K with a rook pawn and the lone king in front of it -> draw
+pub(crate) fn evaluate_known_endgame(board: &Board, score: i32) -> Option<i32> {
+    let strong_side = Side::all().find(|&side| {
+        board.get_occupancy_bb(side.opposite()) == board.get_bb(side.opposite(), Piece::King)
+    })?;
+    let sign = if strong_side == Side::White { 1 } else { -1 };
+
+    let strong_bb = |piece| board.get_bb(strong_side, piece);
+    let pieces_count = board.get_occupancy_bb(strong_side).count_ones();
+
+    if strong_bb(Piece::Queen) | strong_bb(Piece::Rook) != 0 {
+        return Some(score + sign * (KNOWN_WIN + mop_up(board, strong_side)));
+    }
+
+    if pieces_count == 2 && strong_bb(Piece::Pawn) != 0 {
+        return match evaluate_kpk(board, strong_side) {
+            KpkResult::Win => Some(score + sign * KNOWN_WIN),
+            KpkResult::Draw => Some(0),
+            KpkResult::Unknown => None,
+        };
+    }
+
+    None
+}
+
+/// Rewards pushing the lone king to the edge of the board and closing in with the king,
+/// the mating patterns need both
+fn mop_up(board: &Board, strong_side: Side) -> i32 {
+    let strong_king = board.get_king_square(strong_side);
+    let weak_king = board.get_king_square(strong_side.opposite());
+
+    let file = weak_king.file().index() as i32;
+    let rank = weak_king.rank().index() as i32;
+    let center_distance = (3 - file).max(file - 4) + (3 - rank).max(rank - 4);
+
+    let kings_distance = strong_king.manhattan_distance(weak_king) as i32;
+
+    EDGE_DISTANCE_WEIGHT * center_distance
+        + KINGS_DISTANCE_WEIGHT * (MAX_KINGS_MANHATTAN_DISTANCE - kings_distance)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KpkResult {
+    Win,
+    Draw,
+    Unknown,
+}
+
+/// Decides the KP vs K endings that follow from the rule of the square, the key squares
+/// and the rook pawn blocked by the lone king, the rest is left to the search
+fn evaluate_kpk(board: &Board, strong_side: Side) -> KpkResult {
+    let weak_side = strong_side.opposite();
+    let strong_to_move = board.game_state.side_to_move == strong_side;
+
+    // Everything is seen from the strong side, as if its pawn was going up the board
+    let pawn = relative_square(
+        unsafe {
+            Square::from_u8_unchecked(board.get_bb(strong_side, Piece::Pawn).trailing_zeros() as u8)
+        },
+        strong_side,
+    );
+    let strong_king = relative_square(board.get_king_square(strong_side), strong_side);
+    let weak_king = relative_square(board.get_king_square(weak_side), strong_side);
+
+    let pawn_file = pawn.file().index();
+    let pawn_rank = pawn.rank().index();
+    let promotion_square = unsafe { Square::from_u8_unchecked(56 + pawn_file) };
+    let is_rook_pawn = pawn.file() == File::A || pawn.file() == File::H;
+
+    // The lone king on the file in front of a rook pawn can't be driven away
+    if is_rook_pawn && weak_king.file() == pawn.file() && weak_king.rank().index() > pawn_rank {
+        return KpkResult::Draw;
+    }
+
+    // The pawn is lost if the lone king takes it right away
+    if !strong_to_move
+        && weak_king.chebyshev_distance(pawn) == 1
+        && strong_king.chebyshev_distance(pawn) > 1
+    {
+        return KpkResult::Unknown;
+    }
+
+    // Rule of the square: the lone king can't catch the pawn, and the own king is not in
+    // its way
+    let pawn_moves = 7 - pawn_rank - u8::from(pawn_rank == 1);
+    let weak_king_moves = weak_king
+        .chebyshev_distance(promotion_square)
+        .saturating_sub(u8::from(!strong_to_move));
+    let king_blocks_pawn =
+        strong_king.file() == pawn.file() && strong_king.rank().index() > pawn_rank;
+
+    if weak_king_moves > pawn_moves && !king_blocks_pawn {
+        return KpkResult::Win;
+    }
+
+    // Key squares: two ranks in front of the pawn on the own and the adjacent files, and
+    // one rank in front too once the pawn crossed the middle of the board
+    if !is_rook_pawn && pawn_rank < 6 {
+        let strong_king_file = strong_king.file().index();
+        let strong_king_rank = strong_king.rank().index();
+
+        let on_key_rank = strong_king_rank == pawn_rank + 2
+            || (pawn_rank >= 4 && strong_king_rank == pawn_rank + 1);
+
+        if on_key_rank && strong_king_file.abs_diff(pawn_file) <= 1 {
+            return KpkResult::Win;
+        }
+    }
+
+    KpkResult::Unknown
+}
+
+/// The square as seen by the side, black's squares are mirrored vertically
+fn relative_square(square: Square, side: Side) -> Square {
+    match side {
+        Side::White => square,
+        Side::Black => unsafe { Square::from_u8_unchecked(square.index() ^ 56) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{evaluation, fen_parser};
+
+    use super::*;
+
+    fn eval(fen: &str) -> i32 {
+        let board = fen_parser::parse_fen_string(fen).unwrap();
+
+        evaluation::evalute(&board, Side::White)
+    }
+
+    #[test]
+    fn test_mop_up() {
+        let centered = eval("8/8/8/3k4/8/8/2Q5/4K3 w - - 0 1");
+        let cornered = eval("k7/8/8/8/8/8/2Q5/4K3 w - - 0 1");
+        let approached = eval("k7/8/2K5/8/8/8/2Q5/8 w - - 0 1");
+
+        assert!(centered > KNOWN_WIN);
+        assert!(cornered > centered);
+        assert!(approached > cornered);
+
+        // More material is still better, and the same holds for black
+        assert!(eval("k7/8/2K5/8/8/8/2Q5/7R w - - 0 1") > approached);
+        assert!(eval("r3k3/8/8/8/8/8/8/7K w - - 0 1") < -KNOWN_WIN);
+    }
+
+    #[test]
+    fn test_kpk() {
+        // The black king is out of the square with white to move, inside with black to move
+        assert!(eval("k7/8/8/4P3/8/8/8/K7 w - - 0 1") > KNOWN_WIN);
+        assert!(eval("k7/8/8/4P3/8/8/8/K7 b - - 0 1") < KNOWN_WIN);
+
+        // The king on a key square, and the same for black
+        assert!(eval("3k4/8/3K4/8/3P4/8/8/8 b - - 0 1") > KNOWN_WIN);
+        assert!(eval("8/8/8/3p4/8/3k4/8/3K4 w - - 0 1") < -KNOWN_WIN);
+
+        // A rook pawn with the black king in front of it
+        assert_eq!(eval("k7/8/8/P7/8/1K6/8/8 w - - 0 1"), 0);
+    }
+}
@@ -1,21 +1,15 @@
-use bitflags;
 use std::fmt;
 
 use crate::chess_consts;
 
 #[repr(u8)]
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub(crate) enum Side {
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Side {
+    #[default]
     White,
     Black,
 }
 
-impl Default for Side {
-    fn default() -> Self {
-        Side::White
-    }
-}
-
 impl Side {
     #[inline]
     pub(crate) const fn index(self) -> u8 {
@@ -45,11 +39,54 @@ impl Side {
             Side::Black => Rank::R1,
         }
     }
+
+    /// Rank the pieces of the side start on
+    pub(crate) fn get_back_rank(self) -> Rank {
+        self.opposite().get_promotion_rank()
+    }
+
+    /// Parses the side to move letter of a FEN string
+    /// # Examples
+    /// 'w' -> White
+    /// 'b' -> Black
+    pub fn from_char(ch: char) -> Option<Side> {
+        match ch {
+            'w' => Some(Side::White),
+            'b' => Some(Side::Black),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Side::White => 'w',
+            Side::Black => 'b',
+        }
+    }
 }
 
-impl Into<u8> for Side {
-    fn into(self) -> u8 {
-        self as u8
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl std::str::FromStr for Side {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Side::from_char(ch).ok_or(()),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<Side> for u8 {
+    fn from(val: Side) -> Self {
+        val as u8
     }
 }
 
@@ -71,7 +108,7 @@ impl TryFrom<u8> for Side {
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 #[allow(dead_code)]
 #[rustfmt::skip]
-pub(crate) enum Square {
+pub enum Square {
     A1, B1, C1, D1, E1, F1, G1, H1,
     A2, B2, C2, D2, E2, F2, G2, H2,
     A3, B3, C3, D3, E3, F3, G3, H3,
@@ -99,8 +136,13 @@ impl Square {
     }
 
     #[inline]
-    pub(crate) const fn file(self) -> Rank {
-        unsafe { Rank::from_u8_unchecked(self.index() % 8) }
+    pub(crate) const fn file(self) -> File {
+        unsafe { File::from_u8_unchecked(self.index() % 8) }
+    }
+
+    #[inline]
+    pub(crate) const fn from_file_rank(file: File, rank: Rank) -> Square {
+        unsafe { Square::from_u8_unchecked(rank.index() * 8 + file.index()) }
     }
 
     #[inline]
@@ -135,6 +177,51 @@ impl Square {
         }
     }
 
+    /// Number of king moves between the squares
+    /// # Examples
+    /// A1 H8 -> 7
+    /// E4 F6 -> 2
+    pub(crate) const fn chebyshev_distance(self, other: Square) -> u8 {
+        let file_distance = self.file().index().abs_diff(other.file().index());
+        let rank_distance = self.rank().index().abs_diff(other.rank().index());
+
+        if file_distance > rank_distance {
+            file_distance
+        } else {
+            rank_distance
+        }
+    }
+
+    /// Number of rook steps between the squares
+    /// # Examples
+    /// A1 H8 -> 14
+    /// E4 F6 -> 3
+    pub(crate) const fn manhattan_distance(self, other: Square) -> u8 {
+        self.file().index().abs_diff(other.file().index())
+            + self.rank().index().abs_diff(other.rank().index())
+    }
+
+    /// Shifts the square by the given number of files and ranks, None if it leaves the board
+    /// # Examples
+    /// E4 1 2 -> Some(F6)
+    /// H4 1 0 -> None
+    pub(crate) const fn offset(self, df: i8, dr: i8) -> Option<Square> {
+        let file = self.file().index() as i8 + df;
+        let rank = self.rank().index() as i8 + dr;
+
+        if file < 0
+            || file >= chess_consts::BOARD_SIZE as i8
+            || rank < 0
+            || rank >= chess_consts::BOARD_SIZE as i8
+        {
+            return None;
+        }
+
+        Some(unsafe {
+            Square::from_u8_unchecked((rank * chess_consts::BOARD_SIZE as i8 + file) as u8)
+        })
+    }
+
     /// Moves backward on one rank
     /// # Examples
     /// A2 White -> A1
@@ -151,9 +238,9 @@ impl Square {
     }
 }
 
-impl Into<u8> for Square {
-    fn into(self) -> u8 {
-        self.index()
+impl From<Square> for u8 {
+    fn from(val: Square) -> Self {
+        val.index()
     }
 }
 
@@ -162,7 +249,7 @@ impl TryFrom<u8> for Square {
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         if value < 64 {
-            Ok(unsafe { std::mem::transmute(value) })
+            Ok(unsafe { Square::from_u8_unchecked(value) })
         } else {
             Err(())
         }
@@ -215,7 +302,7 @@ impl fmt::Display for Square {
 }
 
 #[repr(u8)]
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[allow(dead_code)]
 #[rustfmt::skip]
 pub(crate) enum File { A=0, B=1, C=2, D=3, E=4, F=5, G=6, H=7 }
@@ -225,8 +312,7 @@ impl File {
         self as u8
     }
 
-    #[allow(dead_code)]
-    pub(crate) const unsafe fn from_u8_unchecked(value: u8) -> Rank {
+    pub(crate) const unsafe fn from_u8_unchecked(value: u8) -> File {
         unsafe { std::mem::transmute(value) }
     }
 }
@@ -236,7 +322,7 @@ impl TryFrom<u8> for File {
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         if value < 8 {
-            Ok(unsafe { std::mem::transmute(value) })
+            Ok(unsafe { File::from_u8_unchecked(value) })
         } else {
             Err(())
         }
@@ -264,7 +350,7 @@ impl TryFrom<u8> for Rank {
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         if value < 8 {
-            Ok(unsafe { std::mem::transmute(value) })
+            Ok(unsafe { Rank::from_u8_unchecked(value) })
         } else {
             Err(())
         }
@@ -274,7 +360,7 @@ impl TryFrom<u8> for Rank {
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[rustfmt::skip]
-pub(crate) enum Piece {Pawn, Knight, Bishop, Rook, Queen, King}
+pub enum Piece {Pawn, Knight, Bishop, Rook, Queen, King}
 
 impl Piece {
     pub(crate) const PROMOTION_PIECES: [Piece; 4] =
@@ -291,10 +377,64 @@ impl Piece {
     pub(crate) fn all() -> impl Iterator<Item = Piece> {
         (0..6).map(|v| unsafe { Piece::from_u8_unchecked(v) })
     }
+
+    /// Letters of the white pieces, the black pieces use the lowercase ones
+    const CHARS: [char; chess_consts::PIECE_TYPES_COUNT] = ['P', 'N', 'B', 'R', 'Q', 'K'];
+
+    /// Parses a FEN piece letter, uppercase letters are white pieces
+    /// # Examples
+    /// 'N' -> (White, Knight)
+    /// 'q' -> (Black, Queen)
+    pub fn from_char(ch: char) -> Option<(Side, Piece)> {
+        let index = Piece::CHARS
+            .iter()
+            .position(|&piece_ch| piece_ch == ch.to_ascii_uppercase())?;
+        let side = if ch.is_ascii_uppercase() {
+            Side::White
+        } else {
+            Side::Black
+        };
+
+        Some((side, unsafe { Piece::from_u8_unchecked(index as u8) }))
+    }
+
+    /// FEN letter of the piece, uppercase for white
+    /// # Examples
+    /// Knight White -> 'N'
+    /// Queen Black -> 'q'
+    pub fn to_char(self, side: Side) -> char {
+        let ch = Piece::CHARS[self.index() as usize];
+
+        match side {
+            Side::White => ch,
+            Side::Black => ch.to_ascii_lowercase(),
+        }
+    }
+}
+
+impl fmt::Display for Piece {
+    /// Writes the uppercase letter of the piece, as in SAN
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char(Side::White))
+    }
+}
+
+impl std::str::FromStr for Piece {
+    type Err = ();
+
+    /// Accepts the letter of the piece in either case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Piece::from_char(ch).map(|(_, piece)| piece).ok_or(()),
+            _ => Err(()),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub(crate) enum Move {
+pub enum Move {
     Normal {
         from: Square,
         to: Square,
@@ -325,81 +465,65 @@ impl Move {
         matches!(self, Move::Normal { promo: Some(_), .. })
     }
 
-    pub(crate) fn get_castling_move(side: Side, castling_side: CastlingSide) -> Move {
-        let (from, to) = CastlingSide::get_castling_positions(side, Piece::King, castling_side);
-
-        Move::Castle {
-            from: from,
-            to: to,
-            side: castling_side,
+    pub(crate) fn get_from_to(&self) -> (Square, Square) {
+        match self {
+            Move::Normal { from, to, .. } => (*from, *to),
+            &Move::Castle { from, to, .. } => (from, to),
         }
     }
 
-    pub(crate) fn get_from_to(&self) -> (Square, Square) {
+    /// The moving piece, the king for castlings
+    pub(crate) fn get_piece(&self) -> Piece {
         match self {
-            Move::Normal { from, to, .. } => return (*from, *to),
-            &Move::Castle { from, to, .. } => return (from, to),
+            Move::Normal { piece, .. } => *piece,
+            Move::Castle { .. } => Piece::King,
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    /// Writes the move in UCI coordinate notation, castlings are written as the king's
+    /// move, use uci::serialize_board_move_to_uci_str for Chess960 output
+    /// # Examples
+    /// e2e4, a7a8q, e1g1
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Move::Normal {
+                from, to, promo, ..
+            } => {
+                write!(f, "{from}{to}")?;
+
+                // UCI writes the promotion piece in lowercase
+                if let Some(promo_piece) = promo {
+                    write!(f, "{}", promo_piece.to_char(Side::Black))?;
+                }
+
+                Ok(())
+            }
+            Move::Castle { from, to, .. } => write!(f, "{from}{to}"),
         }
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub(crate) enum CastlingSide {
+pub enum CastlingSide {
     KingSide,
     QueenSide,
 }
 
 impl CastlingSide {
-    pub(crate) const WHITE_KING_SIDE_EMPTY_MASK: u64 = Square::F1.bit() | Square::G1.bit();
-    pub(crate) const WHITE_KING_SIDE_NOT_ATTACKED_MASK: u64 =
-        CastlingSide::WHITE_KING_SIDE_EMPTY_MASK | Square::E1.bit();
-
-    pub(crate) const WHITE_QUEEN_SIDE_EMPTY_MASK: u64 =
-        Square::B1.bit() | Square::C1.bit() | Square::D1.bit();
-    pub(crate) const WHITE_QUEEN_SIDE_NOT_ATTACKED_MASK: u64 =
-        Square::C1.bit() | Square::D1.bit() | Square::E1.bit();
-
-    pub(crate) const BLACK_KING_SIDE_EMPTY_MASK: u64 = Square::F8.bit() | Square::G8.bit();
-    pub(crate) const BLACK_KING_SIDE_NOT_ATTACKED_MASK: u64 =
-        CastlingSide::BLACK_KING_SIDE_EMPTY_MASK | Square::E8.bit();
-
-    pub(crate) const BLACK_QUEEN_SIDE_EMPTY_MASK: u64 =
-        Square::B8.bit() | Square::C8.bit() | Square::D8.bit();
-    pub(crate) const BLACK_QUEEN_SIDE_NOT_ATTACKED_MASK: u64 =
-        Square::C8.bit() | Square::D8.bit() | Square::E8.bit();
-
-    pub(crate) fn get_castling_positions(
-        side: Side,
-        piece: Piece,
-        castling_side: CastlingSide,
-    ) -> (Square, Square) {
-        if !&[Piece::King, Piece::Rook].contains(&piece) {
-            panic!("Wrong piece type");
-        }
+    pub(crate) const fn index(self) -> u8 {
+        self as u8
+    }
 
-        if side == Side::White {
-            match (piece, castling_side) {
-                (Piece::King, CastlingSide::KingSide) => return (Square::E1, Square::G1),
-                (Piece::King, CastlingSide::QueenSide) => return (Square::E1, Square::C1),
-                (Piece::Rook, CastlingSide::KingSide) => return (Square::H1, Square::F1),
-                (Piece::Rook, CastlingSide::QueenSide) => return (Square::A1, Square::D1),
-                _ => unreachable!(),
-            }
-        } else {
-            match (piece, castling_side) {
-                (Piece::King, CastlingSide::KingSide) => return (Square::E8, Square::G8),
-                (Piece::King, CastlingSide::QueenSide) => return (Square::E8, Square::C8),
-                (Piece::Rook, CastlingSide::KingSide) => return (Square::H8, Square::F8),
-                (Piece::Rook, CastlingSide::QueenSide) => return (Square::A8, Square::D8),
-                _ => unreachable!(),
-            }
-        }
+    pub(crate) fn all() -> impl Iterator<Item = CastlingSide> {
+        [CastlingSide::KingSide, CastlingSide::QueenSide].into_iter()
     }
 }
 
 bitflags::bitflags! {
     #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
-    pub(crate) struct MoveFlags: u8 {
+    pub struct MoveFlags: u8 {
         const NONE        = 0;
         const EN_PASSANT  = 1 << 0;
         const DOUBLE_PUSH = 1 << 1;
@@ -456,4 +580,42 @@ mod tests {
     fn test_move_size() {
         println!("Move size: {}", std::mem::size_of::<Move>());
     }
+
+    #[test]
+    fn test_square_geometry() {
+        assert_eq!(Square::A1.chebyshev_distance(Square::H8), 7);
+        assert_eq!(Square::E4.chebyshev_distance(Square::F6), 2);
+        assert_eq!(Square::E4.chebyshev_distance(Square::E4), 0);
+
+        assert_eq!(Square::A1.manhattan_distance(Square::H8), 14);
+        assert_eq!(Square::F6.manhattan_distance(Square::E4), 3);
+
+        assert_eq!(Square::E4.offset(1, 2), Some(Square::F6));
+        assert_eq!(Square::E4.offset(-4, -3), Some(Square::A1));
+        assert_eq!(Square::H4.offset(1, 0), None);
+        assert_eq!(Square::A8.offset(0, 1), None);
+        assert_eq!(Square::B1.offset(-2, 1), None);
+    }
+
+    #[test]
+    fn test_piece_and_side_chars() {
+        for side in Side::all() {
+            for piece in Piece::all() {
+                assert_eq!(Piece::from_char(piece.to_char(side)), Some((side, piece)));
+            }
+
+            assert_eq!(side.to_string().parse(), Ok(side));
+        }
+
+        assert_eq!(Piece::from_char('n'), Some((Side::Black, Piece::Knight)));
+        assert_eq!(Piece::from_char('x'), None);
+        assert_eq!(Piece::from_char('1'), None);
+
+        assert_eq!(Piece::Queen.to_string(), "Q");
+        assert_eq!("q".parse(), Ok(Piece::Queen));
+        assert!("qq".parse::<Piece>().is_err());
+
+        assert_eq!(Side::from_char('b'), Some(Side::Black));
+        assert!("white".parse::<Side>().is_err());
+    }
 }
@@ -0,0 +1,212 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{board::Board, enums::Move, fen_parser};
+
+const EPD_FEN_PARTS_COUNT: usize = 4;
+const BEST_MOVE_OPCODE: &str = "bm";
+const AVOID_MOVE_OPCODE: &str = "am";
+const ID_OPCODE: &str = "id";
+
+#[derive(Debug)]
+pub(crate) enum ParseEpdError {
+    IncorrectPartsLength,
+    Fen(fen_parser::ParseFenError),
+    UnterminatedString,
+    MoveParse(String),
+}
+
+impl Display for ParseEpdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseEpdError::IncorrectPartsLength => {
+                write!(f, "Error in EPD string: Must contain at least 4 parts")
+            }
+            ParseEpdError::Fen(e) => write!(f, "{e}"),
+            ParseEpdError::UnterminatedString => {
+                write!(f, "Error in EPD string: Unterminated quoted operand")
+            }
+            ParseEpdError::MoveParse(mv) => {
+                write!(f, "Error in EPD string: Failed to parse move {mv}")
+            }
+        }
+    }
+}
+
+/// A position from a test suite with its operations, the "bm" and "am" moves are
+/// resolved against the position
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub(crate) struct EpdRecord {
+    pub(crate) board: Board,
+    pub(crate) operations: HashMap<String, Vec<String>>,
+    pub(crate) best_moves: Vec<Move>,
+    pub(crate) avoid_moves: Vec<Move>,
+    pub(crate) id: Option<String>,
+}
+
+/// Parses an EPD line, the first four fields are the FEN position without the move
+/// counters, they are followed by the ";" terminated operations
+/// # Examples
+/// 2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+#[allow(dead_code)]
+pub(crate) fn parse_epd_string(epd: &str) -> Result<EpdRecord, ParseEpdError> {
+    let epd = epd.trim();
+
+    let mut fen_parts = Vec::with_capacity(EPD_FEN_PARTS_COUNT);
+    let mut rest = epd;
+    for _ in 0..EPD_FEN_PARTS_COUNT {
+        let part_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (part, tail) = rest.split_at(part_end);
+
+        if part.is_empty() {
+            return Err(ParseEpdError::IncorrectPartsLength);
+        }
+
+        fen_parts.push(part);
+        rest = tail.trim_start();
+    }
+
+    let mut board =
+        fen_parser::parse_fen_string(&fen_parts.join(" ")).map_err(ParseEpdError::Fen)?;
+    let operations = parse_operations(rest)?;
+
+    let mut resolve_moves = |opcode: &str| -> Result<Vec<Move>, ParseEpdError> {
+        operations
+            .get(opcode)
+            .into_iter()
+            .flatten()
+            .map(|san| {
+                board
+                    .parse_san(san)
+                    .ok_or_else(|| ParseEpdError::MoveParse(san.clone()))
+            })
+            .collect()
+    };
+
+    let best_moves = resolve_moves(BEST_MOVE_OPCODE)?;
+    let avoid_moves = resolve_moves(AVOID_MOVE_OPCODE)?;
+    let id = operations
+        .get(ID_OPCODE)
+        .and_then(|operands| operands.first())
+        .cloned();
+
+    Ok(EpdRecord {
+        board,
+        operations,
+        best_moves,
+        avoid_moves,
+        id,
+    })
+}
+
+/// Splits the operations into their opcodes and operands, quoted operands may
+/// contain spaces and semicolons
+fn parse_operations(s: &str) -> Result<HashMap<String, Vec<String>>, ParseEpdError> {
+    let mut operations = HashMap::new();
+
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut in_quotes = false;
+
+    let finish_token = |token: &mut String, tokens: &mut Vec<String>| {
+        if !token.is_empty() {
+            tokens.push(std::mem::take(token));
+        }
+    };
+
+    for ch in s.chars() {
+        match ch {
+            '"' if in_quotes => {
+                tokens.push(std::mem::take(&mut token));
+                in_quotes = false;
+            }
+            '"' => {
+                finish_token(&mut token, &mut tokens);
+                in_quotes = true;
+            }
+            _ if in_quotes => token.push(ch),
+            ';' => {
+                finish_token(&mut token, &mut tokens);
+
+                let mut operation = std::mem::take(&mut tokens).into_iter();
+                if let Some(opcode) = operation.next() {
+                    operations.insert(opcode, operation.collect());
+                }
+            }
+            _ if ch.is_whitespace() => finish_token(&mut token, &mut tokens),
+            _ => token.push(ch),
+        }
+    }
+
+    if in_quotes {
+        return Err(ParseEpdError::UnterminatedString);
+    }
+
+    // The last operation may miss its terminating semicolon
+    finish_token(&mut token, &mut tokens);
+    let mut operation = tokens.into_iter();
+    if let Some(opcode) = operation.next() {
+        operations.insert(opcode, operation.collect());
+    }
+
+    Ok(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_epd_string() {
+        let record = parse_epd_string(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";",
+        )
+        .unwrap();
+
+        assert_eq!(
+            record.board.to_fen(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+        );
+        assert_eq!(
+            record
+                .best_moves
+                .iter()
+                .map(|mv| mv.to_string())
+                .collect::<Vec<_>>(),
+            ["g3g6"]
+        );
+        assert!(record.avoid_moves.is_empty());
+        assert_eq!(record.id.as_deref(), Some("WAC.001"));
+    }
+
+    #[test]
+    fn test_parse_epd_operations() {
+        let record = parse_epd_string(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - \
+             am Qe2 Ng5; bm Bb5 Bc4; c0 \"quoted; with spaces\"; acd 12",
+        )
+        .unwrap();
+
+        assert_eq!(record.best_moves.len(), 2);
+        assert_eq!(record.avoid_moves.len(), 2);
+        assert_eq!(record.id, None);
+        assert_eq!(record.operations["c0"], ["quoted; with spaces"]);
+        assert_eq!(record.operations["acd"], ["12"]);
+    }
+
+    #[test]
+    fn test_parse_invalid_epd_string() {
+        assert!(matches!(
+            parse_epd_string("8/8/8/8/8/8/8/8 w"),
+            Err(ParseEpdError::IncorrectPartsLength)
+        ));
+        assert!(matches!(
+            parse_epd_string("4k3/8/8/8/8/8/8/4K3 w - - bm Qd2;"),
+            Err(ParseEpdError::MoveParse(_))
+        ));
+        assert!(matches!(
+            parse_epd_string("4k3/8/8/8/8/8/8/4K3 w - - id \"open;"),
+            Err(ParseEpdError::UnterminatedString)
+        ));
+    }
+}
@@ -1,15 +1,54 @@
-use std::sync::atomic::Ordering;
+use std::fmt::Display;
 
 use crate::{
     board::Board,
-    enums::{Piece, Side},
+    chess_consts, endgame,
+    enums::{File, Move, Piece, Rank, Side, Square},
     helpers,
-    move_generator::MoveBuffer,
-    move_ordering, searching,
+    king_attack_table::get_king_attacks_mask,
+    knight_attack_table::get_knight_attacks_mask,
+    move_generator::{MoveBuffer, MoveGenMode},
+    pawn_attack_table::get_pawn_attacks_mask,
+    pawn_hash,
+    searching::SearchContext,
+    sliding_piece_attack_table::{
+        get_bishop_attacks_mask, get_queen_attacks_mask, get_rook_attacks_mask,
+    },
 };
 
 pub(crate) const MATE_EVALUATION: i32 = 30_000;
 
+/// Scores at least this far from 0 are mates found within the search
+pub(crate) const MATE_THRESHOLD: i32 = MATE_EVALUATION - chess_consts::MAX_PLY as i32;
+
+/// Score of the side to move mated at the given distance from the search root. A closer
+/// mate scores lower, so the winning side goes for the shortest mate and the losing
+/// side for the longest defense
+pub(crate) const fn mated_in(ply: u32) -> i32 {
+    -MATE_EVALUATION + ply as i32
+}
+
+pub(crate) const fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_THRESHOLD
+}
+
+/// Static evaluations are clamped to -MAX_EVALUATION..=MAX_EVALUATION, well below the
+/// mate scores, so that no sum of the terms is taken for a mate. The scores are laid out
+/// as: evaluations up to MAX_EVALUATION, the known wins of the endgames inside them, the
+/// mates from MATE_THRESHOLD to MATE_EVALUATION and the search bounds far above
+pub(crate) const MAX_EVALUATION: i32 = 20_000;
+
+/// Game phase with all the pieces on the board, see calc_phase
+pub(crate) const MAX_PHASE: i32 = 24;
+
+/// Plies of captures searched past the horizon, deeper positions get the static
+/// evaluation
+const MAX_QUIESCENCE_PLY: u32 = 16;
+
+/// Safety margin of delta pruning in quiescence, covers the positional swing of a capture
+pub(crate) const DELTA_PRUNING_MARGIN: i32 = 200;
+
+/// The default material values of EvalParams, the engine reads them from the params
 mod piece_scores {
 
     use crate::enums::{Piece, Side};
@@ -27,6 +66,14 @@ mod piece_scores {
     pub(super) const WHITE_KING_SCORE: i32 = 10_000;
     pub(super) const BLACK_KING_SCORE: i32 = -10_000;
 
+    pub(super) const ENDGAME_PAWN_SCORE: i32 = 120;
+    pub(super) const ENDGAME_KNIGHT_SCORE: i32 = 280;
+    pub(super) const ENDGAME_BISHOP_SCORE: i32 = 320;
+    pub(super) const ENDGAME_ROOK_SCORE: i32 = 550;
+    pub(super) const ENDGAME_QUEEN_SCORE: i32 = 1000;
+    pub(super) const ENDGAME_KING_SCORE: i32 = 10_000;
+
+    /// Material value of the piece, negative for black
     pub(super) fn get_piece_score(piece: Piece, side: Side) -> i32 {
         if side == Side::White {
             match piece {
@@ -48,14 +95,173 @@ mod piece_scores {
             }
         }
     }
+
+    pub(super) fn get_endgame_piece_score(piece: Piece, side: Side) -> i32 {
+        let score = match piece {
+            Piece::Pawn => ENDGAME_PAWN_SCORE,
+            Piece::Knight => ENDGAME_KNIGHT_SCORE,
+            Piece::Bishop => ENDGAME_BISHOP_SCORE,
+            Piece::Rook => ENDGAME_ROOK_SCORE,
+            Piece::Queen => ENDGAME_QUEEN_SCORE,
+            Piece::King => ENDGAME_KING_SCORE,
+        };
+
+        if side == Side::White { score } else { -score }
+    }
+}
+
+mod mobility {
+    use crate::enums::Piece;
+
+    use super::TaperedScore;
+
+    pub(super) const KNIGHT_MOBILITY_WEIGHT: TaperedScore = TaperedScore::new(4, 4);
+    pub(super) const BISHOP_MOBILITY_WEIGHT: TaperedScore = TaperedScore::new(5, 5);
+    pub(super) const ROOK_MOBILITY_WEIGHT: TaperedScore = TaperedScore::new(2, 4);
+    pub(super) const QUEEN_MOBILITY_WEIGHT: TaperedScore = TaperedScore::new(1, 2);
+
+    /// Returns the score of a single reachable square for the piece, pawns and kings have no mobility term
+    pub(super) fn get_mobility_weight(piece: Piece) -> Option<TaperedScore> {
+        match piece {
+            Piece::Knight => Some(KNIGHT_MOBILITY_WEIGHT),
+            Piece::Bishop => Some(BISHOP_MOBILITY_WEIGHT),
+            Piece::Rook => Some(ROOK_MOBILITY_WEIGHT),
+            Piece::Queen => Some(QUEEN_MOBILITY_WEIGHT),
+            Piece::Pawn | Piece::King => None,
+        }
+    }
 }
 
-mod pst_tables {
+mod pawn_structure {
+    use crate::{
+        chess_consts,
+        enums::{File, Side, Square},
+        helpers,
+    };
+
+    use super::TaperedScore;
+
+    pub(super) const DOUBLED_PAWN_PENALTY: TaperedScore = TaperedScore::new(-10, -20);
+    pub(super) const ISOLATED_PAWN_PENALTY: TaperedScore = TaperedScore::new(-10, -15);
+
+    /// Passed pawn bonus indexed by the rank relative to the pawn's side
+    pub(super) const PASSED_PAWN_BONUS: [TaperedScore; chess_consts::BOARD_SIZE] = [
+        TaperedScore::new(0, 0),
+        TaperedScore::new(5, 10),
+        TaperedScore::new(10, 20),
+        TaperedScore::new(20, 35),
+        TaperedScore::new(35, 60),
+        TaperedScore::new(60, 100),
+        TaperedScore::new(100, 150),
+        TaperedScore::new(0, 0),
+    ];
+
+    /// Returns a bitboard with the files next to the given one set
+    /// # Examples
+    /// A -> B file
+    /// E -> D and F files
+    pub(super) fn adjacent_files_mask(file: File) -> u64 {
+        let file_bb = helpers::file_mask(file);
+
+        ((file_bb & chess_consts::NOT_A_FILE_BB) >> 1)
+            | ((file_bb & chess_consts::NOT_H_FILE_BB) << 1)
+    }
+
+    /// Returns a bitboard with all squares of the ranks in front of the square from side's point of view
+    pub(super) fn forward_ranks_mask(square: Square, side: Side) -> u64 {
+        let rank = square.rank().index() as u32;
+
+        match side {
+            Side::White => u64::MAX
+                .checked_shl((rank + 1) * chess_consts::BOARD_SIZE as u32)
+                .unwrap_or(chess_consts::EMPTY_BB),
+            Side::Black => (1u64 << (rank * chess_consts::BOARD_SIZE as u32)) - 1,
+        }
+    }
+
+    /// Returns a bitboard with squares that must be free of enemy pawns for a pawn to be passed
+    pub(super) fn passed_pawn_mask(square: Square, side: Side) -> u64 {
+        let file = square.file();
+
+        forward_ranks_mask(square, side) & (helpers::file_mask(file) | adjacent_files_mask(file))
+    }
+}
+
+mod piece_bonuses {
+    use super::TaperedScore;
+
+    pub(super) const BISHOP_PAIR_BONUS: TaperedScore = TaperedScore::new(30, 50);
+
+    /// Bonus for a rook on a file without pawns of either side
+    pub(super) const ROOK_OPEN_FILE_BONUS: TaperedScore = TaperedScore::new(25, 10);
+
+    /// Bonus for a rook on a file without friendly pawns
+    pub(super) const ROOK_HALF_OPEN_FILE_BONUS: TaperedScore = TaperedScore::new(12, 5);
+}
+
+mod king_safety {
     use crate::{
         chess_consts,
         enums::{Side, Square},
+        helpers,
+    };
+
+    use super::TaperedScore;
+
+    // King safety matters only while there are enough pieces to attack the king,
+    // so the endgame parts are zero and the term fades out with the phase
+    pub(super) const MISSING_SHIELD_PAWN_PENALTY: TaperedScore = TaperedScore::new(-25, 0);
+    pub(super) const ADVANCED_SHIELD_PAWN_PENALTY: TaperedScore = TaperedScore::new(-10, 0);
+
+    /// Penalty indexed by the number of enemy pieces attacking the king zone
+    pub(super) const KING_ZONE_ATTACKERS_PENALTY: [TaperedScore; 6] = [
+        TaperedScore::new(0, 0),
+        TaperedScore::new(-5, 0),
+        TaperedScore::new(-25, 0),
+        TaperedScore::new(-55, 0),
+        TaperedScore::new(-90, 0),
+        TaperedScore::new(-130, 0),
+    ];
+
+    /// Returns the rank mask `steps` ranks in front of the king from side's point of view,
+    /// empty if the rank is outside of the board
+    pub(super) fn shield_rank_mask(king_square: Square, side: Side, steps: i8) -> u64 {
+        let rank_steps = match side {
+            Side::White => steps,
+            Side::Black => -steps,
+        };
+
+        king_square
+            .offset(0, rank_steps)
+            .map_or(chess_consts::EMPTY_BB, |square| {
+                helpers::rank_mask(square.rank())
+            })
+    }
+}
+
+mod pst_tables {
+    use crate::{
+        chess_consts,
+        enums::{Piece, Side, Square},
     };
 
+    /// Returns the default midgame and endgame tables for the piece
+    pub(super) fn get_pst_tables(
+        piece: Piece,
+    ) -> (
+        &'static [i16; chess_consts::SQUARES_COUNT],
+        &'static [i16; chess_consts::SQUARES_COUNT],
+    ) {
+        match piece {
+            Piece::Pawn => (&PAWN_MIDGAME_PST_TABLE, &PAWN_ENDGAME_PST_TABLE),
+            Piece::Knight => (&KNIGHT_MIDGAME_PST_TABLE, &KNIGHT_ENDGAME_PST_TABLE),
+            Piece::Bishop => (&BISHOP_MIDGAME_PST_TABLE, &BISHOP_ENDGAME_PST_TABLE),
+            Piece::Rook => (&ROOK_MIDGAME_PST_TABLE, &ROOK_ENDGAME_PST_TABLE),
+            Piece::Queen => (&QUEEN_MIDGAME_PST_TABLE, &QUEEN_ENDGAME_PST_TABLE),
+            Piece::King => (&KING_MIDGAME_PST_TABLE, &KING_ENDGAME_PST_TABLE),
+        }
+    }
+
     pub(super) fn get_pst_value(
         table: &[i16; chess_consts::SQUARES_COUNT],
         square: Square,
@@ -71,7 +277,7 @@ mod pst_tables {
     }
 
     #[rustfmt::skip]
-    pub(super) const PAWN_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
+    pub(super) const PAWN_MIDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
      0,   0,   0,   0,   0,   0,   0,   0,
     30,  30,  30,  40,  40,  30,  30,  30,
     20,  20,  20,  30,  30,  30,  20,  20,
@@ -83,7 +289,7 @@ mod pst_tables {
  ];
 
     #[rustfmt::skip]
-    pub(super) const KNIGHT_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
+    pub(super) const KNIGHT_MIDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
      5,   0,   0,   0,   0,   0,   0,  -5,
     -5,   0,   0,  10,  10,   0,   0,  -5,
     -5,   5,  20,  20,  20,  20,   5,  -5,
@@ -95,7 +301,7 @@ mod pst_tables {
      ];
 
     #[rustfmt::skip]
-    pub(super) const BISHOP_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
+    pub(super) const BISHOP_MIDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
      0,   0,   0,   0,   0,   0,   0,   0,
      0,   0,   0,   0,   0,   0,   0,   0,
      0,   0,   0,  10,  10,   0,   0,   0,
@@ -107,7 +313,7 @@ mod pst_tables {
     ];
 
     #[rustfmt::skip]
-    pub(super) const ROOK_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
+    pub(super) const ROOK_MIDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
     50,  50,  50,  50,  50,  50,  50,  50,
     50,  50,  50,  50,  50,  50,  50,  50,
      0,   0,  10,  20,  20,  10,   0,   0,
@@ -119,7 +325,7 @@ mod pst_tables {
     ];
 
     #[rustfmt::skip]
-    pub(super) const QUEEN_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
+    pub(super) const QUEEN_MIDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
      -20,-10,-10, -5, -5,-10,-10,-20,
      -10,  0,  5,  0,  0,  0,  0,-10,
      -10,  5,  5,  5,  5,  5,  0,-10,
@@ -130,6 +336,66 @@ mod pst_tables {
      -20,-10,-10, -5, -5,-10,-10,-20
     ];
 
+    #[rustfmt::skip]
+    pub(super) const PAWN_ENDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    80,  80,  80,  80,  80,  80,  80,  80,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    30,  30,  30,  30,  30,  30,  30,  30,
+    15,  15,  15,  15,  15,  15,  15,  15,
+     5,   5,   5,   5,   5,   5,   5,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0
+    ];
+
+    #[rustfmt::skip]
+    pub(super) const KNIGHT_ENDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
+   -40, -30, -20, -20, -20, -20, -30, -40,
+   -30, -10,   0,   0,   0,   0, -10, -30,
+   -20,   0,  10,  15,  15,  10,   0, -20,
+   -20,   5,  15,  20,  20,  15,   5, -20,
+   -20,   0,  15,  20,  20,  15,   0, -20,
+   -20,   5,  10,  15,  15,  10,   5, -20,
+   -30, -10,   0,   5,   5,   0, -10, -30,
+   -40, -30, -20, -20, -20, -20, -30, -40
+    ];
+
+    #[rustfmt::skip]
+    pub(super) const BISHOP_ENDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,  10,  15,  15,  10,   5, -10,
+   -10,   5,  10,  15,  15,  10,   5, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20
+    ];
+
+    #[rustfmt::skip]
+    pub(super) const ROOK_ENDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
+    10,  10,  10,  10,  10,  10,  10,  10,
+    15,  15,  15,  15,  15,  15,  15,  15,
+     5,   5,   5,   5,   5,   5,   5,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0
+    ];
+
+    #[rustfmt::skip]
+    pub(super) const QUEEN_ENDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
+     -20,-10,-10, -5, -5,-10,-10,-20,
+     -10,  0,  5,  5,  5,  5,  0,-10,
+     -10,  5, 10, 10, 10, 10,  5,-10,
+      -5,  5, 10, 15, 15, 10,  5, -5,
+      -5,  5, 10, 15, 15, 10,  5, -5,
+     -10,  5, 10, 10, 10, 10,  5,-10,
+     -10,  0,  5,  5,  5,  5,  0,-10,
+     -20,-10,-10, -5, -5,-10,-10,-20
+    ];
+
     #[rustfmt::skip]
     pub(super) const KING_MIDGAME_PST_TABLE: [i16; chess_consts::SQUARES_COUNT] = [
      -30,-40,-40,-50,-50,-40,-40,-30,
@@ -155,72 +421,581 @@ mod pst_tables {
     ];
 }
 
-pub(crate) fn evalute(board: &Board, side: Side) -> i32 {
-    let mut score: i32 = 0;
-    let phase = calc_phase(board);
+/// Evaluation weights that can be changed at runtime, e.g. by a tuner.
+/// All arrays are indexed by piece (pawn, knight, bishop, rook, queen, king),
+/// PST arrays are laid out from white's point of view with the a8 square first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+    pub midgame_piece_values: [i32; chess_consts::PIECE_TYPES_COUNT],
+    pub endgame_piece_values: [i32; chess_consts::PIECE_TYPES_COUNT],
+    pub midgame_pst: [[i16; chess_consts::SQUARES_COUNT]; chess_consts::PIECE_TYPES_COUNT],
+    pub endgame_pst: [[i16; chess_consts::SQUARES_COUNT]; chess_consts::PIECE_TYPES_COUNT],
+}
 
-    for piece in Piece::all() {
-        let white_bb = board.get_bb(Side::White, piece);
-        let black_bb = board.get_bb(Side::Black, piece);
-
-        score += white_bb.count_ones() as i32 * piece_scores::get_piece_score(piece, Side::White);
-        score += black_bb.count_ones() as i32 * piece_scores::get_piece_score(piece, Side::Black);
-
-        let pst_table = match piece {
-            Piece::Pawn => pst_tables::PAWN_PST_TABLE,
-            Piece::Knight => pst_tables::KNIGHT_PST_TABLE,
-            Piece::Bishop => pst_tables::BISHOP_PST_TABLE,
-            Piece::Rook => pst_tables::ROOK_PST_TABLE,
-            Piece::Queen => pst_tables::QUEEN_PST_TABLE,
-            Piece::King => {
-                if (0..=10).contains(&phase) {
-                    pst_tables::KING_ENDGAME_PST_TABLE
-                } else {
-                    pst_tables::KING_MIDGAME_PST_TABLE
-                }
+impl Default for EvalParams {
+    fn default() -> Self {
+        let mut params = EvalParams {
+            midgame_piece_values: [0; chess_consts::PIECE_TYPES_COUNT],
+            endgame_piece_values: [0; chess_consts::PIECE_TYPES_COUNT],
+            midgame_pst: [[0; chess_consts::SQUARES_COUNT]; chess_consts::PIECE_TYPES_COUNT],
+            endgame_pst: [[0; chess_consts::SQUARES_COUNT]; chess_consts::PIECE_TYPES_COUNT],
+        };
+
+        for piece in Piece::all() {
+            let index = piece.index() as usize;
+            let (midgame_pst, endgame_pst) = pst_tables::get_pst_tables(piece);
+
+            params.midgame_piece_values[index] = piece_scores::get_piece_score(piece, Side::White);
+            params.endgame_piece_values[index] =
+                piece_scores::get_endgame_piece_score(piece, Side::White);
+            params.midgame_pst[index] = *midgame_pst;
+            params.endgame_pst[index] = *endgame_pst;
+        }
+
+        params
+    }
+}
+
+/// Score of a single evaluation term, kept separately for the midgame and the endgame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct TaperedScore {
+    pub(crate) mg: i32,
+    pub(crate) eg: i32,
+}
+
+impl TaperedScore {
+    pub(crate) const fn new(mg: i32, eg: i32) -> TaperedScore {
+        TaperedScore { mg, eg }
+    }
+
+    /// Interpolates between the midgame and the endgame score based on the game phase
+    /// # Examples
+    /// phase 24 (all pieces on board) -> mg
+    /// phase 0 (only kings and pawns) -> eg
+    pub(crate) const fn taper(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl std::ops::Mul<i32> for TaperedScore {
+    type Output = TaperedScore;
+
+    fn mul(self, rhs: i32) -> TaperedScore {
+        TaperedScore::new(self.mg * rhs, self.eg * rhs)
+    }
+}
+
+impl std::ops::Add for TaperedScore {
+    type Output = TaperedScore;
+
+    fn add(self, rhs: TaperedScore) -> TaperedScore {
+        TaperedScore::new(self.mg + rhs.mg, self.eg + rhs.eg)
+    }
+}
+
+impl std::ops::Sub for TaperedScore {
+    type Output = TaperedScore;
+
+    fn sub(self, rhs: TaperedScore) -> TaperedScore {
+        TaperedScore::new(self.mg - rhs.mg, self.eg - rhs.eg)
+    }
+}
+
+impl std::ops::AddAssign for TaperedScore {
+    fn add_assign(&mut self, rhs: TaperedScore) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::SubAssign for TaperedScore {
+    fn sub_assign(&mut self, rhs: TaperedScore) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::ops::Neg for TaperedScore {
+    type Output = TaperedScore;
+
+    fn neg(self) -> Self::Output {
+        TaperedScore::new(-self.mg, -self.eg)
+    }
+}
+
+/// Material and PST contribution of a single piece type, from white's perspective
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PieceEvaluation {
+    pub(crate) material: TaperedScore,
+    pub(crate) pst: TaperedScore,
+    pub(crate) mobility: TaperedScore,
+}
+
+impl PieceEvaluation {
+    pub(crate) fn total(&self) -> TaperedScore {
+        self.material + self.pst + self.mobility
+    }
+}
+
+/// Static evaluation split into its terms, used by the "eval" command
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct EvaluationBreakdown {
+    pub(crate) pieces: [PieceEvaluation; chess_consts::PIECE_TYPES_COUNT],
+    pub(crate) material: TaperedScore,
+    pub(crate) pawn_structure: TaperedScore,
+    pub(crate) king_safety: TaperedScore,
+    pub(crate) bishop_pair: TaperedScore,
+    pub(crate) rook_files: TaperedScore,
+    pub(crate) phase: i32,
+    pub(crate) score: i32,
+}
+
+/// Returns a bitboard of all squares attacked by the pawns of the given side
+fn get_pawn_attacks_bb(board: &Board, side: Side) -> u64 {
+    helpers::get_squares_iter(board.get_bb(side, Piece::Pawn))
+        .fold(chess_consts::EMPTY_BB, |acc, sq| {
+            acc | get_pawn_attacks_mask(side, sq)
+        })
+}
+
+/// Counts squares reachable by the pieces of the given type, ignoring squares occupied
+/// by friendly pieces and squares attacked by enemy pawns
+fn evaluate_mobility(board: &Board, side: Side, piece: Piece, enemy_pawn_attacks_bb: u64) -> i32 {
+    let available_bb = !board.get_occupancy_bb(side) & !enemy_pawn_attacks_bb;
+
+    helpers::get_squares_iter(board.get_bb(side, piece))
+        .map(|sq| (get_piece_attacks_bb(board, piece, sq) & available_bb).count_ones() as i32)
+        .sum()
+}
+
+/// Returns squares attacked by a knight, bishop, rook or queen standing on the square
+fn get_piece_attacks_bb(board: &Board, piece: Piece, square: Square) -> u64 {
+    match piece {
+        Piece::Knight => get_knight_attacks_mask(square),
+        Piece::Bishop => get_bishop_attacks_mask(square, board.global_occupancy),
+        Piece::Rook => get_rook_attacks_mask(square, board.global_occupancy),
+        Piece::Queen => get_queen_attacks_mask(square, board.global_occupancy),
+        _ => chess_consts::EMPTY_BB,
+    }
+}
+
+fn evaluate_bishop_pair(board: &Board, side: Side) -> TaperedScore {
+    if board.piece_count(side, Piece::Bishop) >= 2 {
+        piece_bonuses::BISHOP_PAIR_BONUS
+    } else {
+        TaperedScore::default()
+    }
+}
+
+/// Scores rooks standing on open and half-open files, from the side's perspective
+fn evaluate_rook_files(board: &Board, side: Side) -> TaperedScore {
+    let pawns_bb = board.get_bb(side, Piece::Pawn);
+    let enemy_pawns_bb = board.get_bb(side.opposite(), Piece::Pawn);
+
+    let mut score = TaperedScore::default();
+
+    for sq in helpers::get_squares_iter(board.get_bb(side, Piece::Rook)) {
+        let file_bb = helpers::file_mask(sq.file());
+
+        if file_bb & pawns_bb != 0 {
+            continue;
+        }
+
+        if file_bb & enemy_pawns_bb == 0 {
+            score += piece_bonuses::ROOK_OPEN_FILE_BONUS;
+        } else {
+            score += piece_bonuses::ROOK_HALF_OPEN_FILE_BONUS;
+        }
+    }
+
+    score
+}
+
+/// Scores the pawn shield in front of the king and the number of enemy pieces attacking
+/// the king zone, from the side's perspective
+fn evaluate_king_safety(board: &Board, side: Side) -> TaperedScore {
+    let mut score = TaperedScore::default();
+
+    if board.get_bb(side, Piece::King) == 0 {
+        return score;
+    }
+
+    let king_sq = board.get_king_square(side);
+    let pawns_bb = board.get_bb(side, Piece::Pawn);
+
+    let shield_files = (-1..=1).filter_map(|df| king_sq.offset(df, 0).map(|sq| sq.file()));
+
+    for file in shield_files {
+        let file_bb = helpers::file_mask(file);
+
+        if pawns_bb & file_bb & king_safety::shield_rank_mask(king_sq, side, 1) != 0 {
+            continue;
+        }
+
+        if pawns_bb & file_bb & king_safety::shield_rank_mask(king_sq, side, 2) != 0 {
+            score += king_safety::ADVANCED_SHIELD_PAWN_PENALTY;
+        } else {
+            score += king_safety::MISSING_SHIELD_PAWN_PENALTY;
+        }
+    }
+
+    let king_zone_bb = get_king_attacks_mask(king_sq) | king_sq.bit();
+    let enemy_side = side.opposite();
+
+    let attackers_count = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .into_iter()
+        .flat_map(|piece| {
+            helpers::get_squares_iter(board.get_bb(enemy_side, piece))
+                .filter(move |&sq| get_piece_attacks_bb(board, piece, sq) & king_zone_bb != 0)
+        })
+        .count();
+
+    score += king_safety::KING_ZONE_ATTACKERS_PENALTY
+        [attackers_count.min(king_safety::KING_ZONE_ATTACKERS_PENALTY.len() - 1)];
+
+    score
+}
+
+fn evaluate_piece(
+    board: &Board,
+    piece: Piece,
+    pawn_attacks_bbs: &[u64; chess_consts::SIDES_COUNT],
+    params: &EvalParams,
+) -> PieceEvaluation {
+    let white_bb = board.get_bb(Side::White, piece);
+    let black_bb = board.get_bb(Side::Black, piece);
+
+    let (white_count, black_count) = (white_bb.count_ones() as i32, black_bb.count_ones() as i32);
+
+    let piece_index = piece.index() as usize;
+    let material = TaperedScore::new(
+        params.midgame_piece_values[piece_index],
+        params.endgame_piece_values[piece_index],
+    ) * (white_count - black_count);
+
+    let (mg_table, eg_table) = (
+        &params.midgame_pst[piece_index],
+        &params.endgame_pst[piece_index],
+    );
+
+    let mut pst = TaperedScore::default();
+
+    for sq in helpers::get_squares_iter(white_bb) {
+        pst += TaperedScore::new(
+            pst_tables::get_pst_value(mg_table, sq, Side::White) as i32,
+            pst_tables::get_pst_value(eg_table, sq, Side::White) as i32,
+        );
+    }
+
+    for sq in helpers::get_squares_iter(black_bb) {
+        pst += TaperedScore::new(
+            -pst_tables::get_pst_value(mg_table, sq, Side::Black) as i32,
+            -pst_tables::get_pst_value(eg_table, sq, Side::Black) as i32,
+        );
+    }
+
+    PieceEvaluation {
+        material,
+        pst,
+        mobility: evaluate_piece_mobility(board, piece, pawn_attacks_bbs),
+    }
+}
+
+/// Returns the mobility score of the pieces of the given type, from white's perspective
+fn evaluate_piece_mobility(
+    board: &Board,
+    piece: Piece,
+    pawn_attacks_bbs: &[u64; chess_consts::SIDES_COUNT],
+) -> TaperedScore {
+    match mobility::get_mobility_weight(piece) {
+        Some(weight) => {
+            let white_mobility = evaluate_mobility(
+                board,
+                Side::White,
+                piece,
+                pawn_attacks_bbs[Side::Black.index() as usize],
+            );
+            let black_mobility = evaluate_mobility(
+                board,
+                Side::Black,
+                piece,
+                pawn_attacks_bbs[Side::White.index() as usize],
+            );
+
+            weight * (white_mobility - black_mobility)
+        }
+        None => TaperedScore::default(),
+    }
+}
+
+/// Returns the material and PST score of a single piece, from white's perspective.
+/// This is the value kept incrementally on the board, see Board::add_piece
+pub(crate) fn get_psqt_score(
+    params: &EvalParams,
+    side: Side,
+    piece: Piece,
+    square: Square,
+) -> TaperedScore {
+    let piece_index = piece.index() as usize;
+
+    let score = TaperedScore::new(
+        params.midgame_piece_values[piece_index]
+            + pst_tables::get_pst_value(&params.midgame_pst[piece_index], square, side) as i32,
+        params.endgame_piece_values[piece_index]
+            + pst_tables::get_pst_value(&params.endgame_pst[piece_index], square, side) as i32,
+    );
+
+    if side == Side::White { score } else { -score }
+}
+
+/// Recomputes the material and PST score of the whole board from scratch
+pub(crate) fn calc_psqt_score(board: &Board, params: &EvalParams) -> TaperedScore {
+    let mut score = TaperedScore::default();
+
+    for side in Side::all() {
+        for piece in Piece::all() {
+            for sq in helpers::get_squares_iter(board.get_bb(side, piece)) {
+                score += get_psqt_score(params, side, piece, sq);
             }
+        }
+    }
+
+    score
+}
+
+/// Sums all the terms except material and PST, from white's perspective
+fn evaluate_positional(board: &Board) -> TaperedScore {
+    let pawn_attacks_bbs = get_pawn_attacks_bbs(board);
+    let mut score = TaperedScore::default();
+
+    for piece in Piece::all() {
+        score += evaluate_piece_mobility(board, piece, &pawn_attacks_bbs);
+    }
+
+    // The pawn structure only changes with the pawns, the table keeps it
+    score += pawn_hash::probe(board.game_state.pawn_hash, || {
+        evaluate_pawn_structure(board, Side::White) - evaluate_pawn_structure(board, Side::Black)
+    });
+
+    for side in Side::all() {
+        let side_score = evaluate_king_safety(board, side)
+            + evaluate_bishop_pair(board, side)
+            + evaluate_rook_files(board, side);
+
+        score = match side {
+            Side::White => score + side_score,
+            Side::Black => score - side_score,
         };
+    }
+
+    score
+}
+
+/// Scores doubled, isolated and passed pawns of the given side, from the side's perspective
+fn evaluate_pawn_structure(board: &Board, side: Side) -> TaperedScore {
+    let pawns_bb = board.get_bb(side, Piece::Pawn);
+    let enemy_pawns_bb = board.get_bb(side.opposite(), Piece::Pawn);
+
+    let mut score = TaperedScore::default();
+
+    for file in (0..chess_consts::BOARD_SIZE as u8).map(|f| File::try_from(f).unwrap()) {
+        let pawns_on_file = (pawns_bb & helpers::file_mask(file)).count_ones() as i32;
 
-        for sq in helpers::get_squares_iter(white_bb) {
-            score += pst_tables::get_pst_value(&pst_table, sq, Side::White) as i32;
+        if pawns_on_file > 1 {
+            score += pawn_structure::DOUBLED_PAWN_PENALTY * (pawns_on_file - 1);
+        }
+    }
+
+    for sq in helpers::get_squares_iter(pawns_bb) {
+        if pawns_bb & pawn_structure::adjacent_files_mask(sq.file()) == 0 {
+            score += pawn_structure::ISOLATED_PAWN_PENALTY;
         }
 
-        for sq in helpers::get_squares_iter(black_bb) {
-            score -= pst_tables::get_pst_value(&pst_table, sq, Side::Black) as i32;
+        if enemy_pawns_bb & pawn_structure::passed_pawn_mask(sq, side) == 0 {
+            let relative_rank = match side {
+                Side::White => sq.rank().index(),
+                Side::Black => Rank::R8.index() - sq.rank().index(),
+            };
+
+            score += pawn_structure::PASSED_PAWN_BONUS[relative_rank as usize];
         }
     }
 
-    return if side == Side::White { score } else { -score };
+    score
+}
+
+fn get_pawn_attacks_bbs(board: &Board) -> [u64; chess_consts::SIDES_COUNT] {
+    [
+        get_pawn_attacks_bb(board, Side::White),
+        get_pawn_attacks_bb(board, Side::Black),
+    ]
+}
+
+/// Static evaluation in centipawns from the given side's perspective, positive when the
+/// side stands better, with the evaluation weights of the board. Negamax wants the side
+/// to move, see evalute_cur_side
+pub(crate) fn evalute(board: &Board, side: Side) -> i32 {
+    debug_assert_eq!(
+        board.psqt_score,
+        calc_psqt_score(board, &board.eval_params),
+        "Incremental material and PST score is out of sync"
+    );
+
+    let score = (board.psqt_score + evaluate_positional(board)).taper(calc_phase(board));
+    let score = endgame::evaluate_known_endgame(board, score)
+        .unwrap_or(score)
+        .clamp(-MAX_EVALUATION, MAX_EVALUATION);
+
+    if side == Side::White { score } else { -score }
+}
+
+/// Static evaluation in centipawns from white's perspective whoever is to move, positive
+/// when white stands better. This is what the GUIs and the analysis tools show, the
+/// board's own evaluation weights are used
+/// # Examples
+/// white up a queen, black to move -> about +900
+pub fn evaluate_white_relative(board: &Board) -> i32 {
+    evalute(board, Side::White)
+}
+
+/// Evaluates the board from white's perspective keeping every term separately
+pub(crate) fn evaluate_breakdown(board: &Board) -> EvaluationBreakdown {
+    let params = &*board.eval_params;
+    let mut breakdown = EvaluationBreakdown {
+        phase: calc_phase(board),
+        ..Default::default()
+    };
+
+    let mut score = TaperedScore::default();
+    let pawn_attacks_bbs = get_pawn_attacks_bbs(board);
+
+    for piece in Piece::all() {
+        let piece_evaluation = evaluate_piece(board, piece, &pawn_attacks_bbs, params);
+
+        breakdown.material += piece_evaluation.material;
+        score += piece_evaluation.total();
+        breakdown.pieces[piece.index() as usize] = piece_evaluation;
+    }
+
+    breakdown.pawn_structure =
+        evaluate_pawn_structure(board, Side::White) - evaluate_pawn_structure(board, Side::Black);
+    score += breakdown.pawn_structure;
+
+    breakdown.king_safety =
+        evaluate_king_safety(board, Side::White) - evaluate_king_safety(board, Side::Black);
+    score += breakdown.king_safety;
+
+    breakdown.bishop_pair =
+        evaluate_bishop_pair(board, Side::White) - evaluate_bishop_pair(board, Side::Black);
+    score += breakdown.bishop_pair;
+
+    breakdown.rook_files =
+        evaluate_rook_files(board, Side::White) - evaluate_rook_files(board, Side::Black);
+    score += breakdown.rook_files;
+
+    let score = score.taper(breakdown.phase);
+    breakdown.score = endgame::evaluate_known_endgame(board, score)
+        .unwrap_or(score)
+        .clamp(-MAX_EVALUATION, MAX_EVALUATION);
+
+    breakdown
+}
+
+impl Display for TaperedScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>6} {:>6}", self.mg, self.eg)
+    }
+}
+
+impl Display for EvaluationBreakdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:>8} | {:^13} | {:^13} | {:^13}",
+            "Term", "Material", "PST", "Mobility"
+        )?;
+        writeln!(
+            f,
+            "{:>8} | {:>6} {:>6} | {:>6} {:>6} | {:>6} {:>6}",
+            "", "MG", "EG", "MG", "EG", "MG", "EG"
+        )?;
+
+        for piece in Piece::all() {
+            let PieceEvaluation {
+                material,
+                pst,
+                mobility,
+            } = self.pieces[piece.index() as usize];
+            writeln!(
+                f,
+                "{:>8} | {} | {} | {}",
+                format!("{piece:?}"),
+                material,
+                pst,
+                mobility
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:>15} | {}", "Pawn structure", self.pawn_structure)?;
+        writeln!(f, "{:>15} | {}", "King safety", self.king_safety)?;
+        writeln!(f, "{:>15} | {}", "Bishop pair", self.bishop_pair)?;
+        writeln!(f, "{:>15} | {}", "Rook files", self.rook_files)?;
+
+        writeln!(f)?;
+        writeln!(f, "Material: {}", self.material.taper(self.phase))?;
+        writeln!(f, "Phase: {}", self.phase)?;
+        write!(f, "Evaluation: {} (white side)", self.score)
+    }
 }
 
+/// ply is the distance to the search root, used for the mate scores, and qs_ply the
+/// distance to the horizon
 pub(crate) fn quiescence_search(
     board: &mut Board,
     mut alpha: i32,
     beta: i32,
     bufs: &mut [MoveBuffer],
     ply: u32,
+    qs_ply: u32,
+    ctx: &mut SearchContext,
 ) -> i32 {
-    searching::NODES_COUNTER.fetch_add(1, Ordering::Relaxed);
+    ctx.count_node();
+    ctx.update_seldepth(ply);
+
+    if ctx.check_stop() {
+        return 0;
+    }
+
+    // Long capture sequences are cut off, and the children must not run out of move
+    // buffers or go past the tables indexed by ply
+    if qs_ply >= MAX_QUIESCENCE_PLY || bufs.len() < 2 || ply as usize >= chess_consts::MAX_PLY - 1 {
+        return evalute_cur_side(&*board);
+    }
 
     let moving_side = board.game_state.side_to_move;
 
     let (cur_buf, rest_bufs) = bufs.split_first_mut().unwrap();
     cur_buf.clear();
 
+    // Standing pat is not an option in check, every evasion has to be searched
     if board.is_in_check(moving_side) {
         board.generate_all_legal_moves(moving_side, cur_buf);
 
         if cur_buf.is_empty() {
-            return -MATE_EVALUATION + ply as i32;
+            return mated_in(ply);
         }
 
-        move_ordering::sort_moves(cur_buf, ply, true);
+        ctx.ordering.sort_moves(board, cur_buf, ply, None, false);
 
         for mv in cur_buf.iter().copied() {
             board.make_move(mv);
-            let score = -quiescence_search(board, -beta, -alpha, rest_bufs, ply + 1);
+            let score =
+                -quiescence_search(board, -beta, -alpha, rest_bufs, ply + 1, qs_ply + 1, ctx);
             board.unmake_move();
 
+            if ctx.stopped {
+                return 0;
+            }
+
             if score >= beta {
                 return beta;
             }
@@ -244,13 +1019,43 @@ pub(crate) fn quiescence_search(
     }
 
     board.generate_legal_captures(moving_side, cur_buf);
-    move_ordering::sort_moves(cur_buf, ply, true);
+
+    // Without captures the side may have no move at all. Positions cut off by standing
+    // pat are not checked, the stalemated side rarely stands well
+    if cur_buf.is_empty() && !board.has_legal_move(MoveGenMode::QuietsOnly, moving_side, cur_buf) {
+        return ctx.draw_score(ply);
+    }
+
+    ctx.ordering.sort_moves(board, cur_buf, ply, None, true);
 
     for mv in cur_buf.iter().copied() {
+        // Delta pruning: even winning the captured piece can't raise alpha
+        if let Move::Normal {
+            captured: Some(captured),
+            promo: None,
+            ..
+        } = mv
+            && eval_score
+                + board.eval_params.midgame_piece_values[captured.index() as usize]
+                + ctx.params.delta_pruning_margin
+                < alpha
+        {
+            continue;
+        }
+
+        // Captures losing material are not worth searching in quiescence
+        if board.see(mv) < 0 {
+            continue;
+        }
+
         board.make_move(mv);
-        let score = -quiescence_search(board, -beta, -alpha, rest_bufs, ply + 1);
+        let score = -quiescence_search(board, -beta, -alpha, rest_bufs, ply + 1, qs_ply + 1, ctx);
         board.unmake_move();
 
+        if ctx.stopped {
+            return 0;
+        }
+
         if score >= beta {
             return beta;
         }
@@ -263,26 +1068,86 @@ pub(crate) fn quiescence_search(
     alpha
 }
 
+/// Static evaluation from the perspective of the side to move, positive when it stands
+/// better, as the search needs it
 pub(crate) fn evalute_cur_side(board: &Board) -> i32 {
     evalute(board, board.game_state.side_to_move)
 }
 
 pub(crate) fn calc_phase(board: &Board) -> i32 {
-    let n = (board.get_bb(Side::White, Piece::Knight).count_ones()
-        + board.get_bb(Side::Black, Piece::Knight).count_ones()) as i32;
-    let b = (board.get_bb(Side::White, Piece::Bishop).count_ones()
-        + board.get_bb(Side::Black, Piece::Bishop).count_ones()) as i32;
-    let r = (board.get_bb(Side::White, Piece::Rook).count_ones()
-        + board.get_bb(Side::Black, Piece::Rook).count_ones()) as i32;
-    let q = (board.get_bb(Side::White, Piece::Queen).count_ones()
-        + board.get_bb(Side::Black, Piece::Queen).count_ones()) as i32;
+    let count = |piece| {
+        (board.piece_count(Side::White, piece) + board.piece_count(Side::Black, piece)) as i32
+    };
+    let (n, b, r, q) = (
+        count(Piece::Knight),
+        count(Piece::Bishop),
+        count(Piece::Rook),
+        count(Piece::Queen),
+    );
 
     let ph = n + b + 2 * r + 4 * q;
-    ph.clamp(0, 24)
+    ph.clamp(0, MAX_PHASE)
+}
+
+/// Expected outcome of the game for the side to move, in per mille
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wdl {
+    pub win: u32,
+    pub draw: u32,
+    pub loss: u32,
+}
+
+/// Logistic model of the outcome: a score scores a win with the chance
+/// 1 / (1 + exp(-(score - margin) / WDL_SCALE)) and a loss with the same formula for
+/// -score. The margin shrinks with the material, small advantages convert more often
+/// in the endgames
+const WDL_SCALE: f64 = 80.0;
+const WDL_MIDGAME_DRAW_MARGIN: f64 = 200.0;
+const WDL_ENDGAME_DRAW_MARGIN: f64 = 100.0;
+
+/// Converts a score of the side to move into the win, draw and loss chances, phase is
+/// the one of calc_phase. Mates are sure wins and losses
+/// # Examples
+/// 0 -> mostly draws, the same chances to win and to lose
+/// +300 in an endgame -> mostly wins
+pub(crate) fn score_to_wdl(score: i32, phase: i32) -> Wdl {
+    if is_mate_score(score) {
+        return if score > 0 {
+            Wdl {
+                win: 1000,
+                draw: 0,
+                loss: 0,
+            }
+        } else {
+            Wdl {
+                win: 0,
+                draw: 0,
+                loss: 1000,
+            }
+        };
+    }
+
+    let phase = phase.clamp(0, MAX_PHASE) as f64 / MAX_PHASE as f64;
+    let margin =
+        WDL_ENDGAME_DRAW_MARGIN + (WDL_MIDGAME_DRAW_MARGIN - WDL_ENDGAME_DRAW_MARGIN) * phase;
+    let chance = |score: f64| 1000.0 / (1.0 + (-(score - margin) / WDL_SCALE).exp());
+
+    let win = chance(score as f64).round() as u32;
+    let loss = chance(-score as f64).round() as u32;
+
+    Wdl {
+        win,
+        draw: 1000u32.saturating_sub(win + loss),
+        loss,
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, atomic::AtomicUsize};
+
+    use crate::{fen_parser, move_ordering::MoveOrderingState, searching::StopToken};
+
     use super::*;
 
     #[test]
@@ -291,4 +1156,426 @@ mod tests {
 
         assert_eq!(0, evalute(&board, board.game_state.side_to_move));
     }
+
+    #[test]
+    fn test_evaluation_perspective() {
+        // White is a queen up whoever is to move
+        for (fen, side_to_move_sign) in [
+            (
+                "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                1,
+            ),
+            (
+                "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1",
+                -1,
+            ),
+        ] {
+            let board = fen_parser::parse_fen_string(fen).unwrap();
+            let white_relative = evaluate_white_relative(&board);
+
+            assert!(white_relative > 700, "{fen}");
+            assert_eq!(evalute(&board, Side::Black), -white_relative);
+            assert_eq!(evalute_cur_side(&board), side_to_move_sign * white_relative);
+        }
+    }
+
+    #[test]
+    fn test_score_to_wdl() {
+        for phase in [0, MAX_PHASE / 2, MAX_PHASE] {
+            let even = score_to_wdl(0, phase);
+            assert_eq!(even.win, even.loss);
+            assert!(even.draw > even.win);
+
+            let mut prev_win = 0;
+            for score in (-2000..=2000).step_by(50) {
+                let wdl = score_to_wdl(score, phase);
+                assert_eq!(wdl.win + wdl.draw + wdl.loss, 1000, "{score}");
+                assert!(wdl.win >= prev_win);
+                assert_eq!(score_to_wdl(-score, phase).loss, wdl.win);
+                prev_win = wdl.win;
+            }
+        }
+
+        // The same advantage wins more often with less material on the board
+        assert!(score_to_wdl(150, 0).win > score_to_wdl(150, MAX_PHASE).win);
+
+        assert_eq!(score_to_wdl(MATE_EVALUATION - 3, MAX_PHASE).win, 1000);
+        assert_eq!(score_to_wdl(mated_in(4), 0).loss, 1000);
+    }
+
+    #[test]
+    fn test_evaluation_stays_below_mate_scores() {
+        const { assert!(endgame::KNOWN_WIN < MAX_EVALUATION) };
+        const { assert!(MAX_EVALUATION < MATE_THRESHOLD) };
+
+        // Nine queens and all the pieces against a lone king, not legal but as lopsided as it gets
+        for fen in [
+            "k7/2K5/QQQQQQQQ/8/8/8/8/QRRBBNN1 w - - 0 1",
+            "qrrbbnn1/8/8/8/8/qqqqqqqq/2k5/K7 b - - 0 1",
+        ] {
+            let board = fen_parser::parse_fen_string_unchecked(fen).unwrap();
+
+            for side in Side::all() {
+                let score = evalute(&board, side);
+                assert!(score.abs() <= MAX_EVALUATION, "{fen}: {score}");
+                assert!(!is_mate_score(score));
+            }
+        }
+    }
+
+    #[test]
+    fn test_evaluate_breakdown_matches_evaluate() {
+        for fen in [
+            chess_consts::fen_strings::START_POS_FEN,
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            chess_consts::fen_strings::KILLER_POS_FEN,
+            chess_consts::fen_strings::CMK_POS_FEN,
+        ] {
+            let board = fen_parser::parse_fen_string(fen).unwrap();
+            let breakdown = evaluate_breakdown(&board);
+
+            assert_eq!(evalute(&board, Side::White), breakdown.score);
+            assert_eq!(calc_phase(&board), breakdown.phase);
+            assert_eq!(
+                breakdown.material,
+                breakdown
+                    .pieces
+                    .iter()
+                    .fold(TaperedScore::default(), |acc, p| acc + p.material)
+            );
+        }
+    }
+
+    #[test]
+    fn test_mobility_excludes_friendly_and_pawn_attacked_squares() {
+        // Knight on a1 attacks b3 (free) and c2 (own pawn)
+        let board = fen_parser::parse_fen_string("4k3/8/8/8/8/8/2P5/N3K3 w - - 0 1").unwrap();
+        assert_eq!(1, evaluate_mobility(&board, Side::White, Piece::Knight, 0));
+
+        // b3 is attacked by the black pawn on c4
+        let board = fen_parser::parse_fen_string("4k3/8/8/8/2p5/8/2P5/N3K3 w - - 0 1").unwrap();
+        let black_pawn_attacks_bb = get_pawn_attacks_bb(&board, Side::Black);
+        assert_eq!(
+            0,
+            evaluate_mobility(&board, Side::White, Piece::Knight, black_pawn_attacks_bb)
+        );
+    }
+
+    #[test]
+    fn test_pawn_structure() {
+        // White: doubled c-pawns, every pawn isolated; black: passed h-pawn on h3
+        let board = fen_parser::parse_fen_string("4k3/8/8/8/8/2P4p/P1P5/4K3 w - - 0 1").unwrap();
+
+        let white = evaluate_pawn_structure(&board, Side::White);
+        let black = evaluate_pawn_structure(&board, Side::Black);
+
+        // All white pawns are isolated and passed, c2 and c3 are doubled
+        let expected_white = pawn_structure::DOUBLED_PAWN_PENALTY
+            + pawn_structure::ISOLATED_PAWN_PENALTY * 3
+            + pawn_structure::PASSED_PAWN_BONUS[1] * 2
+            + pawn_structure::PASSED_PAWN_BONUS[2];
+        assert_eq!(expected_white, white);
+
+        // h3 is isolated and passed on the sixth relative rank
+        let expected_black =
+            pawn_structure::ISOLATED_PAWN_PENALTY + pawn_structure::PASSED_PAWN_BONUS[5];
+        assert_eq!(expected_black, black);
+    }
+
+    #[test]
+    fn test_passed_pawn_mask() {
+        assert_eq!(
+            helpers::squares_mask([
+                Square::D6,
+                Square::E6,
+                Square::F6,
+                Square::D7,
+                Square::E7,
+                Square::F7,
+                Square::D8,
+                Square::E8,
+                Square::F8
+            ]),
+            pawn_structure::passed_pawn_mask(Square::E5, Side::White)
+        );
+        assert_eq!(
+            helpers::squares_mask([Square::A1, Square::B1]),
+            pawn_structure::passed_pawn_mask(Square::A2, Side::Black)
+        );
+        assert_eq!(0, pawn_structure::passed_pawn_mask(Square::H8, Side::White));
+    }
+
+    #[test]
+    fn test_king_safety() {
+        // Full pawn shield and no attackers
+        let board = fen_parser::parse_fen_string("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            TaperedScore::default(),
+            evaluate_king_safety(&board, Side::White)
+        );
+
+        // g-pawn advanced by one square, h-pawn missing
+        let board = fen_parser::parse_fen_string("6k1/5ppp/8/8/8/6P1/5P2/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            king_safety::ADVANCED_SHIELD_PAWN_PENALTY + king_safety::MISSING_SHIELD_PAWN_PENALTY,
+            evaluate_king_safety(&board, Side::White)
+        );
+
+        // Only the black queen hits the king zone, the d8 rook is far from it
+        let board = fen_parser::parse_fen_string("3rk3/8/8/8/8/6q1/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            king_safety::KING_ZONE_ATTACKERS_PENALTY[1],
+            evaluate_king_safety(&board, Side::White)
+        );
+
+        // With the rook on the first rank both of them do
+        let board = fen_parser::parse_fen_string("4k3/8/8/8/8/6q1/5PPP/3r2K1 w - - 0 1").unwrap();
+        assert_eq!(
+            king_safety::KING_ZONE_ATTACKERS_PENALTY[2],
+            evaluate_king_safety(&board, Side::White)
+        );
+    }
+
+    #[test]
+    fn test_bishop_pair_and_rook_files() {
+        // White has the bishop pair, a rook on the open d-file and one on the half-open e-file
+        let board = fen_parser::parse_fen_string("4k3/p3p3/8/8/8/8/P7/2BRRBK1 w - - 0 1").unwrap();
+
+        assert_eq!(
+            piece_bonuses::BISHOP_PAIR_BONUS,
+            evaluate_bishop_pair(&board, Side::White)
+        );
+        assert_eq!(
+            TaperedScore::default(),
+            evaluate_bishop_pair(&board, Side::Black)
+        );
+        assert_eq!(
+            piece_bonuses::ROOK_OPEN_FILE_BONUS + piece_bonuses::ROOK_HALF_OPEN_FILE_BONUS,
+            evaluate_rook_files(&board, Side::White)
+        );
+
+        // Rook blocked by its own pawn gets nothing
+        let board = fen_parser::parse_fen_string("4k3/8/8/8/8/8/P7/R3K3 w - - 0 1").unwrap();
+        assert_eq!(
+            TaperedScore::default(),
+            evaluate_rook_files(&board, Side::White)
+        );
+    }
+
+    #[test]
+    fn test_eval_params_change_evaluation() {
+        let board = fen_parser::parse_fen_string("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+
+        let mut stronger_queen_params = EvalParams::default();
+        stronger_queen_params.midgame_piece_values[Piece::Queen.index() as usize] += 100;
+        stronger_queen_params.endgame_piece_values[Piece::Queen.index() as usize] += 100;
+
+        let mut stronger_queen_board = board.clone();
+        stronger_queen_board.set_eval_params(Arc::new(stronger_queen_params));
+
+        // The evaluation follows the weights set on the board
+        assert_eq!(
+            evalute(&board, Side::White) + 100,
+            evalute(&stronger_queen_board, Side::White)
+        );
+    }
+
+    #[test]
+    fn test_incremental_psqt_score() {
+        let params = EvalParams::default();
+        let mut board =
+            fen_parser::parse_fen_string(chess_consts::fen_strings::KILLER_POS_FEN).unwrap();
+        let initial_score = board.psqt_score;
+
+        assert_eq!(calc_psqt_score(&board, &params), initial_score);
+
+        let moves = board.generate_all_legal_moves_to_vec(board.game_state.side_to_move);
+        for mv in moves {
+            board.make_move(mv);
+            assert_eq!(calc_psqt_score(&board, &params), board.psqt_score);
+            board.unmake_move();
+        }
+
+        assert_eq!(initial_score, board.psqt_score);
+    }
+
+    #[test]
+    fn test_quiescence_in_check() {
+        let stop_token = StopToken::new();
+        let mut ordering = MoveOrderingState::new();
+        let nodes = AtomicUsize::new(0);
+        let mut ctx = SearchContext::new(&stop_token, &mut ordering, &nodes);
+        let mut bufs: Vec<MoveBuffer> = (0..chess_consts::MAX_PLY)
+            .map(|_| Vec::with_capacity(chess_consts::MOVES_BUF_SIZE))
+            .collect();
+
+        // Checkmated at the horizon
+        let mut board = fen_parser::parse_fen_string("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(
+            quiescence_search(
+                &mut board,
+                -MATE_EVALUATION,
+                MATE_EVALUATION,
+                &mut bufs,
+                3,
+                0,
+                &mut ctx
+            ),
+            -MATE_EVALUATION + 3
+        );
+
+        // In check but able to escape, the evasion has to be played instead of standing pat
+        let mut board = fen_parser::parse_fen_string("R5k1/5pp1/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        let score = quiescence_search(
+            &mut board,
+            -MATE_EVALUATION,
+            MATE_EVALUATION,
+            &mut bufs,
+            3,
+            0,
+            &mut ctx,
+        );
+        assert!(score > -MATE_EVALUATION + chess_consts::MAX_PLY as i32);
+
+        let evasions = board.generate_all_legal_moves_to_vec(Side::Black);
+        assert_eq!(evasions.len(), 1);
+
+        board.make_move(evasions[0]);
+        assert_eq!(
+            score,
+            -quiescence_search(
+                &mut board,
+                -MATE_EVALUATION,
+                MATE_EVALUATION,
+                &mut bufs,
+                4,
+                1,
+                &mut ctx
+            )
+        );
+    }
+
+    #[test]
+    fn test_delta_pruning_uses_the_board_params() {
+        // The rook can take an undefended pawn, alpha is set so that only a pawn worth
+        // more than the default one is worth the capture
+        let search = |pawn_value: i32| {
+            let mut params = EvalParams::default();
+            params.midgame_piece_values[Piece::Pawn.index() as usize] = pawn_value;
+            params.endgame_piece_values[Piece::Pawn.index() as usize] = pawn_value;
+
+            let mut board =
+                fen_parser::parse_fen_string("4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1").unwrap();
+            board.set_eval_params(Arc::new(params));
+
+            let stop_token = StopToken::new();
+            let mut ordering = MoveOrderingState::new();
+            let nodes = AtomicUsize::new(0);
+            let mut ctx = SearchContext::new(&stop_token, &mut ordering, &nodes);
+            let mut bufs: Vec<MoveBuffer> = (0..chess_consts::MAX_PLY)
+                .map(|_| Vec::with_capacity(chess_consts::MOVES_BUF_SIZE))
+                .collect();
+
+            let alpha = evalute_cur_side(&board) + 400;
+            let score = quiescence_search(
+                &mut board,
+                alpha,
+                MATE_EVALUATION,
+                &mut bufs,
+                1,
+                0,
+                &mut ctx,
+            );
+
+            (score, alpha)
+        };
+
+        let (score, alpha) = search(100);
+        assert!(score <= alpha);
+
+        let (score, alpha) = search(1000);
+        assert!(score > alpha);
+    }
+
+    #[test]
+    fn test_quiescence_stalemate() {
+        let stop_token = StopToken::new();
+        let mut ordering = MoveOrderingState::new();
+        let nodes = AtomicUsize::new(0);
+        let mut ctx = SearchContext::new(&stop_token, &mut ordering, &nodes);
+        let mut bufs: Vec<MoveBuffer> = (0..chess_consts::MAX_PLY)
+            .map(|_| Vec::with_capacity(chess_consts::MOVES_BUF_SIZE))
+            .collect();
+
+        // Black is a queen down but stalemated, standing pat would call it lost
+        let mut board = fen_parser::parse_fen_string("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(
+            quiescence_search(
+                &mut board,
+                -MATE_EVALUATION,
+                MATE_EVALUATION,
+                &mut bufs,
+                3,
+                0,
+                &mut ctx
+            ),
+            0
+        );
+
+        // With a pawn move left the evaluation stands
+        let mut board = fen_parser::parse_fen_string("7k/5Q2/6K1/8/8/p7/8/8 b - - 0 1").unwrap();
+        let score = quiescence_search(
+            &mut board,
+            -MATE_EVALUATION,
+            MATE_EVALUATION,
+            &mut bufs,
+            3,
+            0,
+            &mut ctx,
+        );
+        assert!(score < -500);
+    }
+
+    #[test]
+    fn test_quiescence_depth_cap() {
+        let stop_token = StopToken::new();
+        let mut ordering = MoveOrderingState::new();
+        let nodes = AtomicUsize::new(0);
+        let mut ctx = SearchContext::new(&stop_token, &mut ordering, &nodes);
+        let mut bufs: Vec<MoveBuffer> = (0..3)
+            .map(|_| Vec::with_capacity(chess_consts::MOVES_BUF_SIZE))
+            .collect();
+
+        // Plenty of captures, but only three buffers and a ply close to the limit
+        let mut board =
+            fen_parser::parse_fen_string(chess_consts::fen_strings::KILLER_POS_FEN).unwrap();
+        let static_eval = evalute_cur_side(&board);
+
+        let mut search = |bufs: &mut [MoveBuffer], ply: u32, qs_ply: u32| {
+            quiescence_search(
+                &mut board,
+                -MATE_EVALUATION,
+                MATE_EVALUATION,
+                bufs,
+                ply,
+                qs_ply,
+                &mut ctx,
+            )
+        };
+
+        search(&mut bufs, 3, 0);
+        search(&mut bufs, chess_consts::MAX_PLY as u32 - 2, 0);
+
+        assert_eq!(search(&mut bufs[..1], 3, 0), static_eval);
+        assert_eq!(search(&mut bufs, 3, MAX_QUIESCENCE_PLY), static_eval);
+    }
+
+    #[test]
+    fn test_tapered_score() {
+        let score = TaperedScore::new(100, 200);
+
+        assert_eq!(100, score.taper(MAX_PHASE));
+        assert_eq!(200, score.taper(0));
+        assert_eq!(150, score.taper(MAX_PHASE / 2));
+    }
 }
@@ -1,17 +1,17 @@
 use std::fmt::Display;
 
 use crate::{
-    board::{Board, CastlingState},
+    board::{Board, CastlingConfig, CastlingState},
     chess_consts,
-    enums::{File, Piece, Rank, Side, Square},
+    enums::{CastlingSide, File, Piece, Rank, Side, Square},
+    helpers,
 };
 
 const FEN_PARTS_COUNT: usize = 6;
 const FEN_PARTS_SPLITTER: char = ' ';
-const SIDE_TO_MOVE_CHARS: &str = "wb";
 
 #[derive(Debug)]
-pub(crate) enum ParseFenError {
+pub enum ParseFenError {
     IncorrectPartsLength,
     PiecesParse,
     SideToMoveParse,
@@ -19,6 +19,9 @@ pub(crate) enum ParseFenError {
     EnPassantSquareParse,
     HalfMoveClockParse,
     FullMoveCountParse,
+    InvalidKingCount,
+    PawnOnBackRank,
+    OppositeSideInCheck,
 }
 
 impl Display for ParseFenError {
@@ -39,21 +42,44 @@ impl Display for ParseFenError {
             ParseFenError::FullMoveCountParse => {
                 "Error in FEN string: Failed to parse full moves count"
             }
+            ParseFenError::InvalidKingCount => {
+                "Error in FEN string: Each side must have exactly one king"
+            }
+            ParseFenError::PawnOnBackRank => {
+                "Error in FEN string: Pawns can't stand on the first or the last rank"
+            }
+            ParseFenError::OppositeSideInCheck => {
+                "Error in FEN string: The side not to move is in check"
+            }
         };
         write!(f, "{error}")
     }
 }
 
+impl std::error::Error for ParseFenError {}
+
 type ParseFenResult = Result<Board, ParseFenError>;
 type ParseFenPartResult = Result<(), ParseFenError>;
 
+/// Parses the FEN string and checks that the position is legal
 pub(crate) fn parse_fen_string(fen: &str) -> ParseFenResult {
+    let board = parse_fen_string_unchecked(fen)?;
+    validate_position(&board)?;
+
+    Ok(board)
+}
+
+/// Parses the FEN string without checking the position, the board may lack kings
+/// or have the side not to move in check
+pub(crate) fn parse_fen_string_unchecked(fen: &str) -> ParseFenResult {
     let mut board = Board::default();
     let mut parts: Vec<_> = fen.split(FEN_PARTS_SPLITTER).collect();
 
-    // short fen string case
-    if parts.len() == 4 {
-        parts.append(&mut vec!["0", "1"]);
+    // Short FEN strings leave out the move counters, or only the full move number
+    match parts.len() {
+        4 => parts.extend(["0", "1"]),
+        5 => parts.push("1"),
+        _ => {}
     }
 
     if parts.len() != FEN_PARTS_COUNT {
@@ -73,9 +99,119 @@ pub(crate) fn parse_fen_string(fen: &str) -> ParseFenResult {
         parse_fn(&mut board, part)?;
     }
 
+    board.game_state.hash = board.calc_hash();
+    board.game_state.pawn_hash = board.calc_pawn_hash();
+
     Ok(board)
 }
 
+impl Board {
+    /// Writes the position as a FEN string, the inverse of parse_fen_string
+    /// # Examples
+    /// The start position -> rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+    pub(crate) fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..chess_consts::BOARD_SIZE as u8).rev() {
+            let mut empty_count = 0;
+
+            for file in 0..chess_consts::BOARD_SIZE as u8 {
+                let square = unsafe { Square::from_u8_unchecked(rank * 8 + file) };
+                let piece = Side::all().find_map(|side| {
+                    self.get_occupancy_piece(side, square)
+                        .map(|piece| piece.to_char(side))
+                });
+
+                match piece {
+                    Some(ch) => {
+                        if empty_count > 0 {
+                            fen.push_str(&empty_count.to_string());
+                            empty_count = 0;
+                        }
+                        fen.push(ch);
+                    }
+                    None => empty_count += 1,
+                }
+            }
+
+            if empty_count > 0 {
+                fen.push_str(&empty_count.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(FEN_PARTS_SPLITTER);
+        fen.push(self.game_state.side_to_move.to_char());
+
+        fen.push(FEN_PARTS_SPLITTER);
+        let castling_state = self.game_state.castling_state;
+        if castling_state.is_empty() {
+            fen.push('-');
+        } else {
+            // Non standard king or rook files are written as Shredder-FEN rook file letters
+            let is_standard_setup = self.castling_config == CastlingConfig::default();
+
+            for side in Side::all() {
+                for castling_side in CastlingSide::all() {
+                    if !castling_state.contains(CastlingState::get_flag(side, castling_side)) {
+                        continue;
+                    }
+
+                    let ch = if is_standard_setup {
+                        match castling_side {
+                            CastlingSide::KingSide => 'k',
+                            CastlingSide::QueenSide => 'q',
+                        }
+                    } else {
+                        let rook_sq = self.castling_config.get_rook_square(side, castling_side);
+                        (b'a' + rook_sq.file().index()) as char
+                    };
+
+                    fen.push(match side {
+                        Side::White => ch.to_ascii_uppercase(),
+                        Side::Black => ch,
+                    });
+                }
+            }
+        }
+
+        fen.push(FEN_PARTS_SPLITTER);
+        match self.game_state.en_passant_square {
+            Some(square) => fen.push_str(&square.to_string()),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            self.game_state.half_move_clock, self.game_state.full_moves_count
+        ));
+
+        fen
+    }
+}
+
+pub(crate) fn validate_position(board: &Board) -> ParseFenPartResult {
+    for side in Side::all() {
+        if board.get_bb(side, Piece::King).count_ones() != 1 {
+            return Err(ParseFenError::InvalidKingCount);
+        }
+    }
+
+    let back_ranks_mask = helpers::rank_mask(Rank::R1) | helpers::rank_mask(Rank::R8);
+    let pawns_bb = board.get_bb(Side::White, Piece::Pawn) | board.get_bb(Side::Black, Piece::Pawn);
+    if pawns_bb & back_ranks_mask != 0 {
+        return Err(ParseFenError::PawnOnBackRank);
+    }
+
+    if board.is_in_check(board.game_state.side_to_move.opposite()) {
+        return Err(ParseFenError::OppositeSideInCheck);
+    }
+
+    Ok(())
+}
+
 fn parse_pieces(board: &mut Board, part: &str) -> ParseFenPartResult {
     let mut rank = Rank::R8.index();
     let mut file = File::A.index();
@@ -91,18 +227,6 @@ fn parse_pieces(board: &mut Board, part: &str) -> ParseFenPartResult {
         };
 
         match c {
-            'K' => set_piece(Side::White, Piece::King)?,
-            'Q' => set_piece(Side::White, Piece::Queen)?,
-            'R' => set_piece(Side::White, Piece::Rook)?,
-            'B' => set_piece(Side::White, Piece::Bishop)?,
-            'N' => set_piece(Side::White, Piece::Knight)?,
-            'P' => set_piece(Side::White, Piece::Pawn)?,
-            'k' => set_piece(Side::Black, Piece::King)?,
-            'q' => set_piece(Side::Black, Piece::Queen)?,
-            'r' => set_piece(Side::Black, Piece::Rook)?,
-            'b' => set_piece(Side::Black, Piece::Bishop)?,
-            'n' => set_piece(Side::Black, Piece::Knight)?,
-            'p' => set_piece(Side::Black, Piece::Pawn)?,
             '1'..='8' => {
                 file += c.to_digit(10).unwrap() as u8;
 
@@ -118,7 +242,10 @@ fn parse_pieces(board: &mut Board, part: &str) -> ParseFenPartResult {
                 rank -= 1;
                 file = 0
             }
-            _ => return Err(ParseFenError::PiecesParse),
+            _ => {
+                let (side, piece) = Piece::from_char(c).ok_or(ParseFenError::PiecesParse)?;
+                set_piece(side, piece)?;
+            }
         }
     }
 
@@ -127,55 +254,93 @@ fn parse_pieces(board: &mut Board, part: &str) -> ParseFenPartResult {
     }
 
     board.recalc_occupancies();
+    board.recalc_psqt_score();
     Ok(())
 }
 
 fn parse_side_to_move(board: &mut Board, part: &str) -> ParseFenPartResult {
-    if part.len() == 1
-        && let Some(ch) = part.chars().next()
-        && SIDE_TO_MOVE_CHARS.contains(ch)
-    {
-        match ch {
-            'w' => board.game_state.side_to_move = Side::White,
-            'b' => board.game_state.side_to_move = Side::Black,
-            _ => unreachable!(),
-        }
+    board.game_state.side_to_move = part.parse().map_err(|_| ParseFenError::SideToMoveParse)?;
 
+    Ok(())
+}
+
+/// Parses both the standard KQkq castling rights (X-FEN, the outermost rook is taken)
+/// and the Shredder-FEN file letters used by Chess960 positions
+pub(crate) fn parse_castling_rights(board: &mut Board, part: &str) -> ParseFenPartResult {
+    if part == "-" {
+        board.game_state.castling_state = CastlingState::empty();
         return Ok(());
     }
 
-    return Err(ParseFenError::SideToMoveParse);
-}
+    if !(1..=4).contains(&part.len()) {
+        return Err(ParseFenError::CastlingRightsParse);
+    }
 
-fn parse_castling_rights(board: &mut Board, part: &str) -> ParseFenPartResult {
-    if (1..=4).contains(&part.len()) {
-        for ch in part.chars() {
-            match ch {
-                'K' => board
-                    .game_state
-                    .castling_state
-                    .insert(CastlingState::WHITE_KINGSIDE),
-                'Q' => board
-                    .game_state
-                    .castling_state
-                    .insert(CastlingState::WHITE_QUEENSIDE),
-                'k' => board
-                    .game_state
-                    .castling_state
-                    .insert(CastlingState::BLACK_KINGSIDE),
-                'q' => board
-                    .game_state
-                    .castling_state
-                    .insert(CastlingState::BLACK_QUEENSIDE),
-                '-' if part.len() == 1 => board.game_state.castling_state = CastlingState::empty(),
-                _ => return Err(ParseFenError::CastlingRightsParse),
+    for ch in part.chars() {
+        let side = if ch.is_ascii_uppercase() {
+            Side::White
+        } else {
+            Side::Black
+        };
+        let back_rank = side.get_back_rank();
+
+        let king_file = match board.get_bb(side, Piece::King) {
+            0 => File::E,
+            king_bb => unsafe { Square::from_u8_unchecked(king_bb.trailing_zeros() as u8) }.file(),
+        };
+
+        // The outermost rook on the given side of the king, standard files if there is none
+        let find_rook_file = |castling_side: CastlingSide| {
+            let rooks_bb = board.get_bb(side, Piece::Rook) & helpers::rank_mask(back_rank);
+            let has_rook = |f: &u8| rooks_bb & (1u64 << (back_rank.index() * 8 + f)) != 0;
+
+            let file_index = match castling_side {
+                CastlingSide::KingSide => (king_file.index() + 1..chess_consts::BOARD_SIZE as u8)
+                    .rev()
+                    .find(has_rook)
+                    .unwrap_or(File::H.index()),
+                CastlingSide::QueenSide => (0..king_file.index())
+                    .find(has_rook)
+                    .unwrap_or(File::A.index()),
+            };
+
+            unsafe { File::from_u8_unchecked(file_index) }
+        };
+
+        let (castling_side, rook_file) = match ch.to_ascii_lowercase() {
+            'k' => (
+                CastlingSide::KingSide,
+                find_rook_file(CastlingSide::KingSide),
+            ),
+            'q' => (
+                CastlingSide::QueenSide,
+                find_rook_file(CastlingSide::QueenSide),
+            ),
+            file_ch @ 'a'..='h' => {
+                let rook_file = unsafe { File::from_u8_unchecked(file_ch as u8 - b'a') };
+
+                if rook_file.index() > king_file.index() {
+                    (CastlingSide::KingSide, rook_file)
+                } else if rook_file.index() < king_file.index() {
+                    (CastlingSide::QueenSide, rook_file)
+                } else {
+                    return Err(ParseFenError::CastlingRightsParse);
+                }
             }
-        }
+            _ => return Err(ParseFenError::CastlingRightsParse),
+        };
 
-        return Ok(());
+        board.castling_config.set_king_file(side, king_file);
+        board
+            .castling_config
+            .set_rook_file(side, castling_side, rook_file);
+        board
+            .game_state
+            .castling_state
+            .insert(CastlingState::get_flag(side, castling_side));
     }
 
-    return Err(ParseFenError::CastlingRightsParse);
+    Ok(())
 }
 
 fn parse_en_passant_square(board: &mut Board, part: &str) -> ParseFenPartResult {
@@ -199,7 +364,7 @@ fn parse_en_passant_square(board: &mut Board, part: &str) -> ParseFenPartResult
         }
     }
 
-    return Err(ParseFenError::EnPassantSquareParse);
+    Err(ParseFenError::EnPassantSquareParse)
 }
 
 fn parse_half_move_clock(board: &mut Board, part: &str) -> ParseFenPartResult {
@@ -262,4 +427,61 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_chess960_castling_rights_to_fen() {
+        // X-FEN rights of a Chess960 setup are written with the rook files
+        let board =
+            parse_fen_string("rkrbbqnn/pppppppp/8/8/8/8/PPPPPPPP/RKRBBQNN w KQkq - 0 1").unwrap();
+        assert_eq!(
+            board.to_fen(),
+            "rkrbbqnn/pppppppp/8/8/8/8/PPPPPPPP/RKRBBQNN w CAca - 0 1"
+        );
+
+        let board = parse_fen_string("r3k2r/8/8/8/8/8/8/R3K2R w HAha - 0 1").unwrap();
+        assert_eq!(board.to_fen(), "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    }
+
+    #[test]
+    fn test_invalid_positions() {
+        assert!(matches!(
+            parse_fen_string("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Err(ParseFenError::InvalidKingCount)
+        ));
+        assert!(matches!(
+            parse_fen_string("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+            Err(ParseFenError::InvalidKingCount)
+        ));
+        assert!(matches!(
+            parse_fen_string("4k2P/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Err(ParseFenError::PawnOnBackRank)
+        ));
+        assert!(matches!(
+            parse_fen_string("4k3/8/8/8/8/8/8/p3K3 b - - 0 1"),
+            Err(ParseFenError::PawnOnBackRank)
+        ));
+        assert!(matches!(
+            parse_fen_string("4k3/8/8/8/8/8/8/4K2r b - - 0 1"),
+            Err(ParseFenError::OppositeSideInCheck)
+        ));
+        assert!(parse_fen_string("4k3/8/8/8/8/8/8/4K2r w - - 0 1").is_ok());
+        assert!(parse_fen_string_unchecked("8/8/8/8/8/8/8/8 w - - 0 1").is_ok());
+    }
+
+    #[test]
+    fn test_to_fen_round_trip() {
+        let fens = [
+            chess_consts::fen_strings::START_POS_FEN,
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            chess_consts::fen_strings::KILLER_POS_FEN,
+            chess_consts::fen_strings::CMK_POS_FEN,
+            "8/8/8/8/8/8/8/8 w - - 0 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            "2r1kr2/8/8/8/8/8/8/1R2K1R1 w GBfc - 0 1",
+        ];
+
+        for fen in fens {
+            assert_eq!(fen, parse_fen_string_unchecked(fen).unwrap().to_fen());
+        }
+    }
 }
@@ -0,0 +1,169 @@
+use std::fmt::Display;
+
+use crate::{
+    board::Board,
+    chess_consts,
+    enums::{Piece, Side},
+};
+
+/// How a finished game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw(DrawReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawReason {
+    Stalemate,
+    FiftyMove,
+    Threefold,
+    InsufficientMaterial,
+}
+
+impl Display for GameResult {
+    /// Written like the PGN result tag
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let result = match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw(_) => "1/2-1/2",
+        };
+
+        f.write_str(result)
+    }
+}
+
+impl Board {
+    /// Tells whether the game is over and how, None while it goes on. The draws the
+    /// players could claim end the game here, so a self-play loop can make moves until
+    /// it gets a result
+    /// # Examples
+    /// fool's mate -> Some(BlackWins)
+    /// lone kings -> Some(Draw(InsufficientMaterial))
+    /// start position -> None
+    pub fn game_result(&mut self) -> Option<GameResult> {
+        let side = self.game_state.side_to_move;
+
+        // A mate given with the fiftieth move still counts
+        if self.legal_move_count(side) == 0 {
+            return Some(if !self.is_in_check(side) {
+                GameResult::Draw(DrawReason::Stalemate)
+            } else if side == Side::White {
+                GameResult::BlackWins
+            } else {
+                GameResult::WhiteWins
+            });
+        }
+
+        if self.can_claim_fifty_move_draw() {
+            Some(GameResult::Draw(DrawReason::FiftyMove))
+        } else if self.can_claim_threefold() {
+            Some(GameResult::Draw(DrawReason::Threefold))
+        } else if self.has_insufficient_material() {
+            Some(GameResult::Draw(DrawReason::InsufficientMaterial))
+        } else {
+            None
+        }
+    }
+
+    /// Neither side can mate with any sequence of moves: the kings alone, a single minor
+    /// piece, or only bishops all standing on squares of one color
+    pub(crate) fn has_insufficient_material(&self) -> bool {
+        let pieces_bb = |piece| self.get_bb(Side::White, piece) | self.get_bb(Side::Black, piece);
+
+        if pieces_bb(Piece::Pawn) | pieces_bb(Piece::Rook) | pieces_bb(Piece::Queen) != 0 {
+            return false;
+        }
+
+        let knights_bb = pieces_bb(Piece::Knight);
+        let bishops_bb = pieces_bb(Piece::Bishop);
+
+        if (knights_bb | bishops_bb).count_ones() <= 1 {
+            return true;
+        }
+
+        knights_bb == 0
+            && (bishops_bb & chess_consts::DARK_SQUARES_BB == 0
+                || bishops_bb & !chess_consts::DARK_SQUARES_BB == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fen_parser, uci};
+
+    use super::*;
+
+    fn result(fen: &str) -> Option<GameResult> {
+        fen_parser::parse_fen_string(fen).unwrap().game_result()
+    }
+
+    #[test]
+    fn test_game_result() {
+        assert_eq!(result(chess_consts::fen_strings::START_POS_FEN), None);
+
+        let mut board =
+            uci::parse_uci_position_command("position startpos moves f2f3 e7e5 g2g4 d8h4", false)
+                .unwrap();
+        assert_eq!(board.game_result(), Some(GameResult::BlackWins));
+        assert_eq!(
+            result("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1"),
+            Some(GameResult::WhiteWins)
+        );
+        assert_eq!(
+            result("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+            Some(GameResult::Draw(DrawReason::Stalemate))
+        );
+
+        // The mate given with the hundredth half move wins, the draw comes otherwise
+        assert_eq!(
+            result("R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80"),
+            Some(GameResult::WhiteWins)
+        );
+        assert_eq!(
+            result("6k1/5ppp/8/8/8/8/8/R5K1 b - - 100 80"),
+            Some(GameResult::Draw(DrawReason::FiftyMove))
+        );
+
+        let mut board = uci::parse_uci_position_command(
+            "position startpos moves g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8",
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            board.game_result(),
+            Some(GameResult::Draw(DrawReason::Threefold))
+        );
+        assert_eq!(board.game_result().unwrap().to_string(), "1/2-1/2");
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let insufficient = [
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3KN3/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/5b2/8 w - - 0 1",
+            // The bishops on light squares only
+            "8/8/4k1b1/8/8/3K4/4B3/1B6 w - - 0 1",
+        ];
+        for fen in insufficient {
+            assert_eq!(
+                result(fen),
+                Some(GameResult::Draw(DrawReason::InsufficientMaterial)),
+                "{fen}"
+            );
+        }
+
+        let sufficient = [
+            "8/8/4k3/8/8/3KNN2/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3KN3/5b2/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/4B3/2B5 w - - 0 1",
+            "8/8/4k3/8/8/3K4/7p/8 w - - 0 1",
+        ];
+        for fen in sufficient {
+            assert_eq!(result(fen), None, "{fen}");
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     chess_consts::{self, BOARD_SIZE},
-    enums::{File, Piece, Rank, Side, Square},
+    enums::{File, Rank, Square},
 };
 
 /// Prints the bitboard to stdout
@@ -123,6 +123,19 @@ pub fn squares_mask(squares: impl IntoIterator<Item = Square>) -> u64 {
 /// # Examples
 /// 1010 -> 1 3
 #[inline]
+/// Returns the squares between two squares of the same rank, both ends included
+pub(crate) fn rank_span_mask(a: Square, b: Square) -> u64 {
+    debug_assert!(a.rank() == b.rank(), "Squares must be on the same rank");
+
+    let (low, high) = if a.index() <= b.index() {
+        (a.index(), b.index())
+    } else {
+        (b.index(), a.index())
+    };
+
+    (u64::MAX >> (63 - high)) & (u64::MAX << low)
+}
+
 pub(crate) fn get_bits_iter(bb: u64) -> impl Iterator<Item = usize> {
     let mut x = bb;
 
@@ -155,15 +168,6 @@ pub(crate) fn get_squares_iter(bb: u64) -> impl Iterator<Item = Square> {
     })
 }
 
-#[inline]
-pub(crate) fn get_ascii_piece_char(side: Side, piece: Piece) -> char {
-    const ASCII_PIECE_CHARS: [char; chess_consts::PIECE_TYPES_COUNT * 2] =
-        ['P', 'N', 'B', 'R', 'Q', 'K', 'p', 'n', 'b', 'r', 'q', 'k'];
-
-    ASCII_PIECE_CHARS
-        [(side.index() * chess_consts::PIECE_TYPES_COUNT as u8 + piece.index()) as usize]
-}
-
 #[cfg(test)]
 mod tests {
     use crate::enums::Square;
@@ -186,12 +190,12 @@ mod tests {
     #[test]
     fn get_bit_tests() {
         let a1_bitboard = Square::A1.bit();
-        assert_eq!(is_bit_set(a1_bitboard, Square::A1), true);
-        assert_eq!(is_bit_set(a1_bitboard, Square::A2), false);
+        assert!(is_bit_set(a1_bitboard, Square::A1));
+        assert!(!is_bit_set(a1_bitboard, Square::A2));
 
         let a2_bitboard = Square::A2.bit();
-        assert_eq!(is_bit_set(a2_bitboard, Square::A1), false);
-        assert_eq!(is_bit_set(a2_bitboard, Square::A2), true);
+        assert!(!is_bit_set(a2_bitboard, Square::A1));
+        assert!(is_bit_set(a2_bitboard, Square::A2));
     }
 
     #[test]
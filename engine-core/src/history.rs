@@ -1,69 +1,53 @@
-use std::mem::MaybeUninit;
-
 use crate::{board::GameState, enums::Move};
 
-const MAX_MOVES_COUNT: usize = 4096;
-
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub(crate) struct History {
-    entries: [MaybeUninit<HistoryEntry>; MAX_MOVES_COUNT],
-    len: usize,
+    entries: Vec<HistoryEntry>,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) struct HistoryEntry {
-    pub(crate) mv: Move,
+    /// None for a null move
+    pub(crate) mv: Option<Move>,
     pub(crate) game_state: GameState,
 }
 
 impl HistoryEntry {
     pub(crate) fn new(mv: Move, game_state: GameState) -> HistoryEntry {
-        HistoryEntry { mv, game_state }
+        HistoryEntry {
+            mv: Some(mv),
+            game_state,
+        }
     }
-}
 
-impl History {
-    pub(crate) fn new() -> History {
-        History {
-            entries: [const { MaybeUninit::uninit() }; MAX_MOVES_COUNT],
-            len: 0,
+    pub(crate) fn null(game_state: GameState) -> HistoryEntry {
+        HistoryEntry {
+            mv: None,
+            game_state,
         }
     }
+}
 
+impl History {
+    #[allow(dead_code)]
     pub(crate) fn len(&self) -> usize {
-        self.len
+        self.entries.len()
     }
 
-    pub(crate) fn push(&mut self, entry: HistoryEntry) -> Result<(), HistoryEntry> {
-        if self.len == MAX_MOVES_COUNT {
-            return Err(entry);
-        }
-
-        self.entries[self.len].write(entry);
-        self.len += 1;
-        Ok(())
+    pub(crate) fn push(&mut self, entry: HistoryEntry) {
+        self.entries.push(entry);
     }
 
-    pub(crate) fn pop(&mut self) -> Option<HistoryEntry> {
-        if self.len == 0 {
-            return None;
-        }
-
-        self.len -= 1;
-        unsafe { Some(self.entries[self.len].assume_init_read()) }
+    pub(crate) fn last(&self) -> Option<&HistoryEntry> {
+        self.entries.last()
     }
-}
 
-impl Default for History {
-    fn default() -> Self {
-        History::new()
+    pub(crate) fn pop(&mut self) -> Option<HistoryEntry> {
+        self.entries.pop()
     }
-}
 
-impl Drop for History {
-    fn drop(&mut self) {
-        for i in 0..self.len {
-            unsafe { self.entries[i].assume_init_drop() }
-        }
+    /// Iterates from the first move of the game to the last one
+    pub(crate) fn iter(&self) -> impl DoubleEndedIterator<Item = &HistoryEntry> {
+        self.entries.iter()
     }
 }
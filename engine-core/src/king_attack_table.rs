@@ -57,8 +57,6 @@ mod tests {
     #[test]
     fn test_king_attacks_table() {
         for sq in Square::all() {
-            let sq = Square::try_from(sq).unwrap();
-
             println!("{sq}");
             helpers::print_bitboard(get_king_attacks_mask(sq));
         }
@@ -1,20 +1,41 @@
+mod bench;
 pub mod board;
+mod board_builder;
 mod chess_consts;
+mod endgame;
 mod enums;
-mod evaluation;
+mod epd_parser;
+pub mod evaluation;
 mod fen_parser;
+mod game_result;
 mod helpers;
 mod history;
 mod king_attack_table;
 mod knight_attack_table;
+mod line_table;
 pub mod messaging;
 mod move_generator;
 mod move_operations;
 mod move_ordering;
+mod move_picker;
+pub mod options;
 pub mod out;
 mod pawn_attack_table;
+mod pawn_hash;
 mod perft;
+pub mod pgn;
+mod position;
 mod random_generator;
+mod san;
 pub mod searching;
 mod sliding_piece_attack_table;
+mod transposition_table;
 pub mod uci;
+mod zobrist;
+
+pub use board_builder::BoardBuilder;
+pub use enums::{CastlingSide, Move, MoveFlags, Piece, Side, Square};
+pub use fen_parser::ParseFenError;
+pub use game_result::{DrawReason, GameResult};
+pub use move_operations::IllegalMove;
+pub use position::Position;
@@ -0,0 +1,166 @@
+use crate::{
+    chess_consts,
+    enums::Square,
+    sliding_piece_attack_table::{generate_bishop_attacks_mask, generate_rook_attacks_mask},
+};
+
+/// Squares strictly between two squares on the same rank, file or diagonal, empty for
+/// squares that are not aligned
+static BETWEEN_TABLE: [[u64; chess_consts::SQUARES_COUNT]; chess_consts::SQUARES_COUNT] = {
+    let mut table =
+        [[chess_consts::EMPTY_BB; chess_consts::SQUARES_COUNT]; chess_consts::SQUARES_COUNT];
+
+    let mut from = 0;
+    while from < chess_consts::SQUARES_COUNT {
+        let from_sq = unsafe { Square::from_u8_unchecked(from as u8) };
+
+        let mut to = 0;
+        while to < chess_consts::SQUARES_COUNT {
+            let to_sq = unsafe { Square::from_u8_unchecked(to as u8) };
+
+            // The rays from both squares, each blocked by the other one, overlap between them
+            if generate_rook_attacks_mask(from_sq, 0) & to_sq.bit() != 0 {
+                table[from][to] = generate_rook_attacks_mask(from_sq, to_sq.bit())
+                    & generate_rook_attacks_mask(to_sq, from_sq.bit());
+            } else if generate_bishop_attacks_mask(from_sq, 0) & to_sq.bit() != 0 {
+                table[from][to] = generate_bishop_attacks_mask(from_sq, to_sq.bit())
+                    & generate_bishop_attacks_mask(to_sq, from_sq.bit());
+            }
+
+            to += 1;
+        }
+
+        from += 1;
+    }
+
+    table
+};
+
+/// The whole rank, file or diagonal going through two squares, empty for squares that
+/// are not aligned
+static LINE_TABLE: [[u64; chess_consts::SQUARES_COUNT]; chess_consts::SQUARES_COUNT] = {
+    let mut table =
+        [[chess_consts::EMPTY_BB; chess_consts::SQUARES_COUNT]; chess_consts::SQUARES_COUNT];
+
+    let mut from = 0;
+    while from < chess_consts::SQUARES_COUNT {
+        let from_sq = unsafe { Square::from_u8_unchecked(from as u8) };
+
+        let mut to = 0;
+        while to < chess_consts::SQUARES_COUNT {
+            let to_sq = unsafe { Square::from_u8_unchecked(to as u8) };
+            let ends_bb = from_sq.bit() | to_sq.bit();
+
+            if generate_rook_attacks_mask(from_sq, 0) & to_sq.bit() != 0 {
+                table[from][to] = (generate_rook_attacks_mask(from_sq, 0)
+                    & generate_rook_attacks_mask(to_sq, 0))
+                    | ends_bb;
+            } else if generate_bishop_attacks_mask(from_sq, 0) & to_sq.bit() != 0 {
+                table[from][to] = (generate_bishop_attacks_mask(from_sq, 0)
+                    & generate_bishop_attacks_mask(to_sq, 0))
+                    | ends_bb;
+            }
+
+            to += 1;
+        }
+
+        from += 1;
+    }
+
+    table
+};
+
+/// Get the squares strictly between two aligned squares (pre-generated)
+/// # Examples
+/// A1 D4 -> B2 C3
+/// A1 B3 -> empty, the squares are not aligned
+pub(crate) fn get_between_mask(from: Square, to: Square) -> u64 {
+    BETWEEN_TABLE[from.index() as usize][to.index() as usize]
+}
+
+/// Get the whole line through two aligned squares, including both of them (pre-generated)
+/// # Examples
+/// B2 C3 -> the a1-h8 diagonal
+/// A1 B3 -> empty, the squares are not aligned
+pub(crate) fn get_line_mask(from: Square, to: Square) -> u64 {
+    LINE_TABLE[from.index() as usize][to.index() as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers;
+
+    use super::*;
+
+    #[test]
+    fn test_between_table() {
+        assert_eq!(
+            get_between_mask(Square::A1, Square::D4),
+            helpers::squares_mask([Square::B2, Square::C3])
+        );
+        assert_eq!(
+            get_between_mask(Square::E8, Square::E1),
+            helpers::squares_mask([
+                Square::E7,
+                Square::E6,
+                Square::E5,
+                Square::E4,
+                Square::E3,
+                Square::E2
+            ])
+        );
+        assert_eq!(get_between_mask(Square::H3, Square::F5), Square::G4.bit());
+        assert_eq!(
+            get_between_mask(Square::B1, Square::C1),
+            chess_consts::EMPTY_BB
+        );
+        assert_eq!(
+            get_between_mask(Square::A1, Square::B3),
+            chess_consts::EMPTY_BB
+        );
+        assert_eq!(
+            get_between_mask(Square::D4, Square::D4),
+            chess_consts::EMPTY_BB
+        );
+    }
+
+    #[test]
+    fn test_line_table() {
+        assert_eq!(
+            get_line_mask(Square::B2, Square::C3),
+            helpers::squares_mask([
+                Square::A1,
+                Square::B2,
+                Square::C3,
+                Square::D4,
+                Square::E5,
+                Square::F6,
+                Square::G7,
+                Square::H8,
+            ])
+        );
+        assert_eq!(
+            get_line_mask(Square::C4, Square::F4),
+            helpers::rank_mask(Square::C4.rank())
+        );
+        assert_eq!(
+            get_line_mask(Square::H3, Square::F5),
+            helpers::squares_mask([
+                Square::C8,
+                Square::D7,
+                Square::E6,
+                Square::F5,
+                Square::G4,
+                Square::H3
+            ])
+        );
+        assert_eq!(
+            get_line_mask(Square::A1, Square::B3),
+            chess_consts::EMPTY_BB
+        );
+        assert_eq!(
+            get_line_mask(Square::D4, Square::D4),
+            chess_consts::EMPTY_BB
+        );
+    }
+}
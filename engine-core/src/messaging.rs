@@ -1,10 +1,21 @@
-use std::{sync::mpsc, thread};
+use std::{
+    io::{BufRead, Write},
+    sync::{Arc, Mutex, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
 
 use crate::{
+    bench,
     board::Board,
+    enums::{Move, Side},
+    evaluation::{self, EvalParams, Wdl},
+    move_ordering::MoveOrderingState,
+    options::{self, EngineOptions},
     out,
-    searching::{self, StopToken},
-    uci::{self, GoMode, TimeControl},
+    searching::{self, Deadline, RootMoveScore, ScoreBound, SearchLimits, SearchResult, StopToken},
+    transposition_table::TranspositionTable,
+    uci::{self, TimeControl, UciGoCommand},
 };
 
 pub enum EngineEvent {
@@ -20,11 +31,50 @@ pub enum UciCommand {
     Stop,
     Quit,
     Ping(u64),
+    Eval,
+    SetOption(String),
+    PonderHit,
+    Bench,
+    /// "debug on" or "debug off"
+    Debug(bool),
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum SearchEvent {
-    BestMove { id: u64, mv: String },
+    /// The result of a search, the receiver writes it in its protocol
+    SearchInfo { id: u64, info: SearchInfo },
+    /// Diagnostics written as they are
+    Info { id: u64, info: String },
+    /// The root move the search is on, number counts the root moves from 1
+    CurrMove {
+        id: u64,
+        depth: u32,
+        mv: Move,
+        number: usize,
+    },
+    BestMove {
+        id: u64,
+        mv: String,
+        ponder: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchInfo {
+    pub depth: u32,
+    pub seldepth: u32,
+    /// Rank of the line among the best ones, 1 for the best
+    pub multipv: usize,
+    pub score: i32,
+    pub bound: ScoreBound,
+    /// Outcome chances derived from the score, only with UCI_ShowWDL on
+    pub wdl: Option<Wdl>,
+    pub nodes: usize,
+    /// Per mille of the transposition table in use
+    pub hashfull: usize,
+    pub time_ms: u64,
+    /// Principal variation starting with the best move
+    pub pv: Vec<Move>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -38,7 +88,25 @@ pub struct EngineWorkerHandler {
     pub join: std::thread::JoinHandle<()>,
 }
 
-const DEFAULT_DEPTH: u32 = 6;
+/// The clock time is shared out as if this many moves were left in sudden death
+const SUDDEN_DEATH_MOVES_TO_GO: u64 = 30;
+
+/// Best move reported when the game is over and there is no legal move to play
+const NO_MOVE: &str = "(none)";
+
+/// The root move being searched is only reported once the search has run this long,
+/// the short searches would flood the GUI with it
+const CURRMOVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Depth searched for a go command that can't be parsed, or a mate-only one without a
+/// mate. The GUI waits for a bestmove after every go, so a short search still answers it
+const FALLBACK_GO_DEPTH: u32 = 5;
+
+/// Number of root moves listed in debug mode
+const DEBUG_ROOT_MOVES_COUNT: usize = 5;
+
+const ENGINE_NAME: &str = "Orion";
+const AUTHOR_NAME: &str = "Voyager";
 
 pub fn spawn_worker() -> EngineWorkerHandler {
     let (ev_tx, ev_rx) = mpsc::channel::<EngineEvent>();
@@ -48,6 +116,14 @@ pub fn spawn_worker() -> EngineWorkerHandler {
 
     let join = std::thread::spawn(move || {
         let mut board: Board = Board::get_start_position();
+        // The last position command applied to the board
+        let mut position_cmd: Option<String> = None;
+        let mut options = EngineOptions::default();
+        let eval_params = Arc::new(EvalParams::default());
+        // Killers and history survive between the searches of one game
+        let ordering = Arc::new(Mutex::new(MoveOrderingState::new()));
+        // A search keeps the table it started with when Hash replaces it
+        let mut tt = Arc::new(TranspositionTable::new(options.hash_mb).unwrap());
 
         let stop_token = StopToken::new();
         let mut search_thread: Option<thread::JoinHandle<()>> = None;
@@ -64,27 +140,46 @@ pub fn spawn_worker() -> EngineWorkerHandler {
 
         let mut current_search_id = 0;
 
-        loop {
-            let cmd = match ev_rx.recv() {
-                Ok(cmd) => cmd,
-                Err(_) => break,
-            };
+        let mut pondering = false;
+        let mut pending_bestmove: Option<String> = None;
+        // The deadline of the ponder search and its time, armed at ponderhit
+        let mut ponder_clock: Option<(Deadline, Duration)> = None;
+        // Extra "info string" diagnostics are written while debug mode is on
+        let mut debug = false;
 
+        while let Ok(cmd) = ev_rx.recv() {
             match cmd {
                 EngineEvent::Uci(UciCommand::Ping(id)) => {
                     engine_res_tx.send(EngineResponse::Pong(id)).ok();
                 }
                 EngineEvent::Uci(UciCommand::NewGame) => {
                     stop_search(&stop_token, &mut search_thread);
+                    pondering = false;
+                    pending_bestmove = None;
+                    ordering.lock().unwrap().clear();
+                    tt.clear();
                     board = Board::get_start_position();
+                    board.chess960 = options.chess960;
+                    position_cmd = None;
                 }
                 EngineEvent::Uci(UciCommand::Position(pos_cmd)) => {
                     stop_search(&stop_token, &mut search_thread);
-                    match uci::parse_uci_position_command(&pos_cmd) {
-                        Ok(b) => board = b,
-                        Err(_) => {
-                            out::write_line("bestmove 0000");
-                        }
+                    pondering = false;
+                    pending_bestmove = None;
+                    // A malformed command keeps the previous position, so the next go still
+                    // has a legal position to search
+                    match uci::update_board_from_position_command(
+                        &mut board,
+                        position_cmd.as_deref(),
+                        &pos_cmd,
+                        options.chess960,
+                    ) {
+                        Ok(()) => position_cmd = Some(pos_cmd),
+                        Err(err) => out::write_line(&format!("info string {err}")),
+                    }
+
+                    if debug {
+                        out::write_line(&format!("info string position {}", board.to_fen()));
                     }
                 }
                 EngineEvent::Uci(UciCommand::Go(go_cmd)) => {
@@ -92,41 +187,224 @@ pub fn spawn_worker() -> EngineWorkerHandler {
 
                     stop_token.reset();
 
+                    let go_cmd =
+                        uci::parse_uci_go_commmand(&go_cmd, &mut board).unwrap_or_else(|err| {
+                            out::write_line(&format!("info string {err}"));
+
+                            UciGoCommand {
+                                depth: Some(FALLBACK_GO_DEPTH),
+                                movetime: None,
+                                infinite: false,
+                                tc: TimeControl::default(),
+                                search_moves: None,
+                                nodes: None,
+                                mate: None,
+                                ponder: false,
+                            }
+                        });
+
+                    pending_bestmove = None;
+
                     current_search_id += 1;
                     let search_id = current_search_id;
 
                     let ev_tx = ev_tx.clone();
 
                     let mut b = board.clone();
+                    b.set_eval_params(Arc::clone(&eval_params));
                     let stop = stop_token.clone();
+                    let ordering = Arc::clone(&ordering);
+                    let tt = Arc::clone(&tt);
+
+                    // All the given limits apply and the first one reached ends the search:
+                    // the depth, the nodes and the time, which is the movetime if there
+                    // is one and the share of the clock otherwise. "go infinite" drops
+                    // the clock, a search without any limit only ends with "stop"
+                    let time_budget = match go_cmd.movetime {
+                        Some(ms) => Some(move_time_budget(ms, options.move_overhead_ms)),
+                        None => clock_time_budget(
+                            &go_cmd.tc,
+                            board.game_state.side_to_move,
+                            options.move_overhead_ms,
+                        ),
+                    };
+
+                    // The clock of a ponder search only starts running at ponderhit
+                    let deadline = if go_cmd.ponder {
+                        Deadline::default()
+                    } else {
+                        Deadline::new(time_budget.map(|budget| Instant::now() + budget))
+                    };
+                    ponder_clock = time_budget
+                        .filter(|_| go_cmd.ponder)
+                        .map(|budget| (deadline.clone(), budget));
+
+                    let limits = SearchLimits {
+                        nodes: go_cmd.nodes.map(|nodes| nodes as usize),
+                        deadline,
+                    };
+
+                    // A go with only a mate limit falls back to a short search when there
+                    // is no mate, nothing else would end it
+                    let mate_only = go_cmd.mate.is_some()
+                        && !go_cmd.infinite
+                        && limits.nodes.is_none()
+                        && time_budget.is_none();
+                    let depth = go_cmd.depth.unwrap_or(if mate_only {
+                        FALLBACK_GO_DEPTH
+                    } else {
+                        searching::MAX_SEARCH_DEPTH
+                    });
+
+                    // While pondering the bestmove is held back until ponderhit or stop,
+                    // and so it is for an infinite search that ran out of depth
+                    let infinite = go_cmd.infinite
+                        && limits.nodes.is_none()
+                        && time_budget.is_none()
+                        && go_cmd.mate.is_none();
+                    pondering = go_cmd.ponder || infinite;
+
+                    if debug {
+                        let move_time = time_budget.map_or("none".to_string(), |budget| {
+                            format!("{} ms", budget.as_millis())
+                        });
+                        let nodes = limits
+                            .nodes
+                            .map_or("none".to_string(), |nodes| nodes.to_string());
+
+                        out::write_line(&format!(
+                            "info string search depth {depth} nodes {nodes} movetime {move_time}"
+                        ));
+                    }
 
                     let handle = thread::spawn(move || {
-                        let go_cmd =
-                            uci::parse_uci_go_commmand(&go_cmd)
-                                .ok()
-                                .unwrap_or(uci::UciGoCommand {
-                                    mode: uci::GoMode::Depth(5),
-                                    tc: TimeControl::default(),
-                                    search_moves: None,
-                                    nodes: None,
-                                    mate: None,
-                                });
-                        let depth = if let GoMode::Depth(depth) = go_cmd.mode {
-                            depth
-                        } else {
-                            DEFAULT_DEPTH
+                        let start = Instant::now();
+
+                        let mut ordering = ordering.lock().unwrap();
+                        let phase = evaluation::calc_phase(&b);
+
+                        // The lines are reported after every iteration
+                        let send_infos = |results: &[SearchResult]| {
+                            // The last line was searched last, its count covers the others
+                            let nodes = results.last().map_or(0, |result| result.nodes);
+                            let time_ms = start.elapsed().as_millis() as u64;
+
+                            for (i, result) in results.iter().enumerate() {
+                                ev_tx
+                                    .send(EngineEvent::Search(SearchEvent::SearchInfo {
+                                        id: search_id,
+                                        info: SearchInfo {
+                                            depth: result.depth,
+                                            seldepth: result.seldepth,
+                                            multipv: i + 1,
+                                            score: result.score,
+                                            bound: result.bound,
+                                            wdl: options.show_wdl.then(|| {
+                                                evaluation::score_to_wdl(result.score, phase)
+                                            }),
+                                            nodes,
+                                            hashfull: tt.hashfull(),
+                                            time_ms,
+                                            pv: result.pv.clone(),
+                                        },
+                                    }))
+                                    .ok();
+                            }
+                        };
+
+                        let send_currmove = |depth, mv, number| {
+                            if start.elapsed() >= CURRMOVE_DELAY {
+                                ev_tx
+                                    .send(EngineEvent::Search(SearchEvent::CurrMove {
+                                        id: search_id,
+                                        depth,
+                                        mv,
+                                        number,
+                                    }))
+                                    .ok();
+                            }
+                        };
+
+                        // Without a forced mate the best move is searched for as usual
+                        let search_moves = go_cmd.search_moves.as_deref().unwrap_or_default();
+                        let mate_result = go_cmd.mate.and_then(|moves| {
+                            searching::search_mate(
+                                &mut b,
+                                moves,
+                                &stop,
+                                &mut ordering,
+                                limits.clone(),
+                                search_moves,
+                            )
+                        });
+
+                        let results = match mate_result {
+                            Some(result) => {
+                                send_infos(std::slice::from_ref(&result));
+                                vec![result]
+                            }
+                            None => searching::search_iterative(
+                                &mut b,
+                                depth,
+                                &stop,
+                                &mut ordering,
+                                &tt,
+                                &options,
+                                limits,
+                                search_moves,
+                                send_infos,
+                                send_currmove,
+                            ),
                         };
+                        drop(ordering);
 
-                        let mv = searching::search_bestmove(&mut b, depth, &stop);
-                        let mv_str = match mv {
-                            Some(mv) => uci::serialize_move_to_uci_str(mv),
-                            None => "0000".to_string(),
+                        let Some(result) = results.first() else {
+                            ev_tx
+                                .send(EngineEvent::Search(SearchEvent::BestMove {
+                                    id: search_id,
+                                    mv: NO_MOVE.to_string(),
+                                    ponder: None,
+                                }))
+                                .ok();
+                            return;
                         };
 
+                        let mv = uci::serialize_board_move_to_uci_str(&b, result.best_move);
+                        let root_moves_info =
+                            debug.then(|| format_root_moves(&b, &result.root_moves));
+
+                        // The expected reply is only known when the PV starts with the best move
+                        let ponder = match result.pv.as_slice() {
+                            [first, reply, ..] if *first == result.best_move => {
+                                Some(uci::serialize_board_move_to_uci_str(&b, *reply))
+                            }
+                            _ => None,
+                        };
+
+                        if let Some(root_moves_info) = root_moves_info {
+                            let infos = [
+                                root_moves_info,
+                                format!(
+                                    "info string search finished in {} ms",
+                                    start.elapsed().as_millis()
+                                ),
+                            ];
+
+                            for info in infos {
+                                ev_tx
+                                    .send(EngineEvent::Search(SearchEvent::Info {
+                                        id: search_id,
+                                        info,
+                                    }))
+                                    .ok();
+                            }
+                        }
+
                         ev_tx
                             .send(EngineEvent::Search(SearchEvent::BestMove {
                                 id: search_id,
-                                mv: mv_str,
+                                mv,
+                                ponder,
                             }))
                             .ok();
                     });
@@ -134,8 +412,20 @@ pub fn spawn_worker() -> EngineWorkerHandler {
                     search_thread = Some(handle);
                 }
                 EngineEvent::Uci(UciCommand::Stop) => {
+                    pondering = false;
+
+                    if let Some(bestmove) = pending_bestmove.take() {
+                        out::write_line(&bestmove);
+
+                        if let Some(h) = search_thread.take() {
+                            let _ = h.join();
+                        }
+                        continue;
+                    }
+
                     if search_thread.is_none() {
-                        out::write_line("bestmove 0000");
+                        let mv = get_quick_bestmove(&board, &eval_params, &ordering, &tt);
+                        out::write_line(&format!("bestmove {mv}"));
                         continue;
                     }
 
@@ -145,16 +435,129 @@ pub fn spawn_worker() -> EngineWorkerHandler {
                         let _ = h.join();
                     }
                 }
+                EngineEvent::Uci(UciCommand::PonderHit) => {
+                    // The opponent played the expected move, the ponder search goes on as a normal one
+                    pondering = false;
+
+                    if let Some((deadline, budget)) = ponder_clock.take() {
+                        deadline.set(Instant::now() + budget);
+                    }
+
+                    if let Some(bestmove) = pending_bestmove.take() {
+                        out::write_line(&bestmove);
+                    }
+                }
+                EngineEvent::Uci(UciCommand::Eval) => {
+                    let breakdown = evaluation::evaluate_breakdown(&board);
+
+                    for line in breakdown.to_string().lines() {
+                        out::write_line(line);
+                    }
+                }
+                EngineEvent::Uci(UciCommand::Debug(on)) => debug = on,
+                EngineEvent::Uci(UciCommand::Bench) => {
+                    stop_search(&stop_token, &mut search_thread);
+                    pondering = false;
+                    pending_bestmove = None;
+
+                    let result = bench::run_bench(bench::BENCH_DEPTH);
+
+                    out::write_line(&format!("Total time (ms): {}", result.elapsed.as_millis()));
+                    out::write_line(&format!("Nodes searched: {}", result.nodes));
+                    out::write_line(&format!("Nodes/second: {}", result.nps()));
+                }
+                EngineEvent::Uci(UciCommand::SetOption(option_cmd)) => {
+                    let Ok((name, value)) = uci::parse_uci_setoption_command(&option_cmd) else {
+                        continue;
+                    };
+
+                    let hash_mb = options.hash_mb;
+                    if let Err(err) = options.set(&name, value.as_deref()) {
+                        out::write_line(&format!("info string {name}: {err}"));
+                        continue;
+                    }
+
+                    // The table is only replaced once the new one is allocated, a size
+                    // that doesn't fit keeps the old one
+                    if name.eq_ignore_ascii_case(options::HASH_OPTION) {
+                        match TranspositionTable::new(options.hash_mb) {
+                            Ok(table) => tt = Arc::new(table),
+                            Err(err) => {
+                                out::write_line(&format!("info string {name}: {err}"));
+                                options.hash_mb = hash_mb;
+                            }
+                        }
+                    }
+
+                    // The transposition table and the move ordering tables are all a
+                    // fresh engine would not have
+                    if name.eq_ignore_ascii_case(options::CLEAR_HASH_OPTION) {
+                        stop_search(&stop_token, &mut search_thread);
+                        pondering = false;
+                        pending_bestmove = None;
+                        ordering.lock().unwrap().clear();
+                        tt.clear();
+                    }
+
+                    board.chess960 = options.chess960;
+                }
                 EngineEvent::Uci(UciCommand::Quit) => {
                     stop_search(&stop_token, &mut search_thread);
+
+                    // The best move of a search stopped right before quitting may still
+                    // be queued behind the quit
+                    for event in ev_rx.try_iter() {
+                        if let EngineEvent::Search(SearchEvent::BestMove { id, mv, ponder }) = event
+                            && id == current_search_id
+                            && !pondering
+                        {
+                            out::write_line(&format_bestmove(&mv, ponder.as_deref()));
+                        }
+                    }
+
                     break;
                 }
-                EngineEvent::Search(SearchEvent::BestMove { id, mv }) => {
+                EngineEvent::Search(SearchEvent::SearchInfo { id, info }) => {
+                    if id != current_search_id {
+                        continue;
+                    }
+
+                    out::write_line(&format_search_info(&board, &info));
+                }
+                EngineEvent::Search(SearchEvent::CurrMove {
+                    id,
+                    depth,
+                    mv,
+                    number,
+                }) => {
+                    if id != current_search_id {
+                        continue;
+                    }
+
+                    out::write_line(&format!(
+                        "info depth {depth} currmove {} currmovenumber {number}",
+                        uci::serialize_board_move_to_uci_str(&board, mv)
+                    ));
+                }
+                EngineEvent::Search(SearchEvent::Info { id, info }) => {
                     if id != current_search_id {
                         continue;
                     }
 
-                    out::write_line(&format!("bestmove {mv}"));
+                    out::write_line(&info);
+                }
+                EngineEvent::Search(SearchEvent::BestMove { id, mv, ponder }) => {
+                    if id != current_search_id {
+                        continue;
+                    }
+
+                    let bestmove = format_bestmove(&mv, ponder.as_deref());
+
+                    if pondering {
+                        pending_bestmove = Some(bestmove);
+                    } else {
+                        out::write_line(&bestmove);
+                    }
                 }
             }
         }
@@ -163,6 +566,813 @@ pub fn spawn_worker() -> EngineWorkerHandler {
     EngineWorkerHandler {
         engine_events_tx: ev_tx_clone,
         engine_respones_rx: engine_res_rx,
-        join: join,
+        join,
+    }
+}
+
+/// Share of the remaining clock time given to the move: the time left for the moves to
+/// the next time control, 30 of them in sudden death, and most of the increment. None
+/// without the clock of the side to move
+/// # Examples
+/// wtime 60000 winc 1000, white to move -> 2000 + 750 - overhead ms
+fn clock_time_budget(tc: &TimeControl, side: Side, overhead_ms: u64) -> Option<Duration> {
+    let (time, inc) = match side {
+        Side::White => (tc.wtime?, tc.winc.unwrap_or(0)),
+        Side::Black => (tc.btime?, tc.binc.unwrap_or(0)),
+    };
+    let moves_to_go = tc.movestogo.unwrap_or(SUDDEN_DEATH_MOVES_TO_GO).max(1);
+
+    let budget = (time / moves_to_go + inc * 3 / 4).min(time);
+
+    Some(move_time_budget(budget, overhead_ms))
+}
+
+/// Time the search may take out of the given move time, the overhead is kept back for
+/// the GUI and the network, so the move arrives before the time runs out
+/// # Examples
+/// 1000 ms, overhead 30 ms -> 970 ms
+/// 20 ms, overhead 30 ms -> 0 ms
+fn move_time_budget(move_time_ms: u64, overhead_ms: u64) -> Duration {
+    Duration::from_millis(move_time_ms.saturating_sub(overhead_ms))
+}
+
+/// Writes the search result as a UCI info line, e.g.
+/// "info depth 5 seldepth 9 multipv 1 score cp 35 nodes 12000 hashfull 3 time 40 pv e2e4 e7e5"
+fn format_search_info(board: &Board, info: &SearchInfo) -> String {
+    let mut line = format!(
+        "info depth {} seldepth {} multipv {} score {}",
+        info.depth,
+        info.seldepth,
+        info.multipv,
+        uci::serialize_score_to_uci_str(info.score, info.bound),
+    );
+
+    if let Some(wdl) = info.wdl {
+        line.push_str(&format!(" wdl {} {} {}", wdl.win, wdl.draw, wdl.loss));
+    }
+
+    line.push_str(&format!(
+        " nodes {} hashfull {} time {}",
+        info.nodes, info.hashfull, info.time_ms
+    ));
+
+    if !info.pv.is_empty() {
+        let pv: Vec<String> = info
+            .pv
+            .iter()
+            .map(|&mv| uci::serialize_board_move_to_uci_str(board, mv))
+            .collect();
+        line.push_str(&format!(" pv {}", pv.join(" ")));
+    }
+
+    line
+}
+
+/// Lists the best scored root moves, e.g.
+/// "info string root moves e2e4 cp 35, d2d4 cp 20 upperbound"
+fn format_root_moves(board: &Board, root_moves: &[RootMoveScore]) -> String {
+    let mut root_moves = root_moves.to_vec();
+    root_moves.sort_by_key(|root| std::cmp::Reverse(root.score));
+
+    let moves: Vec<String> = root_moves
+        .iter()
+        .take(DEBUG_ROOT_MOVES_COUNT)
+        .map(|root| {
+            format!(
+                "{} {}",
+                uci::serialize_board_move_to_uci_str(board, root.mv),
+                uci::serialize_score_to_uci_str(root.score, root.bound)
+            )
+        })
+        .collect();
+
+    format!("info string root moves {}", moves.join(", "))
+}
+
+fn format_bestmove(mv: &str, ponder: Option<&str>) -> String {
+    match ponder {
+        Some(ponder) => format!("bestmove {mv} ponder {ponder}"),
+        None => format!("bestmove {mv}"),
+    }
+}
+
+/// Reads the UCI commands line by line and answers them through the output sink, until
+/// "quit" or the end of the input. Searches run on the worker, so only "uci" and
+/// "isready" are answered before the next line is read
+/// # Examples
+/// run_uci_loop(std::io::stdin().lock(), std::io::stdout());
+pub fn run_uci_loop(input: impl BufRead, output: impl Write + Send + 'static) {
+    out::init_out(output);
+
+    let engine_worker_handler = spawn_worker();
+
+    let mut ping_id: u64 = 1;
+
+    for line in input.lines() {
+        let line = match line {
+            Ok(s) => s.trim().to_string(),
+            Err(_) => break,
+        };
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if line == "uci" {
+            out::write_line(&format!("id name {}", ENGINE_NAME));
+            out::write_line(&format!("id author {}", AUTHOR_NAME));
+            for option in &options::UCI_OPTIONS {
+                out::write_line(&option.to_string());
+            }
+            out::write_line("uciok");
+            continue;
+        }
+
+        if line == "isready" {
+            let id = ping_id;
+            ping_id = ping_id.wrapping_add(1);
+
+            engine_worker_handler
+                .engine_events_tx
+                .send(EngineEvent::Uci(UciCommand::Ping(id)))
+                .ok();
+
+            loop {
+                match engine_worker_handler
+                    .engine_respones_rx
+                    .recv_timeout(Duration::from_millis(200))
+                {
+                    Ok(EngineResponse::Pong(x)) if x == id => {
+                        out::write_line("readyok");
+                        break;
+                    }
+                    Ok(EngineResponse::Pong(_)) => {
+                        continue;
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        continue;
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            continue;
+        }
+
+        let cmd = match line.as_str() {
+            "ucinewgame" => UciCommand::NewGame,
+            "stop" => UciCommand::Stop,
+            "ponderhit" => UciCommand::PonderHit,
+            "eval" => UciCommand::Eval,
+            "bench" => UciCommand::Bench,
+            "debug on" => UciCommand::Debug(true),
+            "debug off" => UciCommand::Debug(false),
+            "quit" => UciCommand::Quit,
+            _ if line.starts_with("position ") => UciCommand::Position(line),
+            _ if line.starts_with("go") => UciCommand::Go(line),
+            _ if line.starts_with("setoption ") => UciCommand::SetOption(line),
+            _ => continue,
+        };
+
+        if cmd == UciCommand::Quit {
+            break;
+        }
+
+        engine_worker_handler
+            .engine_events_tx
+            .send(EngineEvent::Uci(cmd))
+            .ok();
+    }
+
+    // The end of the input quits too, the worker would wait for commands forever otherwise
+    engine_worker_handler
+        .engine_events_tx
+        .send(EngineEvent::Uci(UciCommand::Quit))
+        .ok();
+
+    let _ = engine_worker_handler.join.join().ok();
+}
+
+/// Answers a "stop" that came without a running search, a depth 1 search is enough to
+/// come up with a legal move. NO_MOVE is only sent when the game is over
+fn get_quick_bestmove(
+    board: &Board,
+    eval_params: &Arc<EvalParams>,
+    ordering: &Mutex<MoveOrderingState>,
+    tt: &TranspositionTable,
+) -> String {
+    let mut board = board.clone();
+    board.set_eval_params(Arc::clone(eval_params));
+
+    let result = searching::search_bestmove(
+        &mut board,
+        1,
+        &StopToken::new(),
+        &mut ordering.lock().unwrap(),
+        tt,
+    );
+
+    match result {
+        Some(result) => uci::serialize_board_move_to_uci_str(&board, result.best_move),
+        None => NO_MOVE.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, sync::MutexGuard};
+
+    use crate::{
+        chess_consts,
+        enums::{CastlingSide, Side},
+        out::MemorySink,
+    };
+
+    use super::*;
+
+    fn run_session(commands: &[&str]) -> Vec<String> {
+        let _guard = out::TEST_OUT_LOCK.lock().unwrap();
+
+        let sink = MemorySink::new();
+        run_uci_loop(Cursor::new(commands.join("\n")), sink.clone());
+
+        sink.take_lines()
+    }
+
+    /// A worker driven directly, ending the input would stop its search
+    struct WorkerSession {
+        worker: EngineWorkerHandler,
+        sink: MemorySink,
+        _guard: MutexGuard<'static, ()>,
+    }
+
+    impl WorkerSession {
+        fn start(commands: impl IntoIterator<Item = UciCommand>) -> Self {
+            let guard = out::TEST_OUT_LOCK.lock().unwrap();
+
+            let sink = MemorySink::new();
+            out::init_out(sink.clone());
+
+            let session = WorkerSession {
+                worker: spawn_worker(),
+                sink,
+                _guard: guard,
+            };
+            for cmd in commands {
+                session.send(cmd);
+            }
+
+            session
+        }
+
+        fn send(&self, cmd: UciCommand) {
+            self.worker
+                .engine_events_tx
+                .send(EngineEvent::Uci(cmd))
+                .unwrap();
+        }
+
+        fn has_bestmove(&self) -> bool {
+            self.sink.contents().contains("bestmove")
+        }
+
+        /// Waits up to 10 seconds for the bestmove, returns how long it took
+        fn wait_for_bestmove(&self) -> Duration {
+            let start = Instant::now();
+            while !self.has_bestmove() && start.elapsed() < Duration::from_secs(10) {
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            start.elapsed()
+        }
+
+        /// The lines written so far, they are not returned again
+        fn take_lines(&self) -> Vec<String> {
+            self.sink.take_lines()
+        }
+
+        /// Quits the worker, returns all it wrote
+        fn finish(self) -> Vec<String> {
+            self.send(UciCommand::Quit);
+            self.worker.join.join().unwrap();
+
+            self.sink.take_lines()
+        }
+    }
+
+    /// Sends the commands to a worker and waits for the bestmove, returns the output and
+    /// how long the bestmove took
+    fn run_worker_session(commands: &[&str]) -> (Vec<String>, Duration) {
+        let session = WorkerSession::start(commands.iter().map(|&line| match line {
+            _ if line.starts_with("position ") => UciCommand::Position(line.to_string()),
+            _ if line.starts_with("setoption ") => UciCommand::SetOption(line.to_string()),
+            _ => UciCommand::Go(line.to_string()),
+        }));
+        let elapsed = session.wait_for_bestmove();
+
+        (session.finish(), elapsed)
+    }
+
+    /// The depths of the "info depth" lines, in order
+    fn info_depths(lines: &[String]) -> Vec<u32> {
+        lines
+            .iter()
+            .filter_map(|line| line.strip_prefix("info depth "))
+            .map(|line| line.split(' ').next().unwrap().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_uci_session() {
+        let lines = run_session(&[
+            "uci",
+            "isready",
+            "position startpos moves e2e4",
+            "go depth 1",
+            "stop",
+            "quit",
+        ]);
+
+        let options_count = options::UCI_OPTIONS.len();
+        assert_eq!(lines[0], "id name Orion");
+        assert_eq!(lines[1], "id author Voyager");
+        assert!(
+            lines[2..2 + options_count]
+                .iter()
+                .all(|line| line.starts_with("option name "))
+        );
+        assert_eq!(lines[2 + options_count], "uciok");
+        assert_eq!(lines[3 + options_count], "readyok");
+
+        // A single bestmove with a legal reply to e2e4, whether the stop came in time or not
+        let bestmoves: Vec<&String> = lines
+            .iter()
+            .filter(|line| line.starts_with("bestmove "))
+            .collect();
+        assert_eq!(bestmoves.len(), 1, "{lines:?}");
+        assert_eq!(lines.last(), Some(bestmoves[0]));
+
+        let mut board =
+            uci::parse_uci_position_command("position startpos moves e2e4", false).unwrap();
+        let mv_str = bestmoves[0].strip_prefix("bestmove ").unwrap();
+        assert!(Move::parse(mv_str, &mut board).is_some(), "{mv_str}");
+    }
+
+    #[test]
+    fn test_bestmove_without_legal_moves() {
+        // Fool's mate and a stalemate, searched or answered to a bare stop
+        for position in [
+            "position startpos moves f2f3 e7e5 g2g4 d8h4",
+            "position fen k7/8/1Q6/8/8/8/8/7K b - - 0 1",
+        ] {
+            for cmds in [["go depth 3", "quit"], ["stop", "quit"]] {
+                let lines = run_session(&[position, cmds[0], cmds[1]]);
+                assert_eq!(lines, ["bestmove (none)"], "{position}: {cmds:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_uci_session_ends_with_the_input() {
+        let lines = run_session(&["isready", "position startpos", "eval"]);
+
+        assert_eq!(lines[0], "readyok");
+        assert_eq!(lines.last().unwrap(), "Evaluation: 0 (white side)");
+
+        // Nothing after quit is answered
+        let lines = run_session(&["quit", "isready"]);
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn test_multipv_session() {
+        let (lines, _) = run_worker_session(&[
+            "setoption name MultiPV value 2",
+            "position startpos",
+            "go depth 2",
+        ]);
+
+        let infos: Vec<&String> = lines
+            .iter()
+            .filter(|line| line.starts_with("info depth 2"))
+            .collect();
+        assert_eq!(infos.len(), 2, "{lines:?}");
+        assert!(infos[0].contains(" multipv 1 "));
+        assert!(infos[1].contains(" multipv 2 "));
+
+        // The best move is the one of the first line
+        let first_move = infos[0].split(" pv ").nth(1).unwrap().split(' ').next();
+        let bestmove = lines.iter().find_map(|line| line.strip_prefix("bestmove "));
+        assert_eq!(bestmove.and_then(|mv| mv.split(' ').next()), first_move);
+    }
+
+    #[test]
+    fn test_stop_reports_the_best_move_so_far() {
+        // Neither search would end on its own before the stop
+        for go in ["go infinite", "go depth 40"] {
+            let session = WorkerSession::start([
+                UciCommand::Position("position startpos moves e2e4".to_string()),
+                UciCommand::Go(go.to_string()),
+            ]);
+
+            thread::sleep(Duration::from_millis(100));
+            assert!(!session.has_bestmove(), "{go}");
+            session.send(UciCommand::Stop);
+            assert!(session.wait_for_bestmove() < Duration::from_secs(2), "{go}");
+
+            let lines = session.finish();
+            let bestmove = lines
+                .iter()
+                .find_map(|line| line.strip_prefix("bestmove "))
+                .and_then(|mv| mv.split(' ').next())
+                .unwrap();
+
+            let mut board =
+                uci::parse_uci_position_command("position startpos moves e2e4", false).unwrap();
+            assert!(
+                Move::parse(bestmove, &mut board).is_some(),
+                "{go}: {bestmove}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_clear_hash() {
+        // The button takes no value and is not an unknown option
+        let lines = run_session(&[
+            "position startpos",
+            "go depth 2",
+            "setoption name Clear Hash",
+            "isready",
+        ]);
+
+        assert!(!lines.iter().any(|line| line.starts_with("info string")));
+        assert!(lines.contains(&"readyok".to_string()));
+    }
+
+    #[test]
+    fn test_cleared_engine_searches_as_a_fresh_one() {
+        let position = UciCommand::Position(format!(
+            "position fen {}",
+            chess_consts::fen_strings::CMK_POS_FEN
+        ));
+        let go = |depth: u32| UciCommand::Go(format!("go depth {depth}"));
+
+        // The search lines without their times, the rest only depends on the search
+        let infos = |lines: Vec<String>| -> Vec<String> {
+            lines
+                .iter()
+                .filter(|line| line.starts_with("info depth ") && !line.contains(" currmove "))
+                .map(|line| {
+                    let tokens: Vec<&str> = line.split(' ').collect();
+                    let time = tokens.iter().position(|&token| token == "time").unwrap();
+
+                    [&tokens[..time], &tokens[time + 2..]].concat().join(" ")
+                })
+                .collect()
+        };
+
+        let fresh = WorkerSession::start([position.clone(), go(5)]);
+        fresh.wait_for_bestmove();
+        let fresh_infos = infos(fresh.finish());
+
+        // The deeper search of the same position would leave its entries behind
+        for clear in [
+            UciCommand::SetOption("setoption name Clear Hash".to_string()),
+            UciCommand::NewGame,
+        ] {
+            let session = WorkerSession::start([position.clone(), go(6)]);
+            session.wait_for_bestmove();
+            session.take_lines();
+
+            for cmd in [clear.clone(), position.clone(), go(5)] {
+                session.send(cmd);
+            }
+            session.wait_for_bestmove();
+
+            assert_eq!(infos(session.finish()), fresh_infos, "{clear:?}");
+        }
+    }
+
+    #[test]
+    fn test_hash_option() {
+        // The search answers without a table too, the new size takes effect right away
+        for hash_mb in [0, 1] {
+            let setoption = format!("setoption name Hash value {hash_mb}");
+            let (lines, _) = run_worker_session(&[&setoption, "position startpos", "go depth 4"]);
+
+            assert!(!lines.iter().any(|line| line.starts_with("info string")));
+            assert!(lines.last().unwrap().starts_with("bestmove "), "{lines:?}");
+
+            // The table fills up as the search goes, a table of no entries stays empty
+            let hashfull: usize = lines
+                .iter()
+                .rev()
+                .find_map(|line| line.split(" hashfull ").nth(1))
+                .and_then(|rest| rest.split(' ').next()?.parse().ok())
+                .unwrap();
+            assert_eq!(hashfull > 0, hash_mb > 0, "{lines:?}");
+        }
+    }
+
+    #[test]
+    fn test_threads_option() {
+        // The helpers end with the main thread, whichever limit ends its search
+        for go in ["go depth 5", "go movetime 300"] {
+            let (lines, elapsed) = run_worker_session(&[
+                "setoption name Threads value 2",
+                "position startpos moves e2e4",
+                go,
+            ]);
+            assert!(elapsed < Duration::from_secs(5), "{go}");
+            assert!(!lines.iter().any(|line| line.starts_with("info string")));
+
+            let bestmove = lines
+                .last()
+                .and_then(|line| line.strip_prefix("bestmove "))
+                .and_then(|mv| mv.split(' ').next())
+                .unwrap();
+            let mut board =
+                uci::parse_uci_position_command("position startpos moves e2e4", false).unwrap();
+            assert!(
+                Move::parse(bestmove, &mut board).is_some(),
+                "{go}: {bestmove}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_debug_mode() {
+        let lines = run_session(&[
+            "debug on",
+            "position startpos moves e2e4",
+            "go depth 1 nodes 500",
+            "isready",
+            "debug off",
+            "position startpos",
+            "quit",
+        ]);
+
+        assert_eq!(
+            lines[0],
+            "info string position rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+        assert_eq!(
+            lines[1],
+            "info string search depth 1 nodes 500 movetime none"
+        );
+
+        // Nothing is reported about the second position
+        assert!(
+            !lines
+                .iter()
+                .any(|line| line.ends_with("8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"))
+        );
+    }
+
+    #[test]
+    fn test_format_root_moves() {
+        let mut board = Board::get_start_position();
+        let root_moves: Vec<RootMoveScore> = ["g1f3", "e2e4", "a2a3", "d2d4", "b1c3", "h2h4"]
+            .into_iter()
+            .zip([10, 35, -20, 20, 5, -40])
+            .map(|(mv_str, score)| RootMoveScore {
+                mv: Move::parse(mv_str, &mut board).unwrap(),
+                score,
+                bound: if score == 35 {
+                    searching::ScoreBound::Exact
+                } else {
+                    searching::ScoreBound::Upper
+                },
+            })
+            .collect();
+
+        assert_eq!(
+            format_root_moves(&board, &root_moves),
+            "info string root moves e2e4 cp 35, d2d4 cp 20 upperbound, g1f3 cp 10 upperbound, \
+             b1c3 cp 5 upperbound, a2a3 cp -20 upperbound"
+        );
+    }
+
+    #[test]
+    fn test_format_search_info() {
+        let fen = "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w GBgb - 0 1";
+        let mut board =
+            uci::parse_uci_position_command(&format!("position fen {fen}"), true).unwrap();
+
+        // The black castling is written without playing the white move first
+        let white_castle = board
+            .castling_config
+            .get_castling_move(Side::White, CastlingSide::KingSide);
+        let black_castle = board
+            .castling_config
+            .get_castling_move(Side::Black, CastlingSide::QueenSide);
+        let info = SearchInfo {
+            depth: 3,
+            seldepth: 7,
+            multipv: 2,
+            score: -20,
+            bound: ScoreBound::Lower,
+            wdl: None,
+            nodes: 1234,
+            hashfull: 12,
+            time_ms: 56,
+            pv: vec![white_castle, black_castle],
+        };
+
+        assert_eq!(
+            format_search_info(&board, &info),
+            "info depth 3 seldepth 7 multipv 2 score cp -20 lowerbound nodes 1234 hashfull 12 time 56 pv e1g1 e8b8"
+        );
+
+        let mv = Move::parse("e1e2", &mut board).unwrap();
+        let info = SearchInfo {
+            pv: vec![mv],
+            score: evaluation::MATE_EVALUATION - 1,
+            bound: ScoreBound::Exact,
+            ..info
+        };
+        assert_eq!(
+            format_search_info(&board, &info),
+            "info depth 3 seldepth 7 multipv 2 score mate 1 nodes 1234 hashfull 12 time 56 pv e1e2"
+        );
+
+        let info = SearchInfo {
+            score: 0,
+            wdl: Some(Wdl {
+                win: 80,
+                draw: 840,
+                loss: 80,
+            }),
+            pv: Vec::new(),
+            ..info
+        };
+        assert_eq!(
+            format_search_info(&board, &info),
+            "info depth 3 seldepth 7 multipv 2 score cp 0 wdl 80 840 80 nodes 1234 hashfull 12 time 56"
+        );
+    }
+
+    #[test]
+    fn test_move_time_budget() {
+        assert_eq!(move_time_budget(1000, 30), Duration::from_millis(970));
+        assert_eq!(move_time_budget(1000, 0), Duration::from_millis(1000));
+        assert_eq!(move_time_budget(20, 30), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_clock_time_budget() {
+        let tc = TimeControl {
+            wtime: Some(60_000),
+            btime: Some(3_000),
+            winc: Some(1_000),
+            binc: None,
+            movestogo: None,
+        };
+
+        assert_eq!(
+            clock_time_budget(&tc, Side::White, 30),
+            Some(Duration::from_millis(2_000 + 750 - 30))
+        );
+        assert_eq!(
+            clock_time_budget(&tc, Side::Black, 0),
+            Some(Duration::from_millis(100))
+        );
+
+        // The last move before the time control may use all the time but the overhead
+        let tc = TimeControl {
+            movestogo: Some(1),
+            ..tc
+        };
+        assert_eq!(
+            clock_time_budget(&tc, Side::White, 30),
+            Some(Duration::from_millis(60_000 - 30))
+        );
+
+        assert_eq!(
+            clock_time_budget(&TimeControl::default(), Side::White, 30),
+            None
+        );
+    }
+
+    #[test]
+    fn test_clock_limited_session() {
+        // The search ends on its own within the share of the clock
+        let (lines, elapsed) =
+            run_worker_session(&["position startpos", "go wtime 3000 btime 3000"]);
+        assert!(elapsed < Duration::from_secs(1));
+
+        // Every completed iteration was reported, one ply deeper at a time
+        let depths = info_depths(&lines);
+        assert!(!depths.is_empty(), "{lines:?}");
+        assert!(
+            depths.iter().copied().eq(1..=depths.len() as u32),
+            "{depths:?}"
+        );
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn test_unparsable_go_session() {
+        let (lines, _) = run_worker_session(&["position startpos", "go depth many"]);
+
+        // The error is reported and the fallback search runs to its depth
+        assert_eq!(
+            lines[0],
+            "info string Failed to parse the go command argument"
+        );
+        assert_eq!(
+            info_depths(&lines).last(),
+            Some(&FALLBACK_GO_DEPTH),
+            "{lines:?}"
+        );
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn test_ponderhit_starts_the_clock() {
+        // The share of the clock is about half a second
+        let session = WorkerSession::start([
+            UciCommand::Position("position startpos moves e2e4".to_string()),
+            UciCommand::Go("go ponder wtime 15000 btime 15000".to_string()),
+        ]);
+
+        // Pondering longer than the share doesn't use it up
+        thread::sleep(Duration::from_millis(800));
+        assert!(!session.has_bestmove());
+
+        session.send(UciCommand::PonderHit);
+        let elapsed = session.wait_for_bestmove();
+        assert!(elapsed >= Duration::from_millis(300));
+        assert!(elapsed < Duration::from_secs(5));
+
+        assert!(session.finish().last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn test_depth_and_movetime_session() {
+        // Both limits apply, whichever of them comes first ends the search
+        for (go, max_time, max_depth) in [
+            ("go depth 2 movetime 60000", Duration::from_secs(5), 2),
+            ("go movetime 300 depth 40", Duration::from_secs(2), 40),
+        ] {
+            let (lines, elapsed) = run_worker_session(&["position startpos", go]);
+            assert!(elapsed < max_time, "{go}");
+
+            let depths = info_depths(&lines);
+            assert!(!depths.is_empty(), "{go}");
+            assert!(depths.iter().all(|&depth| depth <= max_depth), "{go}");
+            assert!(lines.last().unwrap().starts_with("bestmove "), "{go}");
+        }
+    }
+
+    #[test]
+    fn test_mate_session() {
+        // The mate is reported as found
+        let (lines, _) = run_worker_session(&[
+            "position fen r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1",
+            "go mate 3",
+        ]);
+        assert!(
+            lines.iter().any(|line| line.contains(" score mate 2 ")),
+            "{lines:?}"
+        );
+        assert!(
+            lines.last().unwrap().starts_with("bestmove d5f6"),
+            "{lines:?}"
+        );
+
+        // Without a mate a short search still answers the go
+        let (lines, elapsed) = run_worker_session(&["position startpos", "go mate 2"]);
+        assert!(elapsed < Duration::from_secs(5), "{lines:?}");
+        assert_eq!(
+            info_depths(&lines).last(),
+            Some(&FALLBACK_GO_DEPTH),
+            "{lines:?}"
+        );
+        assert!(lines.last().unwrap().starts_with("bestmove "), "{lines:?}");
+    }
+
+    #[test]
+    fn test_currmove_session() {
+        let (lines, _) = run_worker_session(&["position startpos", "go movetime 1500"]);
+
+        // The root moves are only reported after the first second of the search
+        let currmoves: Vec<&String> = lines
+            .iter()
+            .filter(|line| line.contains(" currmove "))
+            .collect();
+        assert!(!currmoves.is_empty(), "{lines:?}");
+        assert!(lines[0].starts_with("info depth 1 seldepth"), "{lines:?}");
+
+        for line in currmoves {
+            let tokens: Vec<&str> = line.split(' ').collect();
+            assert_eq!(tokens.len(), 7, "{line}");
+            assert_eq!(
+                (tokens[0], tokens[1], tokens[3], tokens[5]),
+                ("info", "depth", "currmove", "currmovenumber")
+            );
+            assert!((1..=20).contains(&tokens[6].parse::<usize>().unwrap()));
+        }
     }
 }
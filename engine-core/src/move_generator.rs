@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+
 use crate::{
     board::Board,
     chess_consts,
@@ -5,6 +7,7 @@ use crate::{
     helpers,
     king_attack_table::get_king_attacks_mask,
     knight_attack_table::get_knight_attacks_mask,
+    line_table,
     pawn_attack_table::get_pawn_attacks_mask,
     sliding_piece_attack_table::{
         get_bishop_attacks_mask, get_queen_attacks_mask, get_rook_attacks_mask,
@@ -14,20 +17,38 @@ use crate::{
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum MoveGenMode {
     All,
+    /// Captures that don't promote, en passant included
     CapturesOnly,
+    /// Moves that neither capture nor promote, castlings included
+    QuietsOnly,
+    /// Promotions with and without a capture
+    PromotionsOnly,
+}
+
+impl MoveGenMode {
+    fn includes_captures(self) -> bool {
+        matches!(self, MoveGenMode::All | MoveGenMode::CapturesOnly)
+    }
+
+    fn includes_quiets(self) -> bool {
+        matches!(self, MoveGenMode::All | MoveGenMode::QuietsOnly)
+    }
+
+    fn includes_promotions(self) -> bool {
+        matches!(self, MoveGenMode::All | MoveGenMode::PromotionsOnly)
+    }
 }
 
 pub(crate) type MoveBuffer = Vec<Move>;
 
-impl Board {
-    pub(crate) fn generate_pseudo_legal_moves(
-        &self,
-        mode: MoveGenMode,
-        side: Side,
-        buf: &mut MoveBuffer,
-    ) {
-        buf.clear();
+thread_local! {
+    /// Scratch space of legal_move_count, reused so that counting doesn't allocate
+    static COUNT_BUFFER: RefCell<MoveBuffer> =
+        RefCell::new(Vec::with_capacity(chess_consts::MOVES_BUF_SIZE));
+}
 
+impl Board {
+    fn append_pseudo_legal_moves(&self, mode: MoveGenMode, side: Side, buf: &mut MoveBuffer) {
         let generate_pseudo_legal_moves_handlers = [
             generate_pseudo_legal_pawn_moves,
             generate_pseudo_legal_knight_moves,
@@ -49,12 +70,24 @@ impl Board {
         side: Side,
         buf: &mut MoveBuffer,
     ) {
-        self.generate_pseudo_legal_moves(mode, side, buf);
+        buf.clear();
+        self.append_legal_moves(mode, side, buf);
+    }
+
+    /// Pushes the legal moves after the ones already in the buffer, which are kept
+    pub(crate) fn append_legal_moves(
+        &mut self,
+        mode: MoveGenMode,
+        side: Side,
+        buf: &mut MoveBuffer,
+    ) {
+        let start = buf.len();
+        self.append_pseudo_legal_moves(mode, side, buf);
 
-        let mut write = 0;
+        let mut write = start;
         let buf_len = buf.len();
 
-        for read in 0..buf_len {
+        for read in start..buf_len {
             let mv = buf[read];
 
             self.make_move(mv);
@@ -74,8 +107,68 @@ impl Board {
         self.generate_legal_moves(MoveGenMode::All, side, buf);
     }
 
+    /// Captures followed by promotions, the moves quiescence search looks at
     pub(crate) fn generate_legal_captures(&mut self, side: Side, buf: &mut MoveBuffer) {
         self.generate_legal_moves(MoveGenMode::CapturesOnly, side, buf);
+        self.append_legal_moves(MoveGenMode::PromotionsOnly, side, buf);
+    }
+
+    /// Stops at the first legal move, cheaper than generating all of them. The buffer
+    /// is only used as scratch space
+    pub(crate) fn has_legal_move(
+        &mut self,
+        mode: MoveGenMode,
+        side: Side,
+        buf: &mut MoveBuffer,
+    ) -> bool {
+        buf.clear();
+        self.append_pseudo_legal_moves(mode, side, buf);
+
+        let legal = buf.iter().any(|&mv| {
+            self.make_move(mv);
+            let ok = !self.is_in_check(side);
+            self.unmake_move();
+
+            ok
+        });
+        buf.clear();
+
+        legal
+    }
+
+    /// Number of the legal moves of the side to move, for the callers that don't need
+    /// the moves themselves. Out of check only the king moves, en passant and castlings
+    /// are made to test them, the moves of the other pieces are legal unless a pinned
+    /// piece leaves its line. The moves go to a per thread buffer instead of a new Vec
+    pub(crate) fn legal_move_count(&mut self, side: Side) -> usize {
+        let in_check = self.is_in_check(side);
+        let pinned_bb = self.get_pinned_bb(side);
+        let king_sq = self.get_king_square(side);
+
+        COUNT_BUFFER.with_borrow_mut(|buf| {
+            buf.clear();
+            self.append_pseudo_legal_moves(MoveGenMode::All, side, buf);
+
+            buf.iter()
+                .filter(|&&mv| match mv {
+                    // The en passant captures were checked when generated
+                    Move::Normal { flags, .. } if flags.contains(MoveFlags::EN_PASSANT) => true,
+                    Move::Normal {
+                        from, to, piece, ..
+                    } if !in_check && piece != Piece::King => {
+                        !helpers::is_bit_set(pinned_bb, from)
+                            || helpers::is_bit_set(line_table::get_line_mask(king_sq, from), to)
+                    }
+                    _ => {
+                        self.make_move(mv);
+                        let ok = !self.is_in_check(side);
+                        self.unmake_move();
+
+                        ok
+                    }
+                })
+                .count()
+        })
     }
 
     pub(crate) fn generate_all_legal_moves_to_vec(&mut self, side: Side) -> Vec<Move> {
@@ -94,6 +187,92 @@ impl Board {
 
         buf
     }
+
+    /// Tells whether the side to move could make the move in the position if its king
+    /// safety is ignored, moves taken from other positions like the killer moves have
+    /// to pass it before being made
+    pub(crate) fn is_pseudo_legal(&self, mv: Move) -> bool {
+        let side = self.game_state.side_to_move;
+
+        let (from, to, piece, captured, promo, flags) = match mv {
+            Move::Castle {
+                side: castling_side,
+                ..
+            } => {
+                return self
+                    .game_state
+                    .castling_state
+                    .get_castlings(side)
+                    .any(|castling| castling == castling_side)
+                    && self.castling_config.get_castling_move(side, castling_side) == mv
+                    && can_castle(self, side, castling_side);
+            }
+            Move::Normal {
+                from,
+                to,
+                piece,
+                captured,
+                promo,
+                flags,
+            } => (from, to, piece, captured, promo, flags),
+        };
+
+        if self.get_occupancy_piece(side, from) != Some(piece) {
+            return false;
+        }
+
+        if flags.contains(MoveFlags::EN_PASSANT) {
+            return piece == Piece::Pawn
+                && captured == Some(Piece::Pawn)
+                && promo.is_none()
+                && self.game_state.en_passant_square == Some(to)
+                && to.is_en_passant_target_for(side)
+                && get_pawn_attacks_mask(side, from) & to.bit() != 0;
+        }
+
+        if self.get_occupancy_bb(side) & to.bit() != 0
+            || self.get_occupancy_piece(side.opposite(), to) != captured
+        {
+            return false;
+        }
+
+        if piece != Piece::Pawn {
+            let attacks_bb = match piece {
+                Piece::Knight => get_knight_attacks_mask(from),
+                Piece::Bishop => get_bishop_attacks_mask(from, self.global_occupancy),
+                Piece::Rook => get_rook_attacks_mask(from, self.global_occupancy),
+                Piece::Queen => get_queen_attacks_mask(from, self.global_occupancy),
+                _ => get_king_attacks_mask(from),
+            };
+
+            return promo.is_none() && flags.is_empty() && attacks_bb & to.bit() != 0;
+        }
+
+        if promo.is_some() != (to.rank() == side.get_promotion_rank()) {
+            return false;
+        }
+
+        if captured.is_some() {
+            return flags.is_empty() && get_pawn_attacks_mask(side, from) & to.bit() != 0;
+        }
+
+        let one_step_bb = push_pawn(from.bit(), side) & self.get_empty_bb();
+
+        if flags.contains(MoveFlags::DOUBLE_PUSH) {
+            let double_push_rank = if side == Side::White {
+                Rank::R4
+            } else {
+                Rank::R5
+            };
+
+            push_pawn(one_step_bb, side)
+                & self.get_empty_bb()
+                & helpers::rank_mask(double_push_rank)
+                == to.bit()
+        } else {
+            flags.is_empty() && one_step_bb == to.bit()
+        }
+    }
 }
 
 fn generate_pseudo_legal_pawn_moves(
@@ -104,21 +283,19 @@ fn generate_pseudo_legal_pawn_moves(
 ) {
     let pawn_bb = board.get_bb(side, Piece::Pawn);
 
-    if mode == MoveGenMode::All {
-        // Generate pawn moves
-        let square_shift = if side == Side::White { 8 } else { -8 };
-
-        // Generate quiet moves
-        let pawn_one_step_bb = push_pawn(pawn_bb, side) & board.get_empty_bb();
+    // Generate pawn moves
+    // Generate quiet moves
+    let pawn_one_step_bb = push_pawn(pawn_bb, side) & board.get_empty_bb();
 
-        let promotion_mask = helpers::rank_mask(side.get_promotion_rank());
-        let pawn_one_step_not_promotion_bb = pawn_one_step_bb & (!promotion_mask);
-        let pawn_one_step_promotion_bb = pawn_one_step_bb & promotion_mask;
+    let promotion_mask = helpers::rank_mask(side.get_promotion_rank());
+    let pawn_one_step_not_promotion_bb = pawn_one_step_bb & (!promotion_mask);
+    let pawn_one_step_promotion_bb = pawn_one_step_bb & promotion_mask;
 
+    if mode.includes_quiets() {
         // One step moves with no promotion
         for bit in helpers::get_bits_iter(pawn_one_step_not_promotion_bb) {
             let to = unsafe { Square::from_u8_unchecked(bit as u8) };
-            let from = unsafe { Square::from_u8_unchecked((bit as i8 - square_shift) as u8) };
+            let from = to.backward(side);
 
             buf.push(Move::Normal {
                 from,
@@ -130,24 +307,6 @@ fn generate_pseudo_legal_pawn_moves(
             });
         }
 
-        // One step moves with promotion
-        for bit in helpers::get_bits_iter(pawn_one_step_promotion_bb) {
-            let to = unsafe { Square::from_u8_unchecked(bit as u8) };
-            let from = unsafe { Square::from_u8_unchecked((bit as i8 - square_shift) as u8) };
-
-            for promotion_piece in Piece::PROMOTION_PIECES {
-                let mv = Move::Normal {
-                    from,
-                    to,
-                    piece: Piece::Pawn,
-                    captured: None,
-                    promo: Some(promotion_piece),
-                    flags: MoveFlags::empty(),
-                };
-                buf.push(mv);
-            }
-        }
-
         // Two steps moves
         let one_step_mask = helpers::rank_mask(if side == Side::White {
             Rank::R3
@@ -159,7 +318,7 @@ fn generate_pseudo_legal_pawn_moves(
 
         for bit in helpers::get_bits_iter(pawn_two_steps_bb) {
             let to = unsafe { Square::from_u8_unchecked(bit as u8) };
-            let from = unsafe { Square::from_u8_unchecked((bit as i8 - 2 * square_shift) as u8) };
+            let from = to.backward(side).backward(side);
 
             let mv = Move::Normal {
                 from,
@@ -173,17 +332,49 @@ fn generate_pseudo_legal_pawn_moves(
         }
     }
 
+    if mode.includes_promotions() {
+        // One step moves with promotion
+        for bit in helpers::get_bits_iter(pawn_one_step_promotion_bb) {
+            let to = unsafe { Square::from_u8_unchecked(bit as u8) };
+            let from = to.backward(side);
+
+            for promotion_piece in Piece::PROMOTION_PIECES {
+                let mv = Move::Normal {
+                    from,
+                    to,
+                    piece: Piece::Pawn,
+                    captured: None,
+                    promo: Some(promotion_piece),
+                    flags: MoveFlags::empty(),
+                };
+                buf.push(mv);
+            }
+        }
+    }
+
+    if !mode.includes_captures() && !mode.includes_promotions() {
+        return;
+    }
+
     // Check whether the current en-passant square is from the opposite side
     let en_passant_sq_bb = if let Some(en_passant_sq) = board.game_state.en_passant_square
         && Square::is_en_passant_target_for(en_passant_sq, side)
+        && mode.includes_captures()
     {
         en_passant_sq.bit()
     } else {
         chess_consts::EMPTY_BB
     };
 
+    // Only the pawns on the last rank before promotion can capture with a promotion
+    let capturing_pawns_bb = match (mode.includes_captures(), mode.includes_promotions()) {
+        (true, true) => pawn_bb,
+        (true, false) => pawn_bb & !push_pawn(promotion_mask, side.opposite()),
+        _ => pawn_bb & push_pawn(promotion_mask, side.opposite()),
+    };
+
     // Normal attacks
-    for bit in helpers::get_bits_iter(pawn_bb) {
+    for bit in helpers::get_bits_iter(capturing_pawns_bb) {
         let from = unsafe { Square::from_u8_unchecked(bit as u8) };
 
         let attacks_bb = get_pawn_attacks_mask(side, from);
@@ -233,7 +424,12 @@ fn generate_pseudo_legal_pawn_moves(
                     promo: None,
                     flags: MoveFlags::EN_PASSANT,
                 };
-                buf.push(mv);
+
+                // The only pseudo-legal moves checked for the king safety here, the pin
+                // of the two pawns can't be seen before the capture
+                if board.is_en_passant_legal(side, from, to) {
+                    buf.push(mv);
+                }
             }
         }
     }
@@ -247,6 +443,11 @@ fn generate_leaper_pseudo_legal_moves(
     attacks_mask_fn: fn(sq: Square) -> u64,
     buf: &mut MoveBuffer,
 ) {
+    // Only pawns promote
+    if mode == MoveGenMode::PromotionsOnly {
+        return;
+    }
+
     let pieces_bb = board.get_bb(side, piece);
 
     let opposite_side = side.opposite();
@@ -254,14 +455,14 @@ fn generate_leaper_pseudo_legal_moves(
     for from in helpers::get_squares_iter(pieces_bb) {
         let attacks_bb = attacks_mask_fn(from);
 
-        if mode == MoveGenMode::All {
+        if mode.includes_quiets() {
             let quiet_moves_bb = attacks_bb & board.get_empty_bb();
 
             for to in helpers::get_squares_iter(quiet_moves_bb) {
                 let mv = Move::Normal {
-                    from: from,
-                    to: to,
-                    piece: piece,
+                    from,
+                    to,
+                    piece,
                     captured: None,
                     promo: None,
                     flags: MoveFlags::empty(),
@@ -270,13 +471,17 @@ fn generate_leaper_pseudo_legal_moves(
             }
         }
 
+        if !mode.includes_captures() {
+            continue;
+        }
+
         let capture_moves_bb = attacks_bb & board.get_occupancy_bb(opposite_side);
 
         for to in helpers::get_squares_iter(capture_moves_bb) {
             let mv = Move::Normal {
-                from: from,
-                to: to,
-                piece: piece,
+                from,
+                to,
+                piece,
                 captured: board.get_occupancy_piece(opposite_side, to),
                 promo: None,
                 flags: MoveFlags::empty(),
@@ -294,20 +499,24 @@ fn generate_sliding_pseudo_legal_moves(
     attacks_mask_fn: fn(sq: Square, occupancy: u64) -> u64,
     buf: &mut MoveBuffer,
 ) {
+    if mode == MoveGenMode::PromotionsOnly {
+        return;
+    }
+
     let piece_bb = board.get_bb(side, piece);
     let opposite_side = side.opposite();
 
     for from in helpers::get_squares_iter(piece_bb) {
         let attack_bb = attacks_mask_fn(from, board.global_occupancy);
 
-        if mode == MoveGenMode::All {
+        if mode.includes_quiets() {
             let quiet_moves_bb = attack_bb & board.get_empty_bb();
 
             for to in helpers::get_squares_iter(quiet_moves_bb) {
                 let mv = Move::Normal {
-                    from: from,
-                    to: to,
-                    piece: piece,
+                    from,
+                    to,
+                    piece,
                     captured: None,
                     promo: None,
                     flags: MoveFlags::empty(),
@@ -316,13 +525,17 @@ fn generate_sliding_pseudo_legal_moves(
             }
         }
 
+        if !mode.includes_captures() {
+            continue;
+        }
+
         let capture_moves_bb = attack_bb & board.get_occupancy_bb(side.opposite());
 
         for to in helpers::get_squares_iter(capture_moves_bb) {
             let mv = Move::Normal {
-                from: from,
-                to: to,
-                piece: piece,
+                from,
+                to,
+                piece,
                 captured: board.get_occupancy_piece(opposite_side, to),
                 promo: None,
                 flags: MoveFlags::empty(),
@@ -398,40 +611,31 @@ fn generate_pseudo_legal_king_moves(
     generate_leaper_pseudo_legal_moves(board, mode, side, Piece::King, get_king_attacks_mask, buf)
 }
 
-fn generate_castling_moves(board: &Board, _: MoveGenMode, side: Side, buf: &mut MoveBuffer) {
+fn generate_castling_moves(board: &Board, mode: MoveGenMode, side: Side, buf: &mut MoveBuffer) {
+    if !mode.includes_quiets() {
+        return;
+    }
+
     let castlings = board.game_state.castling_state.get_castlings(side);
 
     for castling in castlings {
-        let (empty_bb, not_attacked_bb) = match (side, castling) {
-            (Side::White, CastlingSide::KingSide) => (
-                CastlingSide::WHITE_KING_SIDE_EMPTY_MASK,
-                CastlingSide::WHITE_KING_SIDE_NOT_ATTACKED_MASK,
-            ),
-            (Side::White, CastlingSide::QueenSide) => (
-                CastlingSide::WHITE_QUEEN_SIDE_EMPTY_MASK,
-                CastlingSide::WHITE_QUEEN_SIDE_NOT_ATTACKED_MASK,
-            ),
-            (Side::Black, CastlingSide::KingSide) => (
-                CastlingSide::BLACK_KING_SIDE_EMPTY_MASK,
-                CastlingSide::BLACK_KING_SIDE_NOT_ATTACKED_MASK,
-            ),
-            (Side::Black, CastlingSide::QueenSide) => (
-                CastlingSide::BLACK_QUEEN_SIDE_EMPTY_MASK,
-                CastlingSide::BLACK_QUEEN_SIDE_NOT_ATTACKED_MASK,
-            ),
-        };
-
-        let opposite_side = side.opposite();
-        if board.global_occupancy & empty_bb == 0
-            && helpers::get_squares_iter(not_attacked_bb)
-                .all(|square| !board.is_square_attacked(square, opposite_side))
-        {
-            let mv = Move::get_castling_move(side, castling);
+        if can_castle(board, side, castling) {
+            let mv = board.castling_config.get_castling_move(side, castling);
             buf.push(mv);
         }
     }
 }
 
+/// Checks the squares between the king and the rook, the castling right itself is not
+fn can_castle(board: &Board, side: Side, castling: CastlingSide) -> bool {
+    let (empty_bb, not_attacked_bb) = board.castling_config.get_castling_masks(side, castling);
+
+    let opposite_side = side.opposite();
+    board.global_occupancy & empty_bb == 0
+        && helpers::get_squares_iter(not_attacked_bb)
+            .all(|square| !board.is_square_attacked(square, opposite_side))
+}
+
 #[inline(always)]
 fn push_pawn(bb: u64, side: Side) -> u64 {
     if side == Side::White {
@@ -440,3 +644,96 @@ fn push_pawn(bb: u64, side: Side) -> u64 {
         bb >> chess_consts::BOARD_SIZE
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::fen_parser;
+
+    use super::*;
+
+    const FENS: [&str; 5] = [
+        chess_consts::fen_strings::START_POS_FEN,
+        chess_consts::fen_strings::TRICKY_POS_FEN,
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ];
+
+    #[test]
+    fn test_legal_move_count() {
+        for fen in FENS {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+
+            let side = board.game_state.side_to_move;
+
+            // The positions two plies deep have pins, checks and en passant captures
+            for mv in board.generate_all_legal_moves_to_vec(side) {
+                board.make_move(mv);
+
+                for reply in board.generate_all_legal_moves_to_vec(side.opposite()) {
+                    board.make_move(reply);
+                    assert_eq!(
+                        board.legal_move_count(side),
+                        board.generate_all_legal_moves_to_vec(side).len(),
+                        "{fen}: {mv} {reply}"
+                    );
+                    board.unmake_move();
+                }
+
+                board.unmake_move();
+            }
+        }
+
+        let mut board = fen_parser::parse_fen_string("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(board.legal_move_count(Side::Black), 0);
+    }
+
+    #[test]
+    fn test_en_passant_discovered_check() {
+        // Taking en passant empties the rank between the king and the rook, the lone
+        // kings are enough for the test
+        for (fen, en_passant) in [
+            ("8/8/8/K2pP2r/8/8/8/8 w - d6 0 1", "e5d6"),
+            ("8/8/8/8/k2Pp2R/8/8/8 b - d3 0 1", "e4d3"),
+        ] {
+            let mut board = fen_parser::parse_fen_string_unchecked(fen).unwrap();
+            let side = board.game_state.side_to_move;
+
+            let moves = board.generate_all_legal_moves_to_vec(side);
+            assert!(moves.iter().all(|mv| mv.to_string() != en_passant), "{fen}");
+            assert_eq!(board.legal_move_count(side), moves.len(), "{fen}");
+        }
+
+        // Without the rook the capture is fine
+        let mut board =
+            fen_parser::parse_fen_string_unchecked("8/8/8/K2pP3/8/8/8/8 w - d6 0 1").unwrap();
+        let moves = board.generate_all_legal_moves_to_vec(Side::White);
+        assert!(moves.iter().any(|mv| mv.to_string() == "e5d6"));
+        assert_eq!(board.legal_move_count(Side::White), moves.len());
+    }
+
+    #[test]
+    #[ignore]
+    fn test_legal_move_count_speed() {
+        const ITERATIONS: usize = 100_000;
+
+        let mut board =
+            fen_parser::parse_fen_string(chess_consts::fen_strings::TRICKY_POS_FEN).unwrap();
+
+        let start = Instant::now();
+        let mut total = 0;
+        for _ in 0..ITERATIONS {
+            total += board.generate_all_legal_moves_to_vec(Side::White).len();
+        }
+        println!("Vec: {:?} ({total})", start.elapsed());
+
+        let start = Instant::now();
+        let mut total = 0;
+        for _ in 0..ITERATIONS {
+            total += board.legal_move_count(Side::White);
+        }
+        println!("Count: {:?} ({total})", start.elapsed());
+    }
+}
@@ -1,19 +1,66 @@
+use std::fmt::Display;
+
 use crate::{
     board::Board,
-    enums::{CastlingSide, Move, MoveFlags, Piece, Side},
+    enums::{Move, MoveFlags, Piece, Side},
     history::HistoryEntry,
+    pawn_attack_table::get_pawn_attacks_mask,
+    zobrist,
 };
 
+/// Why make_move_checked refused a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalMove {
+    /// The piece is not on the origin square, cannot reach the target square or the
+    /// capture, promotion or castling details don't match the position
+    Impossible(Move),
+    LeavesKingInCheck(Move),
+}
+
+impl Display for IllegalMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IllegalMove::Impossible(mv) => write!(f, "Move {mv} is not possible in the position"),
+            IllegalMove::LeavesKingInCheck(mv) => {
+                write!(f, "Move {mv} leaves the king in check")
+            }
+        }
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
 impl Board {
+    /// Makes the move only if it is legal, the board is left untouched otherwise.
+    /// make_move trusts its move and is meant for the generated ones
+    pub fn make_move_checked(&mut self, mv: Move) -> Result<(), IllegalMove> {
+        if !self.is_pseudo_legal(mv) {
+            return Err(IllegalMove::Impossible(mv));
+        }
+
+        let side = self.game_state.side_to_move;
+        self.make_move(mv);
+
+        if self.is_in_check(side) {
+            self.unmake_move();
+            return Err(IllegalMove::LeavesKingInCheck(mv));
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn make_move(&mut self, mv: Move) {
         // save history
-        self.history
-            .push(HistoryEntry::new(mv, self.game_state))
-            .unwrap();
+        self.history.push(HistoryEntry::new(mv, self.game_state));
 
         let moving_side = self.game_state.side_to_move;
         let opponent_side = moving_side.opposite();
 
+        // The keys of the old castling rights and en passant square are replaced
+        // with the new ones once the move is done
+        self.game_state.hash ^= zobrist::get_castling_key(self.game_state.castling_state)
+            ^ zobrist::get_en_passant_key(self.game_state.en_passant_square);
+
         self.game_state.en_passant_square = None;
 
         match mv {
@@ -43,9 +90,15 @@ impl Board {
                 let piece_to_add = promo.unwrap_or(piece);
                 self.add_piece(moving_side, piece_to_add, to);
 
-                // Set en-passant if double-push
+                // Set en-passant if double-push and an enemy pawn can capture it
                 if flags.contains(MoveFlags::DOUBLE_PUSH) {
-                    self.game_state.en_passant_square = Some(to.backward(moving_side));
+                    let en_passant_sq = to.backward(moving_side);
+                    let capturers_bb = get_pawn_attacks_mask(moving_side, en_passant_sq)
+                        & self.get_bb(opponent_side, Piece::Pawn);
+
+                    if capturers_bb != 0 {
+                        self.game_state.en_passant_square = Some(en_passant_sq);
+                    }
                 }
 
                 // Updating castling rights
@@ -54,15 +107,19 @@ impl Board {
                 }
 
                 if piece == Piece::Rook {
-                    self.game_state
-                        .castling_state
-                        .remove_rook(moving_side, from);
+                    self.game_state.castling_state.remove_rook(
+                        moving_side,
+                        from,
+                        &self.castling_config,
+                    );
                 }
 
                 if let Some(Piece::Rook) = captured {
-                    self.game_state
-                        .castling_state
-                        .remove_rook(opponent_side, to);
+                    self.game_state.castling_state.remove_rook(
+                        opponent_side,
+                        to,
+                        &self.castling_config,
+                    );
                 }
 
                 // Update half-move clock
@@ -73,16 +130,22 @@ impl Board {
                 }
             }
             Move::Castle {
+                from: king_from_sq,
+                to: king_to_sq,
                 side: castling_side,
-                ..
             } => {
-                let (king_from_sq, king_to_sq) =
-                    CastlingSide::get_castling_positions(moving_side, Piece::King, castling_side);
-                let (rook_from_sq, rook_to_sq) =
-                    CastlingSide::get_castling_positions(moving_side, Piece::Rook, castling_side);
+                let (rook_from_sq, rook_to_sq) = self.castling_config.get_castling_positions(
+                    moving_side,
+                    Piece::Rook,
+                    castling_side,
+                );
 
-                self.move_piece(moving_side, Piece::King, king_from_sq, king_to_sq);
-                self.move_piece(moving_side, Piece::Rook, rook_from_sq, rook_to_sq);
+                // Both pieces are lifted first, in Chess960 the king may land on the
+                // rook's square and the other way around
+                self.remove_piece(moving_side, Piece::King, king_from_sq);
+                self.remove_piece(moving_side, Piece::Rook, rook_from_sq);
+                self.add_piece(moving_side, Piece::King, king_to_sq);
+                self.add_piece(moving_side, Piece::Rook, rook_to_sq);
 
                 self.game_state.half_move_clock += 1;
                 self.game_state.castling_state.remove_all(moving_side);
@@ -93,7 +156,23 @@ impl Board {
             self.game_state.full_moves_count += 1;
         }
 
+        self.game_state.hash ^= zobrist::get_castling_key(self.game_state.castling_state)
+            ^ zobrist::get_en_passant_key(self.game_state.en_passant_square)
+            ^ zobrist::get_side_key(moving_side)
+            ^ zobrist::get_side_key(opponent_side);
+
         self.game_state.side_to_move = opponent_side;
+
+        debug_assert_eq!(
+            self.game_state.hash,
+            self.calc_hash(),
+            "Incremental hash is out of sync after {mv}"
+        );
+        debug_assert_eq!(
+            self.game_state.pawn_hash,
+            self.calc_pawn_hash(),
+            "Incremental pawn hash is out of sync after {mv}"
+        );
     }
 
     pub(crate) fn unmake_move(&mut self) {
@@ -101,10 +180,9 @@ impl Board {
             .history
             .pop()
             .expect("Move history was empty while trying to restore state");
+        let mv = mv.expect("The last move was a null move, unmake_null_move takes it back");
 
-        self.game_state = game_state;
-
-        let moving_side = self.game_state.side_to_move;
+        let moving_side = game_state.side_to_move;
         let opponent_side = moving_side.opposite();
 
         match mv {
@@ -131,13 +209,15 @@ impl Board {
                 }
             }
             Move::Castle {
+                from: king_from,
+                to: king_to,
                 side: castling_side,
-                ..
             } => {
-                let (king_from, king_to) =
-                    CastlingSide::get_castling_positions(moving_side, Piece::King, castling_side);
-                let (rook_from, rook_to) =
-                    CastlingSide::get_castling_positions(moving_side, Piece::Rook, castling_side);
+                let (rook_from, rook_to) = self.castling_config.get_castling_positions(
+                    moving_side,
+                    Piece::Rook,
+                    castling_side,
+                );
 
                 self.remove_piece(moving_side, Piece::King, king_to);
                 self.remove_piece(moving_side, Piece::Rook, rook_to);
@@ -146,5 +226,168 @@ impl Board {
                 self.add_piece(moving_side, Piece::Rook, rook_from);
             }
         }
+
+        // Restored last, moving the pieces back changed the hash
+        self.game_state = game_state;
+
+        debug_assert_eq!(
+            self.game_state.hash,
+            self.calc_hash(),
+            "Restored hash doesn't match the position after taking back {mv}"
+        );
+    }
+
+    /// Passes the turn to the opponent without moving a piece, the en passant square
+    /// is gone afterwards. Meant for the null move pruning and for looking at the
+    /// position with the other side to move, the side to move must not be in check
+    pub fn make_null_move(&mut self) {
+        self.history.push(HistoryEntry::null(self.game_state));
+
+        let moving_side = self.game_state.side_to_move;
+        let opponent_side = moving_side.opposite();
+
+        self.game_state.hash ^= zobrist::get_en_passant_key(self.game_state.en_passant_square)
+            ^ zobrist::get_side_key(moving_side)
+            ^ zobrist::get_side_key(opponent_side);
+        self.game_state.en_passant_square = None;
+        self.game_state.half_move_clock += 1;
+
+        if moving_side == Side::Black {
+            self.game_state.full_moves_count += 1;
+        }
+
+        self.game_state.side_to_move = opponent_side;
+    }
+
+    /// Takes back the null move made last
+    pub fn unmake_null_move(&mut self) {
+        let HistoryEntry { mv, game_state } = self
+            .history
+            .pop()
+            .expect("Move history was empty while trying to restore state");
+        assert!(mv.is_none(), "The last move was not a null move");
+
+        self.game_state = game_state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{chess_consts, enums::Square, fen_parser};
+
+    use super::*;
+
+    #[test]
+    fn test_make_move_checked() {
+        // The e3 knight is pinned by the e8 rook
+        let mut board = fen_parser::parse_fen_string("4r1k1/8/8/8/8/4N3/8/R3K3 w Q - 0 1").unwrap();
+        let original = board.clone();
+
+        let knight_move = |from, to| Move::Normal {
+            from,
+            to,
+            piece: Piece::Knight,
+            captured: None,
+            promo: None,
+            flags: MoveFlags::empty(),
+        };
+
+        // No knight on d3, a knight can't move like a rook, the pinned knight
+        let impossible = [
+            knight_move(Square::D3, Square::E5),
+            knight_move(Square::E3, Square::E5),
+        ];
+        for mv in impossible {
+            assert_eq!(
+                board.make_move_checked(mv),
+                Err(IllegalMove::Impossible(mv))
+            );
+            assert_eq!(board, original);
+        }
+
+        let pinned = knight_move(Square::E3, Square::D5);
+        assert_eq!(
+            board.make_move_checked(pinned),
+            Err(IllegalMove::LeavesKingInCheck(pinned))
+        );
+        assert_eq!(board, original);
+
+        let mv = Move::parse("e1c1", &mut board).unwrap();
+        assert_eq!(board.make_move_checked(mv), Ok(()));
+        assert_eq!(board.history.last().unwrap().mv, Some(mv));
+    }
+
+    #[test]
+    fn test_null_move() {
+        let fens = [
+            chess_consts::fen_strings::START_POS_FEN,
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 3 20",
+        ];
+
+        for fen in fens {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            let original = board.clone();
+            let side = board.game_state.side_to_move;
+
+            board.make_null_move();
+            assert_eq!(board.game_state.side_to_move, side.opposite());
+            assert_eq!(board.game_state.en_passant_square, None);
+            assert_eq!(board.game_state.hash, board.calc_hash(), "{fen}");
+            assert_eq!(board.bitboards, original.bitboards);
+
+            // Moves can be made and taken back on top of it
+            let mv = board.generate_all_legal_moves_to_vec(side.opposite())[0];
+            board.make_move(mv);
+            board.unmake_move();
+
+            board.unmake_null_move();
+            assert_eq!(board, original, "{fen}");
+        }
+    }
+
+    #[test]
+    fn test_half_move_clock() {
+        let mut board =
+            fen_parser::parse_fen_string("r3k2r/p7/8/1P6/8/8/6p1/R3K2R w KQkq - 5 20").unwrap();
+        let original = board.clone();
+
+        // Castling is reversible for the clock, pawn moves, promotions, en passant and
+        // other captures reset it
+        let moves = [
+            ("e1c1", 6),
+            ("a7a5", 0),
+            ("b5a6", 0),
+            ("e8g8", 1),
+            ("c1b1", 2),
+            ("g2g1q", 0),
+            ("h1h7", 1),
+            ("g8h7", 0),
+            ("b1c2", 1),
+        ];
+
+        let mut clocks = vec![board.game_state.half_move_clock];
+        for (mv_str, clock) in moves {
+            let mv = Move::parse(mv_str, &mut board).unwrap();
+            board.make_move(mv);
+            assert_eq!(board.game_state.half_move_clock, clock, "{mv_str}");
+
+            // The clock survives the FEN round-trip
+            let fen = board.to_fen();
+            assert_eq!(fen.split(' ').nth(4), Some(clock.to_string().as_str()));
+            let parsed = fen_parser::parse_fen_string(&fen).unwrap();
+            assert_eq!(parsed.game_state.half_move_clock, clock, "{fen}");
+
+            clocks.push(clock);
+        }
+        assert_eq!(board.game_state.full_moves_count, 24);
+
+        clocks.pop();
+        while let Some(clock) = clocks.pop() {
+            board.unmake_move();
+            assert_eq!(board.game_state.half_move_clock, clock);
+        }
+        assert_eq!(board, original);
     }
 }
@@ -1,6 +1,7 @@
 use crate::{
+    board::Board,
     chess_consts,
-    enums::{Move, Piece},
+    enums::{Move, Piece, Side},
 };
 
 const MVV_TABLE: [[u32; chess_consts::PIECE_TYPES_COUNT]; chess_consts::PIECE_TYPES_COUNT] = [
@@ -12,19 +13,66 @@ const MVV_TABLE: [[u32; chess_consts::PIECE_TYPES_COUNT]; chess_consts::PIECE_TY
     [100, 200, 300, 400, 500, 600],
 ];
 
+/// Bound of the scores of each history table, the gravity update never crosses it and
+/// the sum of both tables stays below the scores of the killers and the countermove
+const MAX_HISTORY: i32 = 16_384;
+
 const fn get_mvv_score(attacker: Piece, victim: Piece) -> u32 {
     MVV_TABLE[attacker.index() as usize][victim.index() as usize]
 }
 
-pub(crate) static mut KILLER_MOVES: [[Option<Move>; chess_consts::MAX_PLY]; 2] =
-    [[None; chess_consts::MAX_PLY]; 2];
+/// Ordering bonus by the promoted piece. Queen promotions go before the captures, knight
+/// promotions may fork or give check and stay with the good captures, the rook and bishop
+/// ones are almost never better than the queen one and go after the quiet moves
+const fn get_promotion_score(promo: Piece) -> i32 {
+    match promo {
+        Piece::Queen => 1_000,
+        Piece::Knight => 0,
+        _ => -200_000,
+    }
+}
+
+/// Killer moves and history heuristic tables, owned by a single search
+/// (and kept between the searches of one game)
+#[derive(Clone)]
+pub(crate) struct MoveOrderingState {
+    killer_moves: [[Option<Move>; chess_consts::MAX_PLY]; 2],
+    history_moves: [[i32; chess_consts::SQUARES_COUNT]; chess_consts::SQUARES_COUNT],
+    /// History indexed by the side, the moving piece and its target square, it tells
+    /// apart the pieces moving between the same squares
+    piece_to_history: [[[i32; chess_consts::SQUARES_COUNT]; chess_consts::PIECE_TYPES_COUNT];
+        chess_consts::SIDES_COUNT],
+    /// Quiet refutation of the move indexed by its from and to squares
+    counter_moves: [[Option<Move>; chess_consts::SQUARES_COUNT]; chess_consts::SQUARES_COUNT],
+}
+
+impl Default for MoveOrderingState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MoveOrderingState {
+    pub(crate) fn new() -> Self {
+        Self {
+            killer_moves: [[None; chess_consts::MAX_PLY]; 2],
+            history_moves: [[0; chess_consts::SQUARES_COUNT]; chess_consts::SQUARES_COUNT],
+            piece_to_history: [[[0; chess_consts::SQUARES_COUNT]; chess_consts::PIECE_TYPES_COUNT];
+                chess_consts::SIDES_COUNT],
+            counter_moves: [[None; chess_consts::SQUARES_COUNT]; chess_consts::SQUARES_COUNT],
+        }
+    }
 
-#[allow(static_mut_refs)]
-pub(crate) fn update_killers(mv: Move, ply: u32) {
-    let p = ply as usize;
+    /// Wipes killers, history and countermoves
+    pub(crate) fn clear(&mut self) {
+        self.clear_killers();
+        self.clear_history();
+        self.clear_counter_moves();
+    }
 
-    unsafe {
-        let km = &mut KILLER_MOVES;
+    pub(crate) fn update_killers(&mut self, mv: Move, ply: u32) {
+        let p = ply as usize;
+        let km = &mut self.killer_moves;
 
         let k0 = km[0][p];
 
@@ -35,97 +83,177 @@ pub(crate) fn update_killers(mv: Move, ply: u32) {
         km[1][p] = k0;
         km[0][p] = Some(mv);
     }
-}
 
-#[allow(static_mut_refs)]
-pub(crate) fn clear_killers() {
-    unsafe { KILLER_MOVES.fill([None; chess_consts::MAX_PLY]) };
-}
+    /// The killer moves of the ply, the most recent one first
+    pub(crate) fn get_killers(&self, ply: u32) -> [Option<Move>; 2] {
+        [
+            self.killer_moves[0][ply as usize],
+            self.killer_moves[1][ply as usize],
+        ]
+    }
 
-static mut HISTORY_MOVES: [[u64; chess_consts::SQUARES_COUNT]; chess_consts::SQUARES_COUNT] =
-    [[0; chess_consts::SQUARES_COUNT]; chess_consts::SQUARES_COUNT];
+    pub(crate) fn clear_killers(&mut self) {
+        self.killer_moves.fill([None; chess_consts::MAX_PLY]);
+    }
 
-pub(crate) fn update_history(mv: Move, depth: u32) {
-    let (from, to) = mv.get_from_to();
-    let f = from.index() as usize;
-    let t = to.index() as usize;
-    let add = (depth * depth) as u64;
+    /// Rewards a quiet move of the side in both history tables
+    pub(crate) fn update_history(&mut self, mv: Move, side: Side, depth: u32) {
+        let (from, to) = mv.get_from_to();
+        let bonus = (depth * depth).min(MAX_HISTORY as u32) as i32;
 
-    unsafe {
-        HISTORY_MOVES[f][t] = HISTORY_MOVES[f][t].saturating_add(add);
+        apply_gravity(
+            &mut self.history_moves[from.index() as usize][to.index() as usize],
+            bonus,
+        );
+        apply_gravity(
+            &mut self.piece_to_history[side.index() as usize][mv.get_piece().index() as usize]
+                [to.index() as usize],
+            bonus,
+        );
     }
-}
 
-pub(crate) fn normalize_history() {
-    unsafe {
-        for from in 0..chess_consts::SQUARES_COUNT {
-            for to in 0..chess_consts::SQUARES_COUNT {
-                HISTORY_MOVES[from][to] >>= 1;
-            }
+    /// Sum of the from-to and the piece-to history scores of a quiet move
+    fn get_history_score(&self, mv: Move, side: Side) -> i32 {
+        let (from, to) = mv.get_from_to();
+
+        self.history_moves[from.index() as usize][to.index() as usize]
+            + self.piece_to_history[side.index() as usize][mv.get_piece().index() as usize]
+                [to.index() as usize]
+    }
+
+    pub(crate) fn clear_history(&mut self) {
+        self.history_moves.fill([0; chess_consts::SQUARES_COUNT]);
+        self.piece_to_history
+            .fill([[0; chess_consts::SQUARES_COUNT]; chess_consts::PIECE_TYPES_COUNT]);
+    }
+
+    pub(crate) fn update_counter_move(&mut self, prev_move: Move, mv: Move) {
+        let (from, to) = prev_move.get_from_to();
+        self.counter_moves[from.index() as usize][to.index() as usize] = Some(mv);
+    }
+
+    pub(crate) fn clear_counter_moves(&mut self) {
+        self.counter_moves.fill([None; chess_consts::SQUARES_COUNT]);
+    }
+
+    pub(crate) fn get_counter_move(&self, prev_move: Option<Move>) -> Option<Move> {
+        let (from, to) = prev_move?.get_from_to();
+        self.counter_moves[from.index() as usize][to.index() as usize]
+    }
+
+    /// Ages the history between the searches, the scores of the previous position
+    /// still help but give way to the new ones
+    pub(crate) fn normalize_history(&mut self) {
+        let from_to_scores = self.history_moves.iter_mut().flatten();
+        let piece_to_scores = self.piece_to_history.iter_mut().flatten().flatten();
+
+        for score in from_to_scores.chain(piece_to_scores) {
+            *score /= 2;
         }
     }
-}
 
-pub(crate) fn score_move(mv: Move, ply: u32, only_captures: bool) -> i32 {
-    if mv.is_capture() {
-        let (piece, captured) = match mv {
+    pub(crate) fn score_move(
+        &self,
+        board: &Board,
+        mv: Move,
+        ply: u32,
+        counter_move: Option<Move>,
+        only_captures: bool,
+    ) -> i32 {
+        let promo_score = match mv {
             Move::Normal {
-                piece, captured, ..
-            } => (piece, captured.unwrap()),
-            _ => unreachable!(),
+                promo: Some(promo), ..
+            } => Some(get_promotion_score(promo)),
+            _ => None,
         };
 
-        get_mvv_score(piece, captured) as i32 + 100_000
-    } else {
-        if only_captures {
-            return 0;
-        }
-
-        if let Some(first_km) = unsafe { KILLER_MOVES }[0][ply as usize]
-            && first_km == mv
-        {
-            return 90_000;
-        } else if let Some(second_km) = unsafe { KILLER_MOVES }[1][ply as usize]
-            && second_km == mv
-        {
-            return 80_000;
+        if mv.is_capture() {
+            let (piece, captured) = match mv {
+                Move::Normal {
+                    piece, captured, ..
+                } => (piece, captured.unwrap()),
+                _ => unreachable!(),
+            };
+
+            let mvv_score = get_mvv_score(piece, captured) as i32 + promo_score.unwrap_or(0);
+
+            // Losing captures go after the quiet moves
+            if board.see(mv) >= 0 {
+                mvv_score + 100_000
+            } else {
+                mvv_score - 100_000
+            }
+        } else if let Some(promo_score) = promo_score {
+            // The quiet promotions are handed out with the captures
+            promo_score + 100_000
         } else {
-            let (from, to) = mv.get_from_to();
+            if only_captures {
+                return 0;
+            }
 
-            (unsafe { HISTORY_MOVES })[from.index() as usize][to.index() as usize] as i32
+            if let Some(first_km) = self.killer_moves[0][ply as usize]
+                && first_km == mv
+            {
+                90_000
+            } else if let Some(second_km) = self.killer_moves[1][ply as usize]
+                && second_km == mv
+            {
+                80_000
+            } else if counter_move == Some(mv) {
+                70_000
+            } else {
+                self.get_history_score(mv, board.game_state.side_to_move)
+            }
         }
     }
-}
 
-pub(crate) fn sort_moves(moves: &mut [Move], ply: u32, only_captures: bool) {
-    let n = moves.len();
+    /// Sorts the moves from the most to the least promising one, prev_move is the move
+    /// that led to the position and is used to look up its countermove
+    pub(crate) fn sort_moves(
+        &self,
+        board: &Board,
+        moves: &mut [Move],
+        ply: u32,
+        prev_move: Option<Move>,
+        only_captures: bool,
+    ) {
+        let n = moves.len();
+
+        if n <= 1 {
+            return;
+        }
 
-    if n <= 1 {
-        return;
-    }
+        let counter_move = self.get_counter_move(prev_move);
 
-    let mut scores = [0i32; chess_consts::MOVES_BUF_SIZE];
-    for i in 0..n {
-        scores[i] = score_move(moves[i], ply, only_captures);
-    }
+        let mut scores = [0i32; chess_consts::MOVES_BUF_SIZE];
+        for i in 0..n {
+            scores[i] = self.score_move(board, moves[i], ply, counter_move, only_captures);
+        }
 
-    for i in 1..n {
-        let mv = moves[i];
-        let sc = scores[i];
+        for i in 1..n {
+            let mv = moves[i];
+            let sc = scores[i];
 
-        let mut j = i;
+            let mut j = i;
 
-        while j > 0 && scores[j - 1] < sc {
-            moves[j] = moves[j - 1];
-            scores[j] = scores[j - 1];
-            j -= 1;
-        }
+            while j > 0 && scores[j - 1] < sc {
+                moves[j] = moves[j - 1];
+                scores[j] = scores[j - 1];
+                j -= 1;
+            }
 
-        moves[j] = mv;
-        scores[j] = sc;
+            moves[j] = mv;
+            scores[j] = sc;
+        }
     }
 }
 
+/// Gravity update: the bonus shrinks as the score approaches MAX_HISTORY, so a few
+/// deep cutoffs can't outweigh everything else and the score never overflows
+fn apply_gravity(score: &mut i32, bonus: i32) {
+    *score += bonus - *score * bonus / MAX_HISTORY;
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -136,24 +264,150 @@ mod tests {
     use super::*;
 
     #[test]
-    #[ignore]
     fn test_score_move_function() {
         let mut board =
-            fen_parser::parse_fen_string("1k6/8/8/2q1r2P/3P4/B2N4/8/K7 b - - 0 1").unwrap();
+            fen_parser::parse_fen_string("1k6/8/8/2q1r2P/3P4/B2N4/8/K7 w - - 0 1").unwrap();
 
         let mut moves = board.generate_all_legal_moves_to_vec(Side::White);
+        let mut state = MoveOrderingState::new();
+
+        // The knight retreat cut off deeper searches than the king move
+        let good_quiet = Move::parse("d3f2", &mut board).unwrap();
+        let bad_quiet = Move::parse("a1b1", &mut board).unwrap();
+        for _ in 0..3 {
+            state.update_history(bad_quiet, Side::White, 2);
+        }
+        state.update_history(good_quiet, Side::White, 6);
 
-        sort_moves(&mut moves, 0, false);
+        state.sort_moves(&board, &mut moves, 0, None, false);
 
-        for mv in moves {
-            println!("Move: {mv:?}, score: {}", score_move(mv, 0, false));
+        let captures_count = moves.iter().filter(|mv| mv.is_capture()).count();
+        assert!(moves[..captures_count].iter().all(|mv| mv.is_capture()));
+        assert_eq!(moves[captures_count], good_quiet);
+        assert_eq!(moves[captures_count + 1], bad_quiet);
+    }
+
+    #[test]
+    fn test_promotion_ordering() {
+        // The b7 pawn can promote on b8 or take the rook on a8
+        let mut board = fen_parser::parse_fen_string("r6k/1P6/8/8/8/8/8/7K w - - 0 1").unwrap();
+
+        let mut moves = board.generate_all_legal_moves_to_vec(Side::White);
+        let state = MoveOrderingState::new();
+        state.sort_moves(&board, &mut moves, 0, None, false);
+
+        let order: Vec<String> = moves.iter().map(|mv| mv.to_string()).collect();
+        let position = |mv_str: &str| order.iter().position(|mv| mv == mv_str).unwrap();
+
+        assert_eq!(&order[..2], ["b7a8q", "b7b8q"]);
+        assert!(position("b7a8n") < position("h1g1"));
+        assert!(position("b7b8n") < position("h1g1"));
+        for underpromotion in ["b7a8r", "b7a8b", "b7b8r", "b7b8b"] {
+            assert!(
+                position(underpromotion) > position("h1h2"),
+                "{underpromotion}"
+            );
         }
     }
 
+    #[test]
+    fn test_capture_promotion_ordering() {
+        // The b7 pawn can take the a8 rook or the c8 knight while promoting, the queens
+        // can take each other
+        let mut board =
+            fen_parser::parse_fen_string("r1n4k/1P6/8/8/3q4/8/3Q4/7K w - - 0 1").unwrap();
+
+        let mut moves = board.generate_all_legal_moves_to_vec(Side::White);
+        let state = MoveOrderingState::new();
+        state.sort_moves(&board, &mut moves, 0, None, false);
+
+        let order: Vec<String> = moves.iter().map(|mv| mv.to_string()).collect();
+        let position = |mv_str: &str| order.iter().position(|mv| mv == mv_str).unwrap();
+
+        // The more valuable victim first, the capture of the queen follows the promotions
+        assert_eq!(
+            &order[..4],
+            ["b7a8q", "b7c8q", "b7b8q", "d2d4"],
+            "{order:?}"
+        );
+        assert!(position("b7a8n") > position("d2d4"));
+        assert!(position("b7a8n") < position("b7c8n"));
+        assert!(position("b7a8r") > position("h1h2"));
+    }
+
+    #[test]
+    fn test_history_gravity() {
+        let mut state = MoveOrderingState::new();
+        let mv = Move::parse("g1f3", &mut Board::get_start_position()).unwrap();
+        let score = |state: &MoveOrderingState| {
+            state.score_move(&Board::get_start_position(), mv, 0, None, false)
+        };
+
+        state.update_history(mv, Side::White, 4);
+        assert_eq!(score(&state), 2 * 16);
+
+        // Deep cutoffs approach the bound without crossing it
+        for _ in 0..1000 {
+            state.update_history(mv, Side::White, 60);
+        }
+        assert!(score(&state) > 2 * MAX_HISTORY * 9 / 10);
+        assert!(score(&state) <= 2 * MAX_HISTORY);
+
+        state.normalize_history();
+        assert!(score(&state) <= MAX_HISTORY);
+    }
+
+    #[test]
+    fn test_piece_to_history() {
+        // Both knights and the bishop can go to f3
+        let mut board =
+            fen_parser::parse_fen_string("4k3/8/8/8/8/8/3N2B1/4K1N1 w - - 0 1").unwrap();
+        let mut score_of = |state: &MoveOrderingState, mv: &str| {
+            let mv = Move::parse(mv, &mut board).unwrap();
+            state.score_move(&board, mv, 0, None, false)
+        };
+
+        let mut state = MoveOrderingState::new();
+        state.update_history(
+            Move::parse("g1f3", &mut Board::get_start_position()).unwrap(),
+            Side::White,
+            4,
+        );
+
+        assert_eq!(score_of(&state, "g1f3"), 2 * 16);
+        assert_eq!(score_of(&state, "d2f3"), 16);
+        assert_eq!(score_of(&state, "g2f3"), 0);
+    }
+
+    #[test]
+    fn test_counter_move_ordering() {
+        let mut board = Board::get_start_position();
+        let mut state = MoveOrderingState::new();
+
+        let prev_move = board.generate_all_legal_moves_to_vec(Side::White)[0];
+        board.make_move(prev_move);
+
+        let mut moves = board.generate_all_legal_moves_to_vec(Side::Black);
+        let counter_move = *moves.last().unwrap();
+        state.update_counter_move(prev_move, counter_move);
+
+        state.sort_moves(&board, &mut moves, 1, Some(prev_move), false);
+        assert_eq!(moves[0], counter_move);
+
+        state.clear_counter_moves();
+        state.sort_moves(&board, &mut moves, 1, Some(prev_move), false);
+        assert_eq!(
+            state.score_move(&board, counter_move, 1, None, false),
+            state.score_move(&board, moves[0], 1, None, false)
+        );
+    }
+
     #[test]
     #[ignore]
     fn test_normalize_history_function() {
-        update_history(
+        let mut state = MoveOrderingState::new();
+
+        state.update_history(
             Move::Normal {
                 from: Square::A1,
                 to: Square::B1,
@@ -162,11 +416,12 @@ mod tests {
                 promo: None,
                 flags: MoveFlags::empty(),
             },
+            Side::White,
             5,
         );
-        println!("{:?}", unsafe { HISTORY_MOVES });
+        println!("{:?}", state.history_moves);
 
-        normalize_history();
-        println!("{:?}", unsafe { HISTORY_MOVES });
+        state.normalize_history();
+        println!("{:?}", state.history_moves);
     }
 }
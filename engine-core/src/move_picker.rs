@@ -0,0 +1,337 @@
+use crate::{
+    board::Board,
+    chess_consts,
+    enums::Move,
+    move_generator::{MoveBuffer, MoveGenMode},
+    move_ordering::MoveOrderingState,
+};
+
+/// Number of refutation moves: two killers and the countermove
+const REFUTATIONS_COUNT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Refutations,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Hands out the legal moves of a position stage by stage: the hash move, the captures
+/// that don't lose material and the promotions, the killers and the countermove, the
+/// quiet moves and the losing captures last. A stage is generated only when the previous ones run out, so
+/// a cutoff on an early move saves generating and scoring the rest
+/// # Examples
+/// let mut picker = MovePicker::new(buf, None, ordering, ply, prev_move, false);
+/// while let Some(mv) = picker.next(board, ordering) { ... }
+pub(crate) struct MovePicker<'a> {
+    buf: &'a mut MoveBuffer,
+    scores: [i32; chess_consts::MOVES_BUF_SIZE],
+    stage: Stage,
+    /// Position of the next move of the current stage in the buffer
+    cur: usize,
+    /// The losing captures wait in the buffer until the quiet moves are done
+    bad_captures_start: usize,
+    bad_captures_end: usize,
+    hash_move: Option<Move>,
+    /// Refutations that were not handed out are reset to None
+    refutations: [Option<Move>; REFUTATIONS_COUNT],
+    refutation_index: usize,
+    ply: u32,
+    /// Quiet moves are not ordered and the refutations are not tried first, like in
+    /// MoveOrderingState::sort_moves
+    only_captures: bool,
+}
+
+impl<'a> MovePicker<'a> {
+    /// prev_move is the move that led to the position, its countermove is one of the
+    /// refutations
+    pub(crate) fn new(
+        buf: &'a mut MoveBuffer,
+        hash_move: Option<Move>,
+        ordering: &MoveOrderingState,
+        ply: u32,
+        prev_move: Option<Move>,
+        only_captures: bool,
+    ) -> Self {
+        let [first_killer, second_killer] = ordering.get_killers(ply);
+
+        buf.clear();
+
+        Self {
+            buf,
+            scores: [0; chess_consts::MOVES_BUF_SIZE],
+            stage: Stage::HashMove,
+            cur: 0,
+            bad_captures_start: 0,
+            bad_captures_end: 0,
+            hash_move,
+            refutations: [
+                first_killer,
+                second_killer,
+                ordering.get_counter_move(prev_move),
+            ],
+            refutation_index: 0,
+            ply,
+            only_captures,
+        }
+    }
+
+    /// Returns the next move to search, None once all the legal moves were handed out
+    pub(crate) fn next(&mut self, board: &mut Board, ordering: &MoveOrderingState) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+
+                    match self.hash_move {
+                        Some(mv) if board.is_pseudo_legal(mv) && is_legal(board, mv) => {
+                            return Some(mv);
+                        }
+                        _ => self.hash_move = None,
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    // The quiet promotions come with the captures, behind the ones that
+                    // don't lose material
+                    let side = board.game_state.side_to_move;
+                    board.append_legal_moves(MoveGenMode::CapturesOnly, side, self.buf);
+                    board.append_legal_moves(MoveGenMode::PromotionsOnly, side, self.buf);
+
+                    let hash_move = self.hash_move;
+                    self.buf.retain(|&mv| Some(mv) != hash_move);
+
+                    self.score_moves(board, ordering, 0, true);
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => {
+                    // Captures failing the static exchange evaluation get a negative score
+                    if self.select_best(self.buf.len()) && self.scores[self.cur] >= 0 {
+                        self.cur += 1;
+                        return Some(self.buf[self.cur - 1]);
+                    }
+
+                    self.bad_captures_start = self.cur;
+                    self.bad_captures_end = self.buf.len();
+                    self.stage = if self.only_captures {
+                        Stage::GenerateQuiets
+                    } else {
+                        Stage::Refutations
+                    };
+                }
+                Stage::Refutations => {
+                    while self.refutation_index < REFUTATIONS_COUNT {
+                        let i = self.refutation_index;
+                        self.refutation_index += 1;
+
+                        let Some(mv) = self.refutations[i] else {
+                            continue;
+                        };
+
+                        if Some(mv) == self.hash_move
+                            || self.refutations[..i].contains(&Some(mv))
+                            || mv.is_capture()
+                            || !board.is_pseudo_legal(mv)
+                            || !is_legal(board, mv)
+                        {
+                            self.refutations[i] = None;
+                            continue;
+                        }
+
+                        return Some(mv);
+                    }
+
+                    self.stage = Stage::GenerateQuiets;
+                }
+                Stage::GenerateQuiets => {
+                    let start = self.buf.len();
+
+                    let side = board.game_state.side_to_move;
+                    board.append_legal_moves(MoveGenMode::QuietsOnly, side, self.buf);
+
+                    self.score_moves(board, ordering, start, self.only_captures);
+                    self.cur = start;
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => {
+                    while self.select_best(self.buf.len()) {
+                        let mv = self.buf[self.cur];
+                        self.cur += 1;
+
+                        if Some(mv) != self.hash_move
+                            && (self.only_captures || !self.refutations.contains(&Some(mv)))
+                        {
+                            return Some(mv);
+                        }
+                    }
+
+                    self.cur = self.bad_captures_start;
+                    self.stage = Stage::BadCaptures;
+                }
+                Stage::BadCaptures => {
+                    if self.select_best(self.bad_captures_end) {
+                        self.cur += 1;
+                        return Some(self.buf[self.cur - 1]);
+                    }
+
+                    self.stage = Stage::Done;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    fn score_moves(
+        &mut self,
+        board: &Board,
+        ordering: &MoveOrderingState,
+        start: usize,
+        only_captures: bool,
+    ) {
+        for i in start..self.buf.len() {
+            self.scores[i] = ordering.score_move(board, self.buf[i], self.ply, None, only_captures);
+        }
+    }
+
+    /// Moves the best scored move of buf[cur..end] to cur, false if the range is empty.
+    /// Ties keep the generation order
+    fn select_best(&mut self, end: usize) -> bool {
+        if self.cur >= end {
+            return false;
+        }
+
+        let mut best = self.cur;
+        for i in self.cur + 1..end {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+
+        self.buf[self.cur..=best].rotate_right(1);
+        self.scores[self.cur..=best].rotate_right(1);
+
+        true
+    }
+}
+
+fn is_legal(board: &mut Board, mv: Move) -> bool {
+    let side = board.game_state.side_to_move;
+
+    board.make_move(mv);
+    let legal = !board.is_in_check(side);
+    board.unmake_move();
+
+    legal
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{enums::Side, fen_parser};
+
+    use super::*;
+
+    fn collect_moves(
+        board: &mut Board,
+        hash_move: Option<Move>,
+        ordering: &MoveOrderingState,
+        ply: u32,
+    ) -> Vec<Move> {
+        let mut buf = MoveBuffer::new();
+        let mut picker = MovePicker::new(&mut buf, hash_move, ordering, ply, None, false);
+
+        let mut moves = Vec::new();
+        while let Some(mv) = picker.next(board, ordering) {
+            moves.push(mv);
+        }
+
+        moves
+    }
+
+    #[test]
+    fn test_move_picker_hands_out_every_legal_move_once() {
+        let fens = [
+            chess_consts::fen_strings::START_POS_FEN,
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+
+        for fen in fens {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            let mut legal_moves =
+                board.generate_all_legal_moves_to_vec(board.game_state.side_to_move);
+
+            // A legal hash move and killers, and a killer that is not legal here
+            let mut ordering = MoveOrderingState::new();
+            let quiets: Vec<Move> = legal_moves
+                .iter()
+                .copied()
+                .filter(|mv| !mv.is_capture() && !mv.is_promo())
+                .collect();
+            ordering.update_killers(quiets[1], 3);
+            ordering.update_killers(
+                Move::parse("a2a4", &mut Board::get_start_position()).unwrap(),
+                3,
+            );
+
+            let mut moves = collect_moves(&mut board, Some(quiets[0]), &ordering, 3);
+            assert_eq!(moves[0], quiets[0], "{fen}");
+
+            legal_moves.sort_by_key(|mv| format!("{mv:?}"));
+            moves.sort_by_key(|mv| format!("{mv:?}"));
+            assert_eq!(moves, legal_moves, "{fen}");
+        }
+    }
+
+    #[test]
+    fn test_move_picker_skips_unusable_hash_moves() {
+        // The e2 rook is pinned, a hash move stored for another position with the same
+        // key must not be played here
+        let fen = "4r1k1/8/8/8/8/8/4R3/4K3 w - - 0 1";
+        let mut board = fen_parser::parse_fen_string(fen).unwrap();
+        let mut legal_moves = board.generate_all_legal_moves_to_vec(Side::White);
+        legal_moves.sort_by_key(|mv| format!("{mv:?}"));
+
+        let pinned_move = Move::parse(
+            "e2a2",
+            &mut fen_parser::parse_fen_string("6k1/8/8/8/8/8/4R3/4K3 w - - 0 1").unwrap(),
+        )
+        .unwrap();
+        let foreign_move = Move::parse("g1f3", &mut Board::get_start_position()).unwrap();
+
+        for hash_move in [pinned_move, foreign_move] {
+            let ordering = MoveOrderingState::new();
+            let mut moves = collect_moves(&mut board, Some(hash_move), &ordering, 0);
+
+            assert!(!moves.contains(&hash_move));
+            moves.sort_by_key(|mv| format!("{mv:?}"));
+            assert_eq!(moves, legal_moves, "{hash_move}");
+        }
+    }
+
+    #[test]
+    fn test_move_picker_stages() {
+        // The a1 rook wins a knight, the d1 rook would lose itself for a defended pawn
+        let mut board =
+            fen_parser::parse_fen_string("4k3/8/2p5/n2p4/8/8/8/R2RK3 w - - 0 1").unwrap();
+
+        let mut ordering = MoveOrderingState::new();
+        let killer = Move::parse("d1d2", &mut board).unwrap();
+        ordering.update_killers(killer, 0);
+
+        let moves = collect_moves(&mut board, None, &ordering, 0);
+
+        assert_eq!(moves[0].to_string(), "a1a5");
+        assert_eq!(moves[1], killer);
+        assert_eq!(moves.last().unwrap().to_string(), "d1d5");
+        assert_eq!(
+            moves.len(),
+            board.generate_all_legal_moves_to_vec(Side::White).len()
+        );
+    }
+}
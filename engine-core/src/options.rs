@@ -0,0 +1,336 @@
+use std::fmt::Display;
+
+use crate::{
+    evaluation,
+    searching::{self, SearchParams},
+    transposition_table,
+};
+
+/// Type and default value of an option, as advertised in the "uci" response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UciOptionKind {
+    Check {
+        default: bool,
+    },
+    Spin {
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    /// An action without a value, e.g. clearing the hash
+    Button,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UciOption {
+    pub name: &'static str,
+    pub kind: UciOptionKind,
+}
+
+pub const HASH_OPTION: &str = "Hash";
+pub const THREADS_OPTION: &str = "Threads";
+pub const PONDER_OPTION: &str = "Ponder";
+pub const CHESS960_OPTION: &str = "UCI_Chess960";
+pub const CONTEMPT_OPTION: &str = "Contempt";
+pub const CLEAR_HASH_OPTION: &str = "Clear Hash";
+pub const MOVE_OVERHEAD_OPTION: &str = "Move Overhead";
+pub const MULTI_PV_OPTION: &str = "MultiPV";
+pub const SHOW_WDL_OPTION: &str = "UCI_ShowWDL";
+
+/// All the options supported by the engine
+pub const UCI_OPTIONS: [UciOption; 9] = [
+    UciOption {
+        name: HASH_OPTION,
+        kind: UciOptionKind::Spin {
+            default: transposition_table::DEFAULT_HASH_MB as i64,
+            min: 0,
+            max: 1024,
+        },
+    },
+    UciOption {
+        name: THREADS_OPTION,
+        kind: UciOptionKind::Spin {
+            default: 1,
+            min: 1,
+            max: 64,
+        },
+    },
+    UciOption {
+        name: PONDER_OPTION,
+        kind: UciOptionKind::Check { default: false },
+    },
+    UciOption {
+        name: CHESS960_OPTION,
+        kind: UciOptionKind::Check { default: false },
+    },
+    UciOption {
+        name: CONTEMPT_OPTION,
+        kind: UciOptionKind::Spin {
+            default: 0,
+            min: -100,
+            max: 100,
+        },
+    },
+    UciOption {
+        name: CLEAR_HASH_OPTION,
+        kind: UciOptionKind::Button,
+    },
+    UciOption {
+        name: MOVE_OVERHEAD_OPTION,
+        kind: UciOptionKind::Spin {
+            default: 30,
+            min: 0,
+            max: 5000,
+        },
+    },
+    UciOption {
+        name: MULTI_PV_OPTION,
+        kind: UciOptionKind::Spin {
+            default: 1,
+            min: 1,
+            max: 256,
+        },
+    },
+    UciOption {
+        name: SHOW_WDL_OPTION,
+        kind: UciOptionKind::Check { default: false },
+    },
+];
+
+pub const IID_MIN_DEPTH_OPTION: &str = "IIDMinDepth";
+pub const IID_REDUCTION_OPTION: &str = "IIDReduction";
+pub const DELTA_PRUNING_MARGIN_OPTION: &str = "DeltaPruningMargin";
+
+/// Knobs of the search heuristics for tuning, accepted by "setoption" but left out of
+/// the "uci" response so that the GUIs don't show them. The defaults are the ones of
+/// SearchParams
+pub const TUNING_OPTIONS: [UciOption; 3] = [
+    UciOption {
+        name: IID_MIN_DEPTH_OPTION,
+        kind: UciOptionKind::Spin {
+            default: searching::IID_MIN_DEPTH as i64,
+            min: 2,
+            max: 64,
+        },
+    },
+    UciOption {
+        name: IID_REDUCTION_OPTION,
+        kind: UciOptionKind::Spin {
+            default: searching::IID_REDUCTION as i64,
+            min: 1,
+            max: 8,
+        },
+    },
+    UciOption {
+        name: DELTA_PRUNING_MARGIN_OPTION,
+        kind: UciOptionKind::Spin {
+            default: evaluation::DELTA_PRUNING_MARGIN as i64,
+            min: 0,
+            max: 2000,
+        },
+    },
+];
+
+impl Display for UciOption {
+    /// # Examples
+    /// option name Hash type spin default 16 min 0 max 1024
+    /// option name Ponder type check default false
+    /// option name Clear Hash type button
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+
+        match self.kind {
+            UciOptionKind::Check { default } => write!(f, "check default {default}"),
+            UciOptionKind::Spin { default, min, max } => {
+                write!(f, "spin default {default} min {min} max {max}")
+            }
+            UciOptionKind::Button => write!(f, "button"),
+        }
+    }
+}
+
+impl UciOption {
+    /// Looks the option up by its name, the tuning options included. UCI option names
+    /// are case insensitive
+    pub fn find(name: &str) -> Option<&'static UciOption> {
+        UCI_OPTIONS
+            .iter()
+            .chain(&TUNING_OPTIONS)
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Current values of all the options
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineOptions {
+    /// Size of the transposition table, 0 disables it
+    pub hash_mb: usize,
+    /// Number of the search threads, the main one included
+    pub threads: usize,
+    pub ponder: bool,
+    pub chess960: bool,
+    pub contempt: i32,
+    /// Milliseconds of the time budget kept back for the GUI and network latency
+    pub move_overhead_ms: u64,
+    /// Number of the best lines reported
+    pub multi_pv: usize,
+    /// Win, draw and loss chances are reported with the scores
+    pub show_wdl: bool,
+    pub search_params: SearchParams,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        let mut options = Self {
+            hash_mb: 0,
+            threads: 0,
+            ponder: false,
+            chess960: false,
+            contempt: 0,
+            move_overhead_ms: 0,
+            multi_pv: 0,
+            show_wdl: false,
+            search_params: SearchParams::default(),
+        };
+
+        for option in UCI_OPTIONS.iter().chain(&TUNING_OPTIONS) {
+            let default = match option.kind {
+                UciOptionKind::Check { default } => default.to_string(),
+                UciOptionKind::Spin { default, .. } => default.to_string(),
+                UciOptionKind::Button => continue,
+            };
+
+            options.set(option.name, Some(&default)).unwrap();
+        }
+
+        options
+    }
+}
+
+impl EngineOptions {
+    /// Applies a "setoption" value, values out of the option's range are rejected.
+    /// Buttons hold no value, pressing them is up to the caller
+    pub fn set(&mut self, name: &str, value: Option<&str>) -> Result<(), &'static str> {
+        let option = UciOption::find(name).ok_or("Unknown option")?;
+
+        if option.kind == UciOptionKind::Button {
+            return Ok(());
+        }
+
+        let value = value.ok_or("The option requires a value")?;
+
+        match option.kind {
+            UciOptionKind::Check { .. } => {
+                let value = match value {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err("Failed to parse check option value"),
+                };
+
+                match option.name {
+                    PONDER_OPTION => self.ponder = value,
+                    CHESS960_OPTION => self.chess960 = value,
+                    SHOW_WDL_OPTION => self.show_wdl = value,
+                    _ => unreachable!(),
+                }
+            }
+            UciOptionKind::Spin { min, max, .. } => {
+                let value = value
+                    .parse::<i64>()
+                    .map_err(|_| "Failed to parse spin option value")?;
+
+                if !(min..=max).contains(&value) {
+                    return Err("The option value is out of range");
+                }
+
+                match option.name {
+                    HASH_OPTION => self.hash_mb = value as usize,
+                    THREADS_OPTION => self.threads = value as usize,
+                    CONTEMPT_OPTION => self.contempt = value as i32,
+                    MOVE_OVERHEAD_OPTION => self.move_overhead_ms = value as u64,
+                    MULTI_PV_OPTION => self.multi_pv = value as usize,
+                    IID_MIN_DEPTH_OPTION => self.search_params.iid_min_depth = value as u32,
+                    IID_REDUCTION_OPTION => self.search_params.iid_reduction = value as u32,
+                    DELTA_PRUNING_MARGIN_OPTION => {
+                        self.search_params.delta_pruning_margin = value as i32
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            UciOptionKind::Button => unreachable!(),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_display() {
+        assert_eq!(
+            "option name Hash type spin default 16 min 0 max 1024",
+            UciOption::find("hash").unwrap().to_string()
+        );
+        assert_eq!(
+            "option name UCI_Chess960 type check default false",
+            UciOption::find(CHESS960_OPTION).unwrap().to_string()
+        );
+        assert_eq!(
+            "option name Clear Hash type button",
+            UciOption::find("clear hash").unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_setting_options() {
+        let mut options = EngineOptions::default();
+        assert_eq!(options.hash_mb, 16);
+
+        assert!(options.set("hash", Some("64")).is_ok());
+        assert_eq!(options.hash_mb, 64);
+
+        assert!(options.set("UCI_Chess960", Some("true")).is_ok());
+        assert!(options.chess960);
+
+        assert!(options.set("Contempt", Some("-20")).is_ok());
+        assert_eq!(options.contempt, -20);
+
+        assert_eq!(options.move_overhead_ms, 30);
+        assert!(options.set("move overhead", Some("100")).is_ok());
+        assert_eq!(options.move_overhead_ms, 100);
+        assert!(options.set("Move Overhead", Some("-1")).is_err());
+
+        assert_eq!(options.multi_pv, 1);
+        assert!(options.set("multipv", Some("3")).is_ok());
+        assert_eq!(options.multi_pv, 3);
+        assert!(options.set("MultiPV", Some("0")).is_err());
+
+        assert!(!options.show_wdl);
+        assert!(options.set("uci_showwdl", Some("true")).is_ok());
+        assert!(options.show_wdl);
+
+        assert_eq!(options.search_params, SearchParams::default());
+        assert!(options.set("IIDReduction", Some("3")).is_ok());
+        assert!(options.set("deltapruningmargin", Some("150")).is_ok());
+        assert_eq!(options.search_params.iid_reduction, 3);
+        assert_eq!(options.search_params.delta_pruning_margin, 150);
+        assert!(options.set("IIDMinDepth", Some("1")).is_err());
+
+        assert!(options.set("Hash", Some("-1")).is_err());
+        assert!(options.set("Ponder", Some("yes")).is_err());
+        assert!(options.set("Threads", None).is_err());
+        assert_eq!(options.threads, 1);
+        assert!(options.set("threads", Some("4")).is_ok());
+        assert_eq!(options.threads, 4);
+        assert!(options.set("Threads", Some("0")).is_err());
+        assert!(options.set("Unknown", Some("1")).is_err());
+        assert!(options.set("Clear Hash", None).is_ok());
+        assert_eq!(options.hash_mb, 64);
+
+        assert!(options.set("Hash", Some("0")).is_ok());
+        assert_eq!(options.hash_mb, 0);
+    }
+}
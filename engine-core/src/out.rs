@@ -1,19 +1,92 @@
 use std::{
     io::Write,
-    sync::{Arc, Mutex, OnceLock},
+    sync::{Arc, Mutex},
 };
 
-static OUT: OnceLock<Arc<Mutex<dyn Write + Send>>> = OnceLock::new();
+/// Sink of all the protocol output, the lines of one write_line call are never
+/// interleaved with the output of other threads
+static OUT: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
 
+/// Held by the tests installing a sink, they would steal each other's output otherwise
+#[cfg(test)]
+pub(crate) static TEST_OUT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Installs the sink of the engine output, replacing the previous one
 pub fn init_out(w: impl Write + Send + 'static) {
-    OUT.set(Arc::new(Mutex::new(w))).ok();
+    *OUT.lock().unwrap() = Some(Box::new(w));
 }
 
 pub fn write_line(s: &str) {
-    if let Some(out) = OUT.get() {
-        let mut w = out.lock().unwrap();
-
+    if let Some(w) = OUT.lock().unwrap().as_mut() {
         writeln!(w, "{s}").ok();
         w.flush().ok();
     }
 }
+
+/// Sink keeping the output in memory, the clones share it, so a clone can be installed
+/// with init_out and the written lines read back through another one
+/// # Examples
+/// let sink = MemorySink::new();
+/// out::init_out(sink.clone());
+/// out::write_line("readyok");
+/// assert_eq!(sink.take_lines(), ["readyok"]);
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink(Arc<Mutex<Vec<u8>>>);
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+
+    /// Returns the written lines and empties the sink
+    pub fn take_lines(&self) -> Vec<String> {
+        let bytes = std::mem::take(&mut *self.0.lock().unwrap());
+
+        String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl Write for MemorySink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_sink() {
+        let _guard = TEST_OUT_LOCK.lock().unwrap();
+
+        let sink = MemorySink::new();
+        init_out(sink.clone());
+
+        write_line("id name Orion");
+        write_line("uciok");
+        assert_eq!(sink.contents(), "id name Orion\nuciok\n");
+        assert_eq!(sink.take_lines(), ["id name Orion", "uciok"]);
+        assert!(sink.take_lines().is_empty());
+
+        // Installing another sink detaches the previous one
+        let other = MemorySink::new();
+        init_out(other.clone());
+        write_line("readyok");
+        assert!(sink.take_lines().is_empty());
+        assert_eq!(other.take_lines(), ["readyok"]);
+    }
+}
@@ -0,0 +1,80 @@
+use std::cell::RefCell;
+
+use crate::evaluation::TaperedScore;
+
+/// Number of entries of a pawn hash table, a power of two
+const PAWN_HASH_ENTRIES: usize = 1 << 14;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PawnHashEntry {
+    pawn_hash: u64,
+    score: TaperedScore,
+}
+
+/// Pawn structure scores keyed on the pawn hash. The pawns rarely move compared to the
+/// other pieces, so most of the evaluations find their pawn structure here
+pub(crate) struct PawnHashTable {
+    entries: Vec<PawnHashEntry>,
+}
+
+impl PawnHashTable {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: vec![PawnHashEntry::default(); PAWN_HASH_ENTRIES],
+        }
+    }
+
+    /// Returns the stored score of the pawns, computes and stores it if it is not
+    /// there. A position without pawns has the hash 0 and the score 0, which the empty
+    /// entries already hold
+    pub(crate) fn probe(
+        &mut self,
+        pawn_hash: u64,
+        compute: impl FnOnce() -> TaperedScore,
+    ) -> TaperedScore {
+        let entry = &mut self.entries[pawn_hash as usize & (PAWN_HASH_ENTRIES - 1)];
+
+        if entry.pawn_hash != pawn_hash {
+            *entry = PawnHashEntry {
+                pawn_hash,
+                score: compute(),
+            };
+        }
+
+        entry.score
+    }
+}
+
+thread_local! {
+    /// Every search thread fills its own table, the entries don't depend on the search
+    static PAWN_HASH_TABLE: RefCell<PawnHashTable> = RefCell::new(PawnHashTable::new());
+}
+
+/// PawnHashTable::probe on the table of the current thread
+pub(crate) fn probe(pawn_hash: u64, compute: impl FnOnce() -> TaperedScore) -> TaperedScore {
+    PAWN_HASH_TABLE.with_borrow_mut(|table| table.probe(pawn_hash, compute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pawn_hash_table() {
+        let mut table = PawnHashTable::new();
+        let score = TaperedScore::new(10, -20);
+
+        assert_eq!(table.probe(12345, || score), score);
+        assert_eq!(table.probe(12345, || unreachable!()), score);
+
+        // Another key of the same slot replaces the entry
+        let other = 12345 + PAWN_HASH_ENTRIES as u64;
+        assert_eq!(
+            table.probe(other, TaperedScore::default),
+            TaperedScore::default()
+        );
+        assert_eq!(table.probe(12345, || score), score);
+
+        assert_eq!(table.probe(0, || unreachable!()), TaperedScore::default());
+    }
+}
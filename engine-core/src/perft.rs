@@ -1,7 +1,11 @@
-use crate::{board::Board, move_generator::MoveBuffer};
+use crate::{
+    board::Board,
+    enums::{Move, MoveFlags},
+    move_generator::MoveBuffer,
+};
 
 #[allow(dead_code)]
-pub(crate) fn perft(board: &mut Board, depth: u32, ply: usize, bufs: &mut [MoveBuffer]) -> u64 {
+pub(crate) fn perft(board: &mut Board, depth: u32, bufs: &mut [MoveBuffer]) -> u64 {
     if depth == 0 {
         return 1;
     }
@@ -14,19 +18,150 @@ pub(crate) fn perft(board: &mut Board, depth: u32, ply: usize, bufs: &mut [MoveB
 
     for &mv in cur.iter() {
         board.make_move(mv);
-        nodes += perft(board, depth - 1, ply + 1, rest);
+        nodes += perft(board, depth - 1, rest);
         board.unmake_move();
     }
 
     nodes
 }
 
+/// Leaf counts of a perft by the kind of the last move, the usual categories of the
+/// published perft results. A move generation bug often shows in one of them while the
+/// total is only slightly off
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct PerftStats {
+    pub(crate) nodes: u64,
+    /// En passant captures included
+    pub(crate) captures: u64,
+    pub(crate) en_passants: u64,
+    pub(crate) castles: u64,
+    pub(crate) promotions: u64,
+    pub(crate) checks: u64,
+}
+
+/// Same as perft, but the leaves are also counted by category
+#[allow(dead_code)]
+pub(crate) fn perft_with_stats(
+    board: &mut Board,
+    depth: u32,
+    bufs: &mut [MoveBuffer],
+    stats: &mut PerftStats,
+) {
+    let (cur, rest) = bufs.split_first_mut().unwrap();
+
+    board.generate_all_legal_moves(board.game_state.side_to_move, cur);
+
+    for &mv in cur.iter() {
+        board.make_move(mv);
+
+        if depth > 1 {
+            perft_with_stats(board, depth - 1, rest, stats);
+        } else {
+            stats.nodes += 1;
+            stats.captures += u64::from(mv.is_capture());
+            stats.promotions += u64::from(mv.is_promo());
+            stats.checks += u64::from(board.is_in_check(board.game_state.side_to_move));
+
+            match mv {
+                Move::Normal { flags, .. } if flags.contains(MoveFlags::EN_PASSANT) => {
+                    stats.en_passants += 1;
+                }
+                Move::Castle { .. } => stats.castles += 1,
+                _ => {}
+            }
+        }
+
+        board.unmake_move();
+    }
+}
+
+/// Node counts of the visited subtrees, keyed on the position hash and the depth
+pub(crate) struct PerftCache {
+    entries: Vec<PerftCacheEntry>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PerftCacheEntry {
+    hash: u64,
+    depth: u32,
+    nodes: u64,
+}
+
+#[allow(dead_code)]
+impl PerftCache {
+    /// The number of entries is rounded up to a power of two
+    pub(crate) fn new(entries_count: usize) -> Self {
+        Self {
+            entries: vec![PerftCacheEntry::default(); entries_count.next_power_of_two()],
+        }
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        hash as usize & (self.entries.len() - 1)
+    }
+
+    fn get(&self, hash: u64, depth: u32) -> Option<u64> {
+        let entry = self.entries[self.index(hash)];
+
+        (entry.hash == hash && entry.depth == depth).then_some(entry.nodes)
+    }
+
+    /// Always replaces the previous entry of the slot
+    fn store(&mut self, hash: u64, depth: u32, nodes: u64) {
+        let index = self.index(hash);
+        self.entries[index] = PerftCacheEntry { hash, depth, nodes };
+    }
+}
+
+/// Same as perft, but the subtrees of transposed positions are counted once
+#[allow(dead_code)]
+pub(crate) fn perft_hashed(
+    board: &mut Board,
+    depth: u32,
+    bufs: &mut [MoveBuffer],
+    cache: &mut PerftCache,
+) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let hash = board.game_state.hash;
+    if let Some(nodes) = cache.get(hash, depth) {
+        return nodes;
+    }
+
+    let (cur, rest) = bufs.split_first_mut().unwrap();
+
+    board.generate_all_legal_moves(board.game_state.side_to_move, cur);
+
+    // The leaves are not worth caching
+    let nodes = if depth == 1 {
+        cur.len() as u64
+    } else {
+        let mut nodes = 0;
+
+        for &mv in cur.iter() {
+            board.make_move(mv);
+            nodes += perft_hashed(board, depth - 1, rest, cache);
+            board.unmake_move();
+        }
+
+        nodes
+    };
+
+    cache.store(hash, depth, nodes);
+
+    nodes
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{chess_consts, fen_parser};
 
     use super::*;
 
+    const PERFT_CACHE_ENTRIES: usize = 1 << 20;
+
     fn test_perft(fen_str: &str, expectations: &[(u32, u64)]) {
         let mut board = fen_parser::parse_fen_string(fen_str).unwrap();
 
@@ -34,8 +169,102 @@ mod tests {
             .map(|_| Vec::with_capacity(chess_consts::MOVES_BUF_SIZE))
             .collect();
 
+        let mut cache = PerftCache::new(PERFT_CACHE_ENTRIES);
+
         for &(depth, expected_moves_count) in expectations {
-            assert_eq!(expected_moves_count, perft(&mut board, depth, 0, &mut bufs));
+            assert_eq!(
+                expected_moves_count,
+                perft_hashed(&mut board, depth, &mut bufs, &mut cache)
+            );
+        }
+    }
+
+    #[test]
+    fn test_perft_hashed_matches_perft() {
+        let mut bufs: Vec<MoveBuffer> = (0..chess_consts::MAX_PLY)
+            .map(|_| Vec::with_capacity(chess_consts::MOVES_BUF_SIZE))
+            .collect();
+
+        for fen in [
+            chess_consts::fen_strings::START_POS_FEN,
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        ] {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            // A tiny cache makes the slots get replaced all the time
+            let mut cache = PerftCache::new(64);
+
+            for depth in 1..=4 {
+                assert_eq!(
+                    perft(&mut board, depth, &mut bufs),
+                    perft_hashed(&mut board, depth, &mut bufs, &mut cache),
+                    "{fen}: {depth}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_perft_stats() {
+        let stats = |fen: &str, depth: u32| {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            let mut bufs: Vec<MoveBuffer> = (0..chess_consts::MAX_PLY)
+                .map(|_| Vec::with_capacity(chess_consts::MOVES_BUF_SIZE))
+                .collect();
+
+            let mut stats = PerftStats::default();
+            perft_with_stats(&mut board, depth, &mut bufs, &mut stats);
+            stats
+        };
+
+        // nodes, captures, en passants, castles, promotions, checks
+        let cases = [
+            (
+                chess_consts::fen_strings::START_POS_FEN,
+                4,
+                [197_281, 1_576, 0, 0, 0, 469],
+            ),
+            (
+                chess_consts::fen_strings::TRICKY_POS_FEN,
+                1,
+                [48, 8, 0, 2, 0, 0],
+            ),
+            (
+                chess_consts::fen_strings::TRICKY_POS_FEN,
+                2,
+                [2_039, 351, 1, 91, 0, 3],
+            ),
+            (
+                chess_consts::fen_strings::TRICKY_POS_FEN,
+                3,
+                [97_862, 17_102, 45, 3_162, 0, 993],
+            ),
+            (
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                4,
+                [43_238, 3_348, 123, 0, 0, 1_680],
+            ),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                3,
+                [9_467, 1_021, 4, 0, 120, 38],
+            ),
+        ];
+
+        for (fen, depth, [nodes, captures, en_passants, castles, promotions, checks]) in cases {
+            assert_eq!(
+                stats(fen, depth),
+                PerftStats {
+                    nodes,
+                    captures,
+                    en_passants,
+                    castles,
+                    promotions,
+                    checks,
+                },
+                "{fen} depth {depth}"
+            );
         }
     }
 
@@ -43,7 +272,14 @@ mod tests {
     fn test_perft_initial_position() {
         test_perft(
             chess_consts::fen_strings::START_POS_FEN,
-            &[(1, 20), (2, 400), (3, 8902), (4, 197_281), (5, 4_865_609)],
+            &[
+                (1, 20),
+                (2, 400),
+                (3, 8902),
+                (4, 197_281),
+                (5, 4_865_609),
+                (6, 119_060_324),
+            ],
         );
     }
 
@@ -56,7 +292,7 @@ mod tests {
                 (2, 2039),
                 (3, 97_862),
                 (4, 4_085_603),
-                (5, 19_3690_690),
+                (5, 193_690_690),
                 // (6, 8_031_647_685),
             ],
         );
@@ -93,4 +329,20 @@ mod tests {
             &[(1, 46), (2, 2_079), (3, 89_890), (4, 3_894_594)],
         );
     }
+
+    #[test]
+    fn chess960_positions_test() {
+        test_perft(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            &[(1, 21), (2, 528), (3, 12_189), (4, 326_672), (5, 8_146_062)],
+        );
+        test_perft(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            &[(1, 21), (2, 807), (3, 18_002), (4, 667_366)],
+        );
+        test_perft(
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            &[(1, 20), (2, 479), (3, 10_471), (4, 273_318)],
+        );
+    }
 }
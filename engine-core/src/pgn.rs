@@ -0,0 +1,272 @@
+use crate::{
+    board::Board,
+    chess_consts,
+    enums::{Move, Side},
+};
+
+const PGN_LINE_WIDTH: usize = 80;
+/// PGN has no notation for null moves, this is the one most GUIs understand
+const NULL_MOVE_SAN: &str = "--";
+
+/// Values of the seven tag roster, the result tag is taken from the final position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnTags {
+    pub event: String,
+    pub site: String,
+    pub date: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+}
+
+impl Default for PgnTags {
+    fn default() -> Self {
+        Self {
+            event: "?".to_string(),
+            site: "?".to_string(),
+            date: "????.??.??".to_string(),
+            round: "?".to_string(),
+            white: "?".to_string(),
+            black: "?".to_string(),
+        }
+    }
+}
+
+/// Writes the game played on the board as PGN, the moves are taken from the board's
+/// history and replayed from the position the game started in
+/// # Examples
+/// [Event "?"]
+/// ...
+/// [Result "*"]
+///
+/// 1. e4 e5 2. Nf3 *
+pub fn serialize_game_to_pgn(board: &Board, tags: &PgnTags) -> String {
+    let (mut board, moves) = rewind_game(board);
+
+    let start_fen = board.to_fen();
+
+    let mut movetext = Vec::with_capacity(moves.len() * 3 / 2 + 1);
+    for (i, &mv) in moves.iter().enumerate() {
+        let full_moves_count = board.game_state.full_moves_count;
+
+        match board.game_state.side_to_move {
+            Side::White => movetext.push(format!("{full_moves_count}.")),
+            Side::Black if i == 0 => movetext.push(format!("{full_moves_count}...")),
+            Side::Black => {}
+        }
+
+        movetext.push(replay_move(&mut board, mv));
+    }
+
+    let result = get_game_result(&mut board);
+    movetext.push(result.clone());
+
+    let mut pgn = String::new();
+    for (name, value) in [
+        ("Event", tags.event.as_str()),
+        ("Site", tags.site.as_str()),
+        ("Date", tags.date.as_str()),
+        ("Round", tags.round.as_str()),
+        ("White", tags.white.as_str()),
+        ("Black", tags.black.as_str()),
+        ("Result", result.as_str()),
+    ] {
+        pgn.push_str(&format!("[{name} \"{value}\"]\n"));
+    }
+
+    if start_fen != chess_consts::fen_strings::START_POS_FEN {
+        pgn.push_str("[SetUp \"1\"]\n");
+        pgn.push_str(&format!("[FEN \"{start_fen}\"]\n"));
+    }
+    pgn.push('\n');
+
+    let mut line_len = 0;
+    for token in movetext {
+        if line_len > 0 && line_len + 1 + token.len() > PGN_LINE_WIDTH {
+            pgn.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            pgn.push(' ');
+            line_len += 1;
+        }
+
+        pgn.push_str(&token);
+        line_len += token.len();
+    }
+    pgn.push('\n');
+
+    pgn
+}
+
+impl Board {
+    /// The FEN of every position of the game after the moves, from the first move to
+    /// the last one, so that a GUI can show any of them
+    /// # Examples
+    /// position startpos moves e2e4 e7e5 ->
+    /// rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1,
+    /// rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2
+    pub fn position_history_fens(&self) -> Vec<String> {
+        self.position_history_with_san()
+            .into_iter()
+            .map(|(_, fen)| fen)
+            .collect()
+    }
+
+    /// position_history_fens with the SAN of the move that led to each position,
+    /// e.g. ("e4", "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+    pub fn position_history_with_san(&self) -> Vec<(String, String)> {
+        let (mut board, moves) = rewind_game(self);
+
+        moves
+            .into_iter()
+            .map(|mv| {
+                let san = replay_move(&mut board, mv);
+                (san, board.to_fen())
+            })
+            .collect()
+    }
+}
+
+/// Takes back all the moves of the board's history, returns the position the game
+/// started in and the moves in the order they were played, None for a null move
+fn rewind_game(board: &Board) -> (Board, Vec<Option<Move>>) {
+    let mut board = board.clone();
+
+    let mut moves = Vec::with_capacity(board.history.len());
+    while let Some(entry) = board.history.last() {
+        moves.push(entry.mv);
+
+        match entry.mv {
+            Some(_) => board.unmake_move(),
+            None => board.unmake_null_move(),
+        }
+    }
+    moves.reverse();
+
+    (board, moves)
+}
+
+/// Plays the move of the history again, returns its SAN
+fn replay_move(board: &mut Board, mv: Option<Move>) -> String {
+    match mv {
+        Some(mv) => {
+            let san = board.move_to_san(mv);
+            board.make_move(mv);
+            san
+        }
+        None => {
+            board.make_null_move();
+            NULL_MOVE_SAN.to_string()
+        }
+    }
+}
+
+/// Result of the game in the final position, "*" if the game is not over yet
+fn get_game_result(board: &mut Board) -> String {
+    board
+        .game_result()
+        .map_or("*".to_string(), |result| result.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fen_parser, uci};
+
+    use super::*;
+
+    #[test]
+    fn test_serialize_game_to_pgn() {
+        let board =
+            uci::parse_uci_position_command("position startpos moves f2f3 e7e5 g2g4 d8h4", false)
+                .unwrap();
+
+        let tags = PgnTags {
+            white: "Orion".to_string(),
+            black: "Orion".to_string(),
+            ..PgnTags::default()
+        };
+
+        assert_eq!(
+            serialize_game_to_pgn(&board, &tags),
+            "[Event \"?\"]\n\
+             [Site \"?\"]\n\
+             [Date \"????.??.??\"]\n\
+             [Round \"?\"]\n\
+             [White \"Orion\"]\n\
+             [Black \"Orion\"]\n\
+             [Result \"0-1\"]\n\
+             \n\
+             1. f3 e5 2. g4 Qh4# 0-1\n"
+        );
+    }
+
+    #[test]
+    fn test_serialize_game_from_fen_to_pgn() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 3 20";
+        let mut board = fen_parser::parse_fen_string(fen).unwrap();
+        for mv in ["e8c8", "e1g1"] {
+            let mv = crate::enums::Move::parse(mv, &mut board).unwrap();
+            board.make_move(mv);
+        }
+
+        let pgn = serialize_game_to_pgn(&board, &PgnTags::default());
+
+        assert!(pgn.contains(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n")));
+        assert!(pgn.ends_with("\n\n20... O-O-O 21. O-O *\n"));
+
+        board.make_null_move();
+        let pgn = serialize_game_to_pgn(&board, &PgnTags::default());
+        assert!(pgn.ends_with("\n\n20... O-O-O 21. O-O -- *\n"));
+    }
+
+    #[test]
+    fn test_pgn_line_wrapping() {
+        let mut board = Board::get_start_position();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        for i in 0..40 {
+            let mv = crate::enums::Move::parse(shuffle[i % 4], &mut board).unwrap();
+            board.make_move(mv);
+        }
+
+        let pgn = serialize_game_to_pgn(&board, &PgnTags::default());
+        let movetext = pgn.split("\n\n").nth(1).unwrap();
+
+        assert!(movetext.lines().count() > 1);
+        assert!(movetext.lines().all(|line| line.len() <= PGN_LINE_WIDTH));
+    }
+
+    #[test]
+    fn test_position_history() {
+        let board =
+            uci::parse_uci_position_command("position startpos moves e2e4 e7e5 g1f3", false)
+                .unwrap();
+
+        assert_eq!(
+            board.position_history_with_san(),
+            [
+                (
+                    "e4".to_string(),
+                    "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".to_string()
+                ),
+                (
+                    "e5".to_string(),
+                    "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2".to_string()
+                ),
+                (
+                    "Nf3".to_string(),
+                    "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2".to_string()
+                ),
+            ]
+        );
+
+        let fens = board.position_history_fens();
+        assert_eq!(fens.len(), 3);
+        assert_eq!(fens.last(), Some(&board.to_fen()));
+
+        assert!(
+            Board::get_start_position()
+                .position_history_fens()
+                .is_empty()
+        );
+    }
+}
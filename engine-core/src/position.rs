@@ -0,0 +1,269 @@
+use std::{fmt::Debug, sync::Arc};
+
+use crate::{
+    board::Board,
+    enums::{Move, Side},
+    evaluation,
+    fen_parser::{self, ParseFenError},
+    game_result::GameResult,
+    move_operations::IllegalMove,
+    move_ordering::MoveOrderingState,
+    searching::{self, StopToken},
+    transposition_table::{DEFAULT_HASH_MB, TranspositionTable},
+};
+
+/// A chess position with the moves played on it, the entry point for using the engine
+/// as a library
+/// # Examples
+/// let mut position = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")?;
+/// let mv = position.best_move(3).unwrap(); // a1a8
+/// position.make(mv)?;
+/// assert!(position.is_checkmate());
+#[derive(Clone)]
+pub struct Position {
+    board: Board,
+    /// Killers and history kept between the searches of best_move, the way the engine
+    /// keeps them between the searches of one game
+    search_state: Box<MoveOrderingState>,
+    /// The transposition table of the searches, shared by the clones of the position
+    tt: Arc<TranspositionTable>,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self::from_board(Board::get_start_position())
+    }
+}
+
+/// Two positions are the same whatever their searches learned
+impl PartialEq for Position {
+    fn eq(&self, other: &Self) -> bool {
+        self.board == other.board
+    }
+}
+
+impl Debug for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Position")
+            .field("board", &self.board)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Position {
+    /// The standard starting position
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Self, ParseFenError> {
+        Ok(Self::from_board(fen_parser::parse_fen_string(fen)?))
+    }
+
+    /// The position set up with Board::empty and its BoardBuilder
+    pub fn from_board(board: Board) -> Self {
+        Self {
+            board,
+            search_state: Box::new(MoveOrderingState::new()),
+            tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB).unwrap()),
+        }
+    }
+
+    pub fn to_fen(&self) -> String {
+        self.board.to_fen()
+    }
+
+    /// The FEN after each move made on the position, see Board::position_history_fens
+    pub fn history_fens(&self) -> Vec<String> {
+        self.board.position_history_fens()
+    }
+
+    pub fn side_to_move(&self) -> Side {
+        self.board.game_state.side_to_move
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut board = self.board.clone();
+
+        board.generate_all_legal_moves_to_vec(self.side_to_move())
+    }
+
+    /// Finds the legal move written in UCI notation, e.g. "e2e4" or "e7e8q"
+    pub fn parse_move(&self, move_str: &str) -> Option<Move> {
+        Move::parse(move_str, &mut self.board.clone())
+    }
+
+    /// Plays the move, moves that are not legal in the position are rejected
+    pub fn make(&mut self, mv: Move) -> Result<(), IllegalMove> {
+        self.board.make_move_checked(mv)
+    }
+
+    /// Takes back the last move, returns None if no move has been made
+    pub fn unmake(&mut self) -> Option<Move> {
+        let mv = self.board.history.last()?.mv?;
+        self.board.unmake_move();
+
+        Some(mv)
+    }
+
+    pub fn is_check(&self) -> bool {
+        self.board.is_in_check(self.side_to_move())
+    }
+
+    pub fn legal_move_count(&self) -> usize {
+        self.board.clone().legal_move_count(self.side_to_move())
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        self.is_check() && self.legal_move_count() == 0
+    }
+
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_check() && self.legal_move_count() == 0
+    }
+
+    /// The side to move may claim a draw by the fifty-move rule
+    pub fn can_claim_fifty_move_draw(&self) -> bool {
+        self.board.can_claim_fifty_move_draw()
+    }
+
+    /// The position occurred for the third time, with the same side to move and the
+    /// same castling and en passant rights
+    pub fn can_claim_threefold(&self) -> bool {
+        self.board.can_claim_threefold()
+    }
+
+    /// How the game ended, None while it goes on, see Board::game_result
+    pub fn game_result(&self) -> Option<GameResult> {
+        self.board.clone().game_result()
+    }
+
+    /// Static evaluation in centipawns from white's perspective, positive when white
+    /// stands better whoever is to move
+    pub fn evaluate(&self) -> i32 {
+        evaluation::evaluate_white_relative(&self.board)
+    }
+
+    /// Searches the position to the given depth, returns None if there are no legal moves.
+    /// The move ordering and the transposition table of the earlier searches carry over
+    /// to this one
+    pub fn best_move(&mut self, depth: u32) -> Option<Move> {
+        let mut board = self.board.clone();
+
+        searching::search_bestmove(
+            &mut board,
+            depth,
+            &StopToken::new(),
+            &mut self.search_state,
+            &self.tt,
+        )
+        .map(|result| result.best_move)
+    }
+
+    /// Forgets the killers, the history and the transposition table entries of the
+    /// earlier searches, so that the next one searches as a fresh engine would. Useful
+    /// between unrelated analyses
+    pub fn clear_search_state(&mut self) {
+        self.search_state.clear();
+        self.tt.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chess_consts,
+        enums::{Piece, Square},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_position_api() {
+        let mut position = Position::new();
+        assert_eq!(position.legal_moves().len(), 20);
+        assert_eq!(position.legal_move_count(), 20);
+        assert_eq!(position.side_to_move(), Side::White);
+
+        // Fool's mate
+        for mv_str in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            let mv = position.parse_move(mv_str).unwrap();
+            assert!(position.make(mv).is_ok());
+        }
+
+        assert!(position.is_check());
+        assert!(position.is_checkmate());
+        assert!(!position.is_stalemate());
+
+        let last_move = position.unmake().unwrap();
+        assert_eq!(last_move.get_from_to(), (Square::D8, Square::H4));
+        assert!(!position.is_check());
+
+        assert!(position.make(last_move).is_ok());
+        assert!(position.make(last_move).is_err());
+    }
+
+    #[test]
+    fn test_position_from_fen() {
+        let mut position = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(position.is_stalemate());
+        assert_eq!(position.best_move(2), None);
+        assert_eq!(position.to_fen(), "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+
+        let mut position = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(position.best_move(3), position.parse_move("a1a8"));
+        assert!(position.evaluate() > 0);
+        assert!(
+            Position::from_fen("r5k1/8/8/8/8/8/5PPP/6K1 w - - 0 1")
+                .unwrap()
+                .evaluate()
+                < 0
+        );
+
+        assert!(Position::from_fen("8/8/8/8/8/8/8/8 w - - 0 1").is_err());
+
+        let board = Board::empty()
+            .set_piece(Side::White, Piece::King, Square::G6)
+            .set_piece(Side::White, Piece::Queen, Square::F7)
+            .set_piece(Side::Black, Piece::King, Square::H8)
+            .set_side_to_move(Side::Black)
+            .build()
+            .unwrap();
+        assert!(Position::from_board(board).is_stalemate());
+        assert_eq!(Position::new().unmake(), None);
+    }
+
+    #[test]
+    fn test_clear_search_state() {
+        let mut position = Position::from_fen(chess_consts::fen_strings::CMK_POS_FEN).unwrap();
+        let fresh_move = position.best_move(4);
+
+        // The deeper search leaves its history behind, the reset drops it
+        position.best_move(5);
+        position.clear_search_state();
+
+        assert_eq!(position.best_move(4), fresh_move);
+    }
+
+    #[test]
+    fn test_draw_claims() {
+        let mut position = Position::new();
+
+        for mv_str in [
+            "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8",
+        ] {
+            assert!(!position.can_claim_threefold());
+
+            let mv = position.parse_move(mv_str).unwrap();
+            position.make(mv).unwrap();
+        }
+
+        // The start position is on the board for the third time
+        assert!(position.can_claim_threefold());
+        assert!(!position.can_claim_fifty_move_draw());
+
+        let position = Position::from_fen("k7/8/8/8/8/8/8/2K4R w - - 100 80").unwrap();
+        assert!(position.can_claim_fifty_move_draw());
+        assert!(!position.can_claim_threefold());
+    }
+}
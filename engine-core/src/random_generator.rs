@@ -22,6 +22,8 @@ impl XorShift64Star {
         x.wrapping_mul(0x2545F4914F6CDD1D)
     }
 
+    // Only the magic number search uses it, the magics themselves are precomputed
+    #[cfg(test)]
     pub(crate) const fn generate_magic_number_candidate(&mut self) -> u64 {
         self.next_u64() & self.next_u64() & self.next_u64()
     }
@@ -0,0 +1,297 @@
+use crate::{
+    board::Board,
+    enums::{CastlingSide, Move, Piece, Side, Square},
+};
+
+impl Board {
+    /// Writes a legal move of the side to move in standard algebraic notation
+    /// # Examples
+    /// e2e4 -> e4
+    /// g1f3 -> Nf3
+    /// e7f8q giving mate -> exf8=Q#
+    /// O-O -> O-O
+    pub(crate) fn move_to_san(&mut self, mv: Move) -> String {
+        let side = self.game_state.side_to_move;
+
+        let mut san = match mv {
+            Move::Castle {
+                side: CastlingSide::KingSide,
+                ..
+            } => "O-O".to_string(),
+            Move::Castle {
+                side: CastlingSide::QueenSide,
+                ..
+            } => "O-O-O".to_string(),
+            Move::Normal {
+                from,
+                to,
+                piece,
+                captured,
+                promo,
+                ..
+            } => {
+                let mut san = String::new();
+
+                if piece == Piece::Pawn {
+                    if captured.is_some() {
+                        san.push(file_char(from));
+                    }
+                } else {
+                    san.push(piece.to_char(Side::White));
+                    san.push_str(&self.get_san_disambiguation(mv));
+                }
+
+                if captured.is_some() {
+                    san.push('x');
+                }
+                san.push_str(&to.to_string());
+
+                if let Some(promo_piece) = promo {
+                    san.push('=');
+                    san.push(promo_piece.to_char(Side::White));
+                }
+
+                san
+            }
+        };
+
+        self.make_move(mv);
+        if self.is_in_check(side.opposite()) {
+            let mated = self.legal_move_count(side.opposite()) == 0;
+            san.push(if mated { '#' } else { '+' });
+        }
+        self.unmake_move();
+
+        san
+    }
+
+    /// Finds the legal move of the side to move written in standard algebraic notation,
+    /// check and annotation symbols are ignored, ambiguous moves are rejected
+    /// # Examples
+    /// "Nf3" on the start position -> g1f3
+    /// "e8=Q+" -> the queen promotion on e8
+    /// "0-0-0" -> queen side castle
+    pub(crate) fn parse_san(&mut self, san: &str) -> Option<Move> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let moves = self.generate_all_legal_moves_to_vec(self.game_state.side_to_move);
+
+        let castling_side = match san {
+            "O-O" | "0-0" => Some(CastlingSide::KingSide),
+            "O-O-O" | "0-0-0" => Some(CastlingSide::QueenSide),
+            _ => None,
+        };
+
+        if let Some(castling_side) = castling_side {
+            return moves
+                .into_iter()
+                .find(|mv| matches!(mv, Move::Castle { side, .. } if *side == castling_side));
+        }
+
+        let mut chars: Vec<char> = san.chars().collect();
+
+        let piece = match chars.first() {
+            Some(&ch) if ch.is_ascii_uppercase() => {
+                chars.remove(0);
+                parse_piece_char(ch)?
+            }
+            _ => Piece::Pawn,
+        };
+
+        let promo = match chars.last() {
+            Some(&ch) if ch.is_ascii_uppercase() => {
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+                Some(parse_piece_char(ch)?)
+            }
+            _ => None,
+        };
+
+        if chars.len() < 2 {
+            return None;
+        }
+
+        let to_str: String = chars.split_off(chars.len() - 2).into_iter().collect();
+        let to = Square::try_from(to_str.as_str()).ok()?;
+
+        let mut from_file = None;
+        let mut from_rank = None;
+
+        for ch in chars {
+            match ch {
+                'a'..='h' if from_file.is_none() => from_file = Some(ch as u8 - b'a'),
+                '1'..='8' if from_rank.is_none() => from_rank = Some(ch as u8 - b'1'),
+                'x' | '-' | ':' => {}
+                _ => return None,
+            }
+        }
+
+        let mut candidates = moves.into_iter().filter(|mv| match *mv {
+            Move::Normal {
+                from,
+                to: mv_to,
+                piece: mv_piece,
+                promo: mv_promo,
+                ..
+            } => {
+                mv_piece == piece
+                    && mv_to == to
+                    && mv_promo == promo
+                    && from_file.is_none_or(|file| from.file().index() == file)
+                    && from_rank.is_none_or(|rank| from.rank().index() == rank)
+            }
+            Move::Castle { .. } => false,
+        });
+
+        let mv = candidates.next()?;
+
+        if candidates.next().is_some() {
+            return None;
+        }
+
+        Some(mv)
+    }
+
+    /// Returns the file, the rank or the whole square of the move's origin, whichever
+    /// is the shortest to tell it apart from the same piece moving to the same square
+    fn get_san_disambiguation(&mut self, mv: Move) -> String {
+        let (from, to) = mv.get_from_to();
+        let Move::Normal { piece, .. } = mv else {
+            return String::new();
+        };
+
+        let others: Vec<Square> = self
+            .generate_all_legal_moves_to_vec(self.game_state.side_to_move)
+            .into_iter()
+            .filter_map(|other| match other {
+                Move::Normal {
+                    from: other_from,
+                    to: other_to,
+                    piece: other_piece,
+                    ..
+                } if other_piece == piece && other_to == to && other_from != from => {
+                    Some(other_from)
+                }
+                _ => None,
+            })
+            .collect();
+
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|sq| sq.file() != from.file()) {
+            file_char(from).to_string()
+        } else if others.iter().all(|sq| sq.rank() != from.rank()) {
+            ((b'1' + from.rank().index()) as char).to_string()
+        } else {
+            from.to_string()
+        }
+    }
+}
+
+fn file_char(square: Square) -> char {
+    (b'a' + square.file().index()) as char
+}
+
+/// Pawns have no letter in SAN, so "P" is rejected
+fn parse_piece_char(ch: char) -> Option<Piece> {
+    match Piece::from_char(ch)? {
+        (Side::White, piece) if piece != Piece::Pawn => Some(piece),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen_parser;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_san() {
+        let mut board = Board::get_start_position();
+
+        assert_eq!(
+            board.parse_san("e4").map(|mv| mv.to_string()),
+            Some("e2e4".to_string())
+        );
+        assert_eq!(
+            board.parse_san("Nf3").map(|mv| mv.to_string()),
+            Some("g1f3".to_string())
+        );
+        assert_eq!(board.parse_san("e5"), None);
+        assert_eq!(board.parse_san("Nd2"), None);
+        assert_eq!(board.parse_san("Qx"), None);
+
+        // Two knights and two rooks can reach the same squares
+        let mut board =
+            fen_parser::parse_fen_string("r3k2r/8/8/8/8/2N3N1/8/R3K2R w KQkq - 0 1").unwrap();
+
+        assert_eq!(board.parse_san("Ne4"), None);
+        assert_eq!(
+            board.parse_san("Nge4").map(|mv| mv.to_string()),
+            Some("g3e4".to_string())
+        );
+        assert_eq!(
+            board.parse_san("Rd1").map(|mv| mv.to_string()),
+            Some("a1d1".to_string())
+        );
+        assert_eq!(
+            board.parse_san("O-O-O+"),
+            Some(
+                board
+                    .castling_config
+                    .get_castling_move(Side::White, CastlingSide::QueenSide)
+            )
+        );
+
+        // Promotions with and without the "=" sign
+        let mut board = fen_parser::parse_fen_string("3r3k/4P3/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(
+            board.parse_san("exd8=N").map(|mv| mv.to_string()),
+            Some("e7d8n".to_string())
+        );
+        assert_eq!(
+            board.parse_san("e8Q+").map(|mv| mv.to_string()),
+            Some("e7e8q".to_string())
+        );
+        assert_eq!(board.parse_san("e8"), None);
+    }
+
+    #[test]
+    fn test_san_round_trip() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+
+        for fen in fens {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+
+            for mv in board.generate_all_legal_moves_to_vec(board.game_state.side_to_move) {
+                let san = board.move_to_san(mv);
+                assert_eq!(board.parse_san(&san), Some(mv), "{fen}: {san}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_move_to_san() {
+        let mut board =
+            fen_parser::parse_fen_string("r3k2r/8/8/8/8/2N3N1/8/R3K2R w KQkq - 0 1").unwrap();
+
+        let san_of = |board: &mut Board, uci: &str| {
+            let mv = Move::parse(uci, board).unwrap();
+            board.move_to_san(mv)
+        };
+
+        assert_eq!(san_of(&mut board, "g3e4"), "Nge4");
+        assert_eq!(san_of(&mut board, "h1h8"), "Rxh8+");
+        assert_eq!(san_of(&mut board, "e1c1"), "O-O-O");
+
+        let mut board = fen_parser::parse_fen_string("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(san_of(&mut board, "a1a8"), "Ra8#");
+    }
+}
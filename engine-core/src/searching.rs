@@ -1,20 +1,49 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicBool, AtomicUsize, Ordering},
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::Instant,
 };
 
 use crate::{
-    board::Board, chess_consts, enums::Move, evaluation, move_generator::MoveBuffer, move_ordering,
+    board::Board,
+    chess_consts,
+    enums::Move,
+    evaluation,
+    move_generator::MoveBuffer,
+    move_ordering::MoveOrderingState,
+    move_picker::MovePicker,
+    options::EngineOptions,
+    transposition_table::{self, TranspositionTable},
 };
 
-const INFINITY: i32 = 1_000_000_00;
+const INFINITY: i32 = 100_000_000;
 const ONLY_CAPTURES_DEPTH: u32 = 2;
+/// Internal iterative deepening: the nodes with at least this depth left first search
+/// IID_REDUCTION plies shallower to find the move to try first
+pub(crate) const IID_MIN_DEPTH: u32 = 5;
+pub(crate) const IID_REDUCTION: u32 = 2;
+/// Number of nodes between two polls of the stop conditions
+const STOP_CHECK_INTERVAL: usize = 2048;
 
-pub(crate) static NODES_COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// Called by the root before each move is searched, with the depth, the move and its
+/// number in the order of the search starting at 1
+pub(crate) type OnRootMove<'a> = &'a dyn Fn(u32, Move, usize);
+
+/// Deepest iteration of search_iterative, the quiescence search goes on from there
+pub(crate) const MAX_SEARCH_DEPTH: u32 = 64;
 
 #[derive(Clone)]
 pub struct StopToken(Arc<AtomicBool>);
 
+impl Default for StopToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StopToken {
     pub fn new() -> Self {
         Self(Arc::new(AtomicBool::new(false)))
@@ -33,97 +62,846 @@ impl StopToken {
     }
 }
 
+/// Limits of a search besides its depth, the search stops once any of them is reached
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SearchLimits {
+    /// Compared against the nodes of the whole search, every depth and line included
+    pub(crate) nodes: Option<usize>,
+    pub(crate) deadline: Deadline,
+}
+
+impl SearchLimits {
+    pub(crate) fn is_reached(&self, nodes: usize) -> bool {
+        self.nodes.is_some_and(|limit| nodes >= limit) || self.deadline.is_passed()
+    }
+}
+
+/// The time a search has to end by, it can be set while the search runs. A ponder
+/// search starts without one and gets it at ponderhit
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Deadline(Arc<Mutex<Option<Instant>>>);
+
+impl Deadline {
+    pub(crate) fn new(deadline: Option<Instant>) -> Self {
+        Self(Arc::new(Mutex::new(deadline)))
+    }
+
+    pub(crate) fn set(&self, deadline: Instant) {
+        *self.0.lock().unwrap() = Some(deadline);
+    }
+
+    pub(crate) fn is_passed(&self) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// The knobs of the search heuristics, set through the tuning options so that they can
+/// be tuned without recompiling. The defaults are the constants the search used before,
+/// none of them was tuned yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchParams {
+    pub iid_min_depth: u32,
+    pub iid_reduction: u32,
+    /// Margin of the delta pruning in the quiescence search
+    pub delta_pruning_margin: i32,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self {
+            iid_min_depth: IID_MIN_DEPTH,
+            iid_reduction: IID_REDUCTION,
+            delta_pruning_margin: evaluation::DELTA_PRUNING_MARGIN,
+        }
+    }
+}
+
+/// State shared by all nodes of a single search
+pub(crate) struct SearchContext<'a> {
+    pub(crate) stop_token: &'a StopToken,
+    pub(crate) ordering: &'a mut MoveOrderingState,
+    pub(crate) pv: PvTable,
+    pub(crate) limits: SearchLimits,
+    /// Nodes searched by the whole search, the root searches of all its lines and
+    /// iterations share it so that the node limit covers them all
+    pub(crate) nodes_searched: &'a AtomicUsize,
+    /// Set once a stop condition is hit, the search then unwinds without using
+    /// the scores of the interrupted nodes
+    pub(crate) stopped: bool,
+    /// Nodes entered by this search, used to space out the stop polls
+    nodes: usize,
+    /// Centipawns the side to move at the root gives up to avoid a draw
+    pub(crate) contempt: i32,
+    /// The deepest ply reached, quiescence search included
+    pub(crate) seldepth: u32,
+    pub(crate) params: SearchParams,
+    /// Shared with the other threads searching the same position
+    pub(crate) tt: &'a TranspositionTable,
+}
+
+impl<'a> SearchContext<'a> {
+    pub(crate) fn new(
+        stop_token: &'a StopToken,
+        ordering: &'a mut MoveOrderingState,
+        nodes_searched: &'a AtomicUsize,
+    ) -> Self {
+        Self {
+            stop_token,
+            ordering,
+            pv: PvTable::new(),
+            limits: SearchLimits::default(),
+            nodes_searched,
+            stopped: false,
+            nodes: 0,
+            contempt: 0,
+            seldepth: 0,
+            params: SearchParams::default(),
+            tt: &transposition_table::NO_TABLE,
+        }
+    }
+
+    /// Polls the stop token and the limits, once stopped the search stays stopped
+    pub(crate) fn should_stop(&mut self) -> bool {
+        if !self.stopped {
+            self.stopped = self.stop_token.is_stopped()
+                || self
+                    .limits
+                    .is_reached(self.nodes_searched.load(Ordering::Relaxed));
+        }
+
+        self.stopped
+    }
+
+    pub(crate) fn count_node(&self) {
+        self.nodes_searched.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Called on entering every node, the stop conditions are only polled every
+    /// STOP_CHECK_INTERVAL nodes to keep it cheap
+    pub(crate) fn check_stop(&mut self) -> bool {
+        self.nodes += 1;
+
+        if self.nodes.is_multiple_of(STOP_CHECK_INTERVAL) {
+            self.should_stop()
+        } else {
+            self.stopped
+        }
+    }
+
+    /// Score of a draw for the side to move at the ply, the root side sees it as a
+    /// loss of the contempt and its opponent as a gain
+    pub(crate) fn draw_score(&self, ply: u32) -> i32 {
+        if ply.is_multiple_of(2) {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    pub(crate) fn update_seldepth(&mut self, ply: u32) {
+        self.seldepth = self.seldepth.max(ply);
+    }
+}
+
+/// Triangular principal variation table, the line starting at ply N is stored
+/// in the row N from the column N on
+pub(crate) struct PvTable {
+    moves: Vec<[Option<Move>; chess_consts::MAX_PLY]>,
+    lengths: [usize; chess_consts::MAX_PLY],
+}
+
+impl PvTable {
+    pub(crate) fn new() -> Self {
+        Self {
+            moves: vec![[None; chess_consts::MAX_PLY]; chess_consts::MAX_PLY],
+            lengths: [0; chess_consts::MAX_PLY],
+        }
+    }
+
+    /// Starts an empty line at the ply
+    pub(crate) fn clear_ply(&mut self, ply: u32) {
+        let ply = ply as usize;
+
+        if ply < chess_consts::MAX_PLY {
+            self.lengths[ply] = ply;
+        }
+    }
+
+    /// Sets the line at the ply to the move followed by the line of the next ply
+    pub(crate) fn update(&mut self, ply: u32, mv: Move) {
+        let ply = ply as usize;
+
+        if ply + 1 >= chess_consts::MAX_PLY {
+            return;
+        }
+
+        let child_len = self.lengths[ply + 1].max(ply + 1);
+
+        let (head, tail) = self.moves.split_at_mut(ply + 1);
+        head[ply][ply] = Some(mv);
+        head[ply][ply + 1..child_len].copy_from_slice(&tail[0][ply + 1..child_len]);
+
+        self.lengths[ply] = child_len;
+    }
+
+    /// Returns the principal variation found from the root
+    pub(crate) fn get_root_line(&self) -> Vec<Move> {
+        self.moves[0][..self.lengths[0]]
+            .iter()
+            .map_while(|&mv| mv)
+            .collect()
+    }
+}
+
 pub(crate) fn negamax_ab(
     board: &mut Board,
     depth: u32,
     alpha: i32,
     beta: i32,
     ply: u32,
-    stop_token: &StopToken,
+    ctx: &mut SearchContext,
     bufs: &mut [MoveBuffer],
 ) -> i32 {
-    if board.game_state.half_move_clock >= 100 {
-        NODES_COUNTER.fetch_add(1, Ordering::Relaxed);
+    search_node(board, depth, alpha, beta, ply, ctx, bufs).0
+}
 
-        return 0;
+/// negamax_ab returning the best move of the node too, None at the leaves and when no
+/// move was searched
+fn search_node(
+    board: &mut Board,
+    depth: u32,
+    alpha: i32,
+    beta: i32,
+    ply: u32,
+    ctx: &mut SearchContext,
+    bufs: &mut [MoveBuffer],
+) -> (i32, Option<Move>) {
+    ctx.pv.clear_ply(ply);
+    ctx.update_seldepth(ply);
+
+    if ctx.check_stop() {
+        return (0, None);
+    }
+
+    if board.is_repetition(ply) {
+        ctx.count_node();
+
+        return (ctx.draw_score(ply), None);
     }
 
     let side_to_move = board.game_state.side_to_move;
 
-    let (cur, rest) = bufs.split_first_mut().unwrap();
-    cur.clear();
-    board.generate_all_legal_moves(side_to_move, cur);
+    // Mates take precedence over the horizon and over the fifty-move draw, these
+    // leaves need to know whether a legal move exists before scoring the position
+    if depth == 0 || board.game_state.half_move_clock >= chess_consts::MAX_HALF_MOVES_COUNT {
+        let cur = &mut bufs[0];
+        cur.clear();
+        board.generate_all_legal_moves(side_to_move, cur);
 
-    if cur.len() == 0 {
-        NODES_COUNTER.fetch_add(1, Ordering::Relaxed);
+        if cur.is_empty() {
+            ctx.count_node();
 
-        if board.is_in_check(side_to_move) {
-            return -evaluation::MATE_EVALUATION + ply as i32;
-        } else {
-            return 0;
+            return (no_moves_score(board, ply, ctx), None);
+        }
+
+        if depth == 0 {
+            let score = evaluation::quiescence_search(board, alpha, beta, bufs, ply, 0, ctx);
+            return (score, None);
         }
+
+        ctx.count_node();
+
+        return (ctx.draw_score(ply), None);
     }
 
-    if depth == 0 {
-        return evaluation::quiescence_search(board, alpha, beta, bufs, ply);
+    ctx.count_node();
+
+    let key = board.game_state.hash;
+    let tt_entry = ctx.tt.probe(key, ply);
+
+    if let Some(entry) = tt_entry
+        && entry.depth >= depth
+    {
+        let cutoff = match entry.bound {
+            ScoreBound::Exact => true,
+            ScoreBound::Lower => entry.score >= beta,
+            ScoreBound::Upper => entry.score <= alpha,
+        };
+
+        if cutoff {
+            // The line of the node is not stored, its move is all there is of it
+            if let Some(mv) = entry.mv
+                && board.make_move_checked(mv).is_ok()
+            {
+                board.unmake_move();
+                ctx.pv.clear_ply(ply + 1);
+                ctx.pv.update(ply, mv);
+            }
+
+            return (entry.score, entry.mv);
+        }
     }
 
-    NODES_COUNTER.fetch_add(1, Ordering::Relaxed);
+    // The move that led here, its countermove gets an ordering bonus
+    let prev_move = board.history.last().and_then(|entry| entry.mv);
 
-    let only_captures = if depth <= ONLY_CAPTURES_DEPTH as u32 {
-        true
-    } else {
-        false
-    };
-    move_ordering::sort_moves(cur, ply, only_captures);
+    let only_captures = depth <= ONLY_CAPTURES_DEPTH;
+
+    // Without a move of the table, the best move of a shallower search of the node is
+    // the next best guess. Its line is not the one of this node
+    let mut first_move = tt_entry.and_then(|entry| entry.mv);
+    let iid_reduction = ctx.params.iid_reduction;
+    if first_move.is_none() && depth >= ctx.params.iid_min_depth && depth > iid_reduction {
+        first_move = search_node(board, depth - iid_reduction, alpha, beta, ply, ctx, bufs).1;
+
+        if ctx.stopped {
+            return (0, None);
+        }
+
+        ctx.pv.clear_ply(ply);
+    }
+
+    let (cur, rest) = bufs.split_first_mut().unwrap();
+    let mut picker = MovePicker::new(cur, first_move, ctx.ordering, ply, prev_move, only_captures);
 
     let mut best = -INFINITY;
+    let mut best_move = None;
 
-    for mv in cur.iter().copied() {
+    while let Some(mv) = picker.next(board, ctx.ordering) {
         let cur_alpha = best.max(alpha);
 
-        if stop_token.is_stopped() {
-            if best == -INFINITY {
-                return alpha;
+        board.make_move(mv);
+        let score = -negamax_ab(board, depth - 1, -beta, -cur_alpha, ply + 1, ctx, rest);
+        board.unmake_move();
+
+        if ctx.stopped {
+            return (0, None);
+        }
+
+        if score > best {
+            best = score;
+            best_move = Some(mv);
+        }
+
+        if score > cur_alpha && score < beta {
+            ctx.pv.update(ply, mv);
+        }
+
+        if score >= beta {
+            if !mv.is_capture() && !mv.is_promo() {
+                ctx.ordering.update_killers(mv, ply);
+                ctx.ordering
+                    .update_history(mv, board.game_state.side_to_move, depth);
+
+                if let Some(prev_move) = prev_move {
+                    ctx.ordering.update_counter_move(prev_move, mv);
+                }
             }
-            {
-                return best;
+
+            break;
+        }
+    }
+
+    // No move was handed out
+    if best == -INFINITY {
+        return (no_moves_score(board, ply, ctx), None);
+    }
+
+    // A node where no move beat alpha doesn't know its best move
+    let (bound, tt_move) = if best >= beta {
+        (ScoreBound::Lower, best_move)
+    } else if best > alpha {
+        (ScoreBound::Exact, best_move)
+    } else {
+        (ScoreBound::Upper, None)
+    };
+    ctx.tt.store(key, ply, depth, best, bound, tt_move);
+
+    (best, best_move)
+}
+
+/// Mate or stalemate score of a position without legal moves
+fn no_moves_score(board: &Board, ply: u32, ctx: &SearchContext) -> i32 {
+    if board.is_in_check(board.game_state.side_to_move) {
+        evaluation::mated_in(ply)
+    } else {
+        ctx.draw_score(ply)
+    }
+}
+
+/// Tells whether the reported score is exact or only a bound of the real score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreBound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SearchResult {
+    pub(crate) best_move: Move,
+    pub(crate) score: i32,
+    pub(crate) bound: ScoreBound,
+    pub(crate) depth: u32,
+    /// The deepest ply reached by the search
+    pub(crate) seldepth: u32,
+    /// Principal variation starting with the best move
+    pub(crate) pv: Vec<Move>,
+    /// The root moves in the order they were searched, the moves that did not beat the
+    /// best one so far only got an upper bound
+    pub(crate) root_moves: Vec<RootMoveScore>,
+    /// Nodes searched up to this result, the earlier searches of the same search included
+    pub(crate) nodes: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RootMoveScore {
+    pub(crate) mv: Move,
+    pub(crate) score: i32,
+    pub(crate) bound: ScoreBound,
+}
+
+pub(crate) fn search_bestmove(
+    board: &mut Board,
+    depth: u32,
+    stop: &StopToken,
+    ordering: &mut MoveOrderingState,
+    tt: &TranspositionTable,
+) -> Option<SearchResult> {
+    search_root(
+        board,
+        depth,
+        -INFINITY,
+        INFINITY,
+        stop,
+        ordering,
+        tt,
+        &SearchLimits::default(),
+        &AtomicUsize::new(0),
+        0,
+        SearchParams::default(),
+        &[],
+        &|_, _, _| {},
+    )
+}
+
+/// Iterative deepening: searches the lines of multipv one ply deeper at a time
+/// up to max_depth, until the stop or the limits end it. The lines of the last
+/// completed iteration are returned, the lines of the first iteration count even when
+/// it was cut short. on_iteration gets the lines every time they are replaced.
+/// With more than one of options.threads, the helpers of lazy SMP search alongside,
+/// see search_helper. The lines reported are those of the calling thread
+/// # Examples
+/// go depth 8 -> iterations 1 to 8
+/// go movetime 1000 -> as many iterations as fit in the time, the last one is dropped
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn search_iterative(
+    board: &mut Board,
+    max_depth: u32,
+    stop: &StopToken,
+    ordering: &mut MoveOrderingState,
+    tt: &TranspositionTable,
+    options: &EngineOptions,
+    limits: SearchLimits,
+    search_moves: &[Move],
+    mut on_iteration: impl FnMut(&[SearchResult]),
+    on_root_move: impl Fn(u32, Move, usize),
+) -> Vec<SearchResult> {
+    let nodes = AtomicUsize::new(0);
+    // The helpers go on until the calling thread is done
+    let helpers_stop = StopToken::new();
+
+    thread::scope(|scope| {
+        for helper in 1..options.threads {
+            let mut board = board.clone();
+            let mut ordering = ordering.clone();
+            let (limits, nodes, helpers_stop) = (&limits, &nodes, &helpers_stop);
+
+            scope.spawn(move || {
+                search_helper(
+                    &mut board,
+                    helper,
+                    max_depth,
+                    helpers_stop,
+                    &mut ordering,
+                    tt,
+                    options,
+                    limits,
+                    nodes,
+                    search_moves,
+                )
+            });
+        }
+
+        let mut results = Vec::new();
+
+        for depth in 1..=max_depth.clamp(1, MAX_SEARCH_DEPTH) {
+            let iteration = multipv(
+                board,
+                depth,
+                stop,
+                ordering,
+                tt,
+                options,
+                &limits,
+                &nodes,
+                search_moves,
+                &on_root_move,
+            );
+            let interrupted = stop.is_stopped() || limits.is_reached(nodes.load(Ordering::Relaxed));
+
+            if !interrupted || results.is_empty() {
+                results = iteration;
+                on_iteration(&results);
+            }
+
+            if interrupted || results.is_empty() {
+                break;
             }
         }
 
-        board.make_move(mv);
-        let score = -negamax_ab(
+        helpers_stop.request_stop();
+
+        results
+    })
+}
+
+/// A helper thread of lazy SMP: deepens the best line on its own copy of the board and
+/// the move ordering until the stop, the limits or max_depth. Its results only reach
+/// the other threads through the transposition table and the node counter it shares
+/// with them. Every other helper starts one ply deeper, so that the threads don't
+/// search the same depth at the same time
+/// # Examples
+/// Threads 3 -> the main thread and the helper 2 start at depth 1, the helper 1 at depth 2
+#[allow(clippy::too_many_arguments)]
+fn search_helper(
+    board: &mut Board,
+    helper: usize,
+    max_depth: u32,
+    stop: &StopToken,
+    ordering: &mut MoveOrderingState,
+    tt: &TranspositionTable,
+    options: &EngineOptions,
+    limits: &SearchLimits,
+    nodes: &AtomicUsize,
+    search_moves: &[Move],
+) {
+    let first_depth = 1 + (helper % 2) as u32;
+
+    for depth in first_depth..=max_depth.clamp(1, MAX_SEARCH_DEPTH) {
+        search_root_with_options(
             board,
-            depth - 1,
-            -beta,
-            -cur_alpha,
-            ply + 1,
-            stop_token,
-            rest,
+            depth,
+            stop,
+            ordering,
+            tt,
+            options,
+            limits,
+            nodes,
+            search_moves,
+            &|_, _, _| {},
         );
+
+        if stop.is_stopped() || limits.is_reached(nodes.load(Ordering::Relaxed)) {
+            break;
+        }
+    }
+}
+
+/// Finds the options.multi_pv best root moves with their lines, best first. The root
+/// is searched again for every line without the moves of the lines found before, the
+/// first line is the search_root_with_options result. The limits cover all the searches,
+/// a line cut short by them or by a stop is left out
+/// # Examples
+/// MultiPV 3 on the start position -> e.g. the lines of e2e4, d2d4 and g1f3
+#[allow(clippy::too_many_arguments)]
+fn multipv(
+    board: &mut Board,
+    depth: u32,
+    stop: &StopToken,
+    ordering: &mut MoveOrderingState,
+    tt: &TranspositionTable,
+    options: &EngineOptions,
+    limits: &SearchLimits,
+    nodes: &AtomicUsize,
+    search_moves: &[Move],
+    on_root_move: OnRootMove,
+) -> Vec<SearchResult> {
+    let Some(first) = search_root_with_options(
+        board,
+        depth,
+        stop,
+        ordering,
+        tt,
+        options,
+        limits,
+        nodes,
+        search_moves,
+        on_root_move,
+    ) else {
+        return Vec::new();
+    };
+
+    if options.multi_pv <= 1 {
+        return vec![first];
+    }
+
+    let legal_moves = board.generate_all_legal_moves_to_vec(board.game_state.side_to_move);
+    let restricted: Vec<Move> = search_moves
+        .iter()
+        .copied()
+        .filter(|mv| legal_moves.contains(mv))
+        .collect();
+    let mut candidates = if restricted.is_empty() {
+        legal_moves
+    } else {
+        restricted
+    };
+
+    // The scores of the first search order the later ones, they skip the usual sorting
+    candidates.sort_by_key(|&mv| {
+        std::cmp::Reverse(
+            first
+                .root_moves
+                .iter()
+                .find(|root| root.mv == mv)
+                .map_or(-INFINITY, |root| root.score),
+        )
+    });
+
+    let mut results = vec![first];
+
+    while results.len() < options.multi_pv {
+        candidates.retain(|&mv| results.iter().all(|result| result.best_move != mv));
+
+        let interrupted = stop.is_stopped() || limits.is_reached(nodes.load(Ordering::Relaxed));
+
+        if candidates.is_empty() || interrupted {
+            break;
+        }
+
+        let result = search_root_with_options(
+            board,
+            depth,
+            stop,
+            ordering,
+            tt,
+            options,
+            limits,
+            nodes,
+            &candidates,
+            on_root_move,
+        );
+        let interrupted = stop.is_stopped() || limits.is_reached(nodes.load(Ordering::Relaxed));
+
+        match result {
+            Some(result) if !interrupted => results.push(result),
+            _ => break,
+        }
+    }
+
+    results
+}
+
+/// search_root with the contempt and the search parameters of the options. A non-empty
+/// search_moves restricts the root to its legal moves, searched in the given order
+#[allow(clippy::too_many_arguments)]
+fn search_root_with_options(
+    board: &mut Board,
+    depth: u32,
+    stop: &StopToken,
+    ordering: &mut MoveOrderingState,
+    tt: &TranspositionTable,
+    options: &EngineOptions,
+    limits: &SearchLimits,
+    nodes: &AtomicUsize,
+    search_moves: &[Move],
+    on_root_move: OnRootMove,
+) -> Option<SearchResult> {
+    search_root(
+        board,
+        depth,
+        -INFINITY,
+        INFINITY,
+        stop,
+        ordering,
+        tt,
+        limits,
+        nodes,
+        options.contempt,
+        options.search_params,
+        search_moves,
+        on_root_move,
+    )
+}
+
+/// Looks for a forced mate in at most the given number of moves, deepening one move at
+/// a time so that the shortest mate is found. Returns None if there is no such mate
+/// or the search was stopped before finding one.
+/// search_moves restricts the root to its moves, all of them legal
+pub(crate) fn search_mate(
+    board: &mut Board,
+    moves: u32,
+    stop: &StopToken,
+    ordering: &mut MoveOrderingState,
+    limits: SearchLimits,
+    search_moves: &[Move],
+) -> Option<SearchResult> {
+    ordering.clear_killers();
+    ordering.clear_counter_moves();
+
+    let nodes = AtomicUsize::new(0);
+    let mut bufs: Vec<MoveBuffer> = (0..chess_consts::MAX_PLY)
+        .map(|_| Vec::with_capacity(chess_consts::MOVES_BUF_SIZE))
+        .collect();
+    let mut ctx = SearchContext::new(stop, ordering, &nodes);
+    ctx.limits = limits;
+
+    for mate_moves in 1..=moves {
+        let depth = 2 * mate_moves - 1;
+        if depth as usize >= chess_consts::MAX_PLY {
+            break;
+        }
+
+        let score = mate_negamax(
+            board,
+            depth,
+            -INFINITY,
+            INFINITY,
+            0,
+            &mut ctx,
+            &mut bufs,
+            search_moves,
+        );
+
+        if ctx.stopped {
+            return None;
+        }
+
+        if score > 0 {
+            let pv = ctx.pv.get_root_line();
+
+            return Some(SearchResult {
+                best_move: pv[0],
+                score,
+                bound: ScoreBound::Exact,
+                depth,
+                seldepth: ctx.seldepth,
+                pv,
+                root_moves: Vec::new(),
+                nodes: nodes.load(Ordering::Relaxed),
+            });
+        }
+    }
+
+    None
+}
+
+/// Negamax that only tells mates apart, positions without a mate within the depth
+/// score 0. The side to move on the last ply can only mate with a check, so the other
+/// moves are skipped there. search_moves only applies to the root
+#[allow(clippy::too_many_arguments)]
+fn mate_negamax(
+    board: &mut Board,
+    depth: u32,
+    alpha: i32,
+    beta: i32,
+    ply: u32,
+    ctx: &mut SearchContext,
+    bufs: &mut [MoveBuffer],
+    search_moves: &[Move],
+) -> i32 {
+    ctx.count_node();
+    ctx.pv.clear_ply(ply);
+    ctx.update_seldepth(ply);
+
+    if ctx.check_stop() {
+        return 0;
+    }
+
+    let side_to_move = board.game_state.side_to_move;
+
+    let (cur, rest) = bufs.split_first_mut().unwrap();
+    board.generate_all_legal_moves(side_to_move, cur);
+
+    if cur.is_empty() {
+        return if board.is_in_check(side_to_move) {
+            evaluation::mated_in(ply)
+        } else {
+            0
+        };
+    }
+
+    if depth == 0 {
+        return 0;
+    }
+
+    let prev_move = board.history.last().and_then(|entry| entry.mv);
+    ctx.ordering.sort_moves(board, cur, ply, prev_move, false);
+    if !search_moves.is_empty() {
+        cur.retain(|mv| search_moves.contains(mv));
+    }
+
+    let mut alpha = alpha;
+    let mut best = -INFINITY;
+
+    for mv in cur.iter().copied() {
+        if depth == 1 && !board.gives_check(mv) {
+            continue;
+        }
+
+        board.make_move(mv);
+
+        let score = -mate_negamax(board, depth - 1, -beta, -alpha, ply + 1, ctx, rest, &[]);
         board.unmake_move();
 
+        if ctx.stopped {
+            return 0;
+        }
+
         if score > best {
             best = score;
         }
 
-        if score >= beta {
-            if !mv.is_capture() && !mv.is_promo() {
-                move_ordering::update_killers(mv, ply);
-                move_ordering::update_history(mv, depth);
-            }
+        if score > alpha {
+            alpha = score;
+            ctx.pv.update(ply, mv);
+        }
 
+        if score >= beta {
             break;
         }
     }
 
-    return best;
+    // Only quiet moves were left on the last ply, none of them mates
+    if best == -INFINITY { 0 } else { best }
 }
 
-pub(crate) fn search_bestmove(board: &mut Board, depth: u32, stop: &StopToken) -> Option<Move> {
-    NODES_COUNTER.store(0, Ordering::Relaxed);
-    move_ordering::clear_killers();
-    move_ordering::normalize_history();
+/// Searches the root within the (alpha, beta) window. A best score at or below alpha
+/// is an upper bound and one at or above beta a lower bound, the search of the root
+/// ends at the first move reaching beta
+#[allow(clippy::too_many_arguments)]
+fn search_root(
+    board: &mut Board,
+    depth: u32,
+    alpha: i32,
+    beta: i32,
+    stop: &StopToken,
+    ordering: &mut MoveOrderingState,
+    tt: &TranspositionTable,
+    limits: &SearchLimits,
+    nodes: &AtomicUsize,
+    contempt: i32,
+    params: SearchParams,
+    search_moves: &[Move],
+    on_root_move: OnRootMove,
+) -> Option<SearchResult> {
+    ordering.clear_killers();
+    ordering.clear_counter_moves();
+    ordering.normalize_history();
 
     let side = board.game_state.side_to_move;
 
@@ -133,60 +911,834 @@ pub(crate) fn search_bestmove(board: &mut Board, depth: u32, stop: &StopToken) -
     let (cur, rest) = bufs.split_first_mut().unwrap();
     board.generate_all_legal_moves(side, cur);
 
-    if cur.len() == 0 {
+    if cur.is_empty() {
         return None;
     }
 
-    let only_captures = if depth <= ONLY_CAPTURES_DEPTH {
-        true
+    // The legal moves of the list keep the order the GUI gave them in, a list without
+    // any leaves the root untouched
+    let restricted: Vec<Move> = search_moves
+        .iter()
+        .copied()
+        .filter(|mv| cur.contains(mv))
+        .collect();
+
+    if restricted.is_empty() {
+        let only_captures = depth <= ONLY_CAPTURES_DEPTH;
+        let prev_move = board.history.last().and_then(|entry| entry.mv);
+        ordering.sort_moves(board, cur, 0, prev_move, only_captures);
     } else {
-        false
-    };
-    move_ordering::sort_moves(cur, 0, only_captures);
+        cur.clear();
+        cur.extend(restricted);
+    }
 
     let mut best_mv = cur[0];
     let mut best_score = -INFINITY;
-    let mut alpha = -INFINITY;
-    let beta = INFINITY;
+    let window_alpha = alpha;
+    let mut alpha = alpha;
+    let mut bound = ScoreBound::Exact;
 
-    for mv in cur.iter().copied() {
-        if stop.is_stopped() {
+    let mut ctx = SearchContext::new(stop, ordering, nodes);
+    ctx.tt = tt;
+    ctx.limits = limits.clone();
+    ctx.contempt = contempt;
+    ctx.params = params;
+    ctx.pv.clear_ply(0);
+
+    let mut root_moves = Vec::with_capacity(cur.len());
+
+    for (i, mv) in cur.iter().copied().enumerate() {
+        if ctx.should_stop() {
+            // Not all root moves were searched, so the best score found so far is only a lower bound
+            bound = ScoreBound::Lower;
             break;
         }
 
-        NODES_COUNTER.fetch_add(1, Ordering::Relaxed);
+        ctx.count_node();
+        on_root_move(depth, mv, i + 1);
 
         board.make_move(mv);
-        let score = -negamax_ab(board, depth - 1, -beta, -alpha, 1, stop, rest);
+        let score = -negamax_ab(board, depth - 1, -beta, -alpha, 1, &mut ctx, rest);
         board.unmake_move();
 
+        // The move was cut short, its score is not used
+        if ctx.stopped {
+            bound = ScoreBound::Lower;
+            break;
+        }
+
+        root_moves.push(RootMoveScore {
+            mv,
+            score,
+            bound: if score >= beta {
+                ScoreBound::Lower
+            } else if score > alpha {
+                ScoreBound::Exact
+            } else {
+                ScoreBound::Upper
+            },
+        });
+
         if score > best_score {
             best_score = score;
             best_mv = mv;
+
+            if score > alpha {
+                ctx.pv.update(0, mv);
+            }
         }
 
         if score > alpha {
             alpha = score;
         }
+
+        if score >= beta {
+            bound = ScoreBound::Lower;
+            break;
+        }
     }
 
-    Some(best_mv)
+    // Stopped before the first move was done, the static evaluation is all there is
+    if best_score == -INFINITY {
+        best_score = evaluation::evalute_cur_side(board);
+    } else if bound == ScoreBound::Exact && best_score <= window_alpha {
+        bound = ScoreBound::Upper;
+    }
+
+    Some(SearchResult {
+        best_move: best_mv,
+        score: best_score,
+        bound,
+        depth,
+        seldepth: ctx.seldepth,
+        pv: ctx.pv.get_root_line(),
+        root_moves,
+        nodes: nodes.load(Ordering::Relaxed),
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::fen_parser;
+    use std::cell::RefCell;
+
+    use crate::{fen_parser, uci};
 
     use super::*;
 
+    /// Small enough to give every search of the tests a fresh table
+    const TEST_HASH_MB: usize = 1;
+
+    /// search_root_with_options counting its own nodes, with a fresh table
+    #[allow(clippy::too_many_arguments)]
+    fn search_bestmove_with_options(
+        board: &mut Board,
+        depth: u32,
+        stop: &StopToken,
+        ordering: &mut MoveOrderingState,
+        options: &EngineOptions,
+        limits: SearchLimits,
+        search_moves: &[Move],
+    ) -> Option<SearchResult> {
+        search_root_with_options(
+            board,
+            depth,
+            stop,
+            ordering,
+            &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+            options,
+            &limits,
+            &AtomicUsize::new(0),
+            search_moves,
+            &|_, _, _| {},
+        )
+    }
+
+    /// multipv counting its own nodes, with a fresh table
+    #[allow(clippy::too_many_arguments)]
+    fn search_multipv(
+        board: &mut Board,
+        depth: u32,
+        stop: &StopToken,
+        ordering: &mut MoveOrderingState,
+        options: &EngineOptions,
+        limits: SearchLimits,
+        search_moves: &[Move],
+    ) -> Vec<SearchResult> {
+        multipv(
+            board,
+            depth,
+            stop,
+            ordering,
+            &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+            options,
+            &limits,
+            &AtomicUsize::new(0),
+            search_moves,
+            &|_, _, _| {},
+        )
+    }
+
     #[test]
     #[ignore]
     fn test_nodes_count() {
         let mut board =
             fen_parser::parse_fen_string(chess_consts::fen_strings::KILLER_POS_FEN).unwrap();
 
-        let _ = search_bestmove(&mut board, 6, &StopToken::new());
+        let result = search_bestmove(
+            &mut board,
+            6,
+            &StopToken::new(),
+            &mut MoveOrderingState::new(),
+            &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+        )
+        .unwrap();
+
+        println!("Nodes count: {}", result.nodes);
+    }
+
+    #[test]
+    fn test_clear_search_state_between_unrelated_positions() {
+        let search = |fen: &str, ordering: &mut MoveOrderingState| {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            search_bestmove(
+                &mut board,
+                4,
+                &StopToken::new(),
+                ordering,
+                &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+            )
+            .map(|r| r.best_move)
+        };
+
+        let fresh_mv = search(
+            chess_consts::fen_strings::CMK_POS_FEN,
+            &mut MoveOrderingState::new(),
+        );
+
+        let mut ordering = MoveOrderingState::new();
+        search(chess_consts::fen_strings::KILLER_POS_FEN, &mut ordering);
+        ordering.clear();
+        let reset_mv = search(chess_consts::fen_strings::CMK_POS_FEN, &mut ordering);
+
+        assert_eq!(fresh_mv, reset_mv);
+    }
+
+    #[test]
+    fn test_transposition_table_saves_nodes() {
+        let search = |tt: &TranspositionTable| {
+            let mut board =
+                fen_parser::parse_fen_string(chess_consts::fen_strings::KILLER_POS_FEN).unwrap();
+
+            search_bestmove(
+                &mut board,
+                5,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                tt,
+            )
+            .unwrap()
+        };
+
+        let without = search(&TranspositionTable::new(0).unwrap());
+        let tt = TranspositionTable::new(TEST_HASH_MB).unwrap();
+        let with = search(&tt);
+        assert!(
+            with.nodes < without.nodes,
+            "{} {}",
+            with.nodes,
+            without.nodes
+        );
+
+        // The entries of the first search cut the second one short
+        let again = search(&tt);
+        assert!(again.nodes < with.nodes, "{} {}", again.nodes, with.nodes);
+    }
+
+    #[test]
+    fn test_lazy_smp_returns_legal_moves() {
+        let options = EngineOptions {
+            threads: 2,
+            ..EngineOptions::default()
+        };
+
+        for fen in [
+            chess_consts::fen_strings::START_POS_FEN,
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            chess_consts::fen_strings::KILLER_POS_FEN,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            let legal_moves = board.generate_all_legal_moves_to_vec(board.game_state.side_to_move);
+
+            let results = search_iterative(
+                &mut board,
+                4,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+                &options,
+                SearchLimits::default(),
+                &[],
+                |_| {},
+                |_, _, _| {},
+            );
+
+            assert_eq!(results[0].depth, 4, "{fen}");
+            assert!(legal_moves.contains(&results[0].best_move), "{fen}");
+            for &mv in &results[0].pv {
+                assert!(board.make_move_checked(mv).is_ok(), "{fen}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_pv() {
+        let mut board =
+            fen_parser::parse_fen_string(chess_consts::fen_strings::CMK_POS_FEN).unwrap();
+        let result = search_bestmove(
+            &mut board,
+            4,
+            &StopToken::new(),
+            &mut MoveOrderingState::new(),
+            &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+        )
+        .unwrap();
+
+        assert!(result.pv.len() >= 2);
+        assert_eq!(result.pv[0], result.best_move);
+
+        // Every move of the line has to be legal in the position it is played from
+        for &mv in &result.pv {
+            let side = board.game_state.side_to_move;
+            assert!(board.generate_all_legal_moves_to_vec(side).contains(&mv));
+            board.make_move(mv);
+        }
+    }
+
+    #[test]
+    fn test_search_returns_legal_moves() {
+        let fens = [
+            chess_consts::fen_strings::START_POS_FEN,
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            chess_consts::fen_strings::KILLER_POS_FEN,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+
+        for fen in fens {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            let legal_moves = board.generate_all_legal_moves_to_vec(board.game_state.side_to_move);
+
+            let result = search_bestmove_with_options(
+                &mut board,
+                3,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                &EngineOptions::default(),
+                SearchLimits::default(),
+                &[],
+            )
+            .unwrap();
+
+            assert!(legal_moves.contains(&result.best_move), "{fen}");
+        }
+    }
+
+    #[test]
+    fn test_seldepth_includes_quiescence() {
+        let mut board =
+            fen_parser::parse_fen_string(chess_consts::fen_strings::TRICKY_POS_FEN).unwrap();
+
+        let result = search_bestmove(
+            &mut board,
+            3,
+            &StopToken::new(),
+            &mut MoveOrderingState::new(),
+            &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+        )
+        .unwrap();
+
+        // The position is full of captures, quiescence goes past the nominal depth
+        assert!(result.seldepth > result.depth);
+    }
+
+    #[test]
+    fn test_root_move_scores() {
+        let mut board =
+            fen_parser::parse_fen_string(chess_consts::fen_strings::TRICKY_POS_FEN).unwrap();
+        let legal_moves_count = board
+            .generate_all_legal_moves_to_vec(board.game_state.side_to_move)
+            .len();
+
+        let result = search_bestmove(
+            &mut board,
+            2,
+            &StopToken::new(),
+            &mut MoveOrderingState::new(),
+            &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(result.root_moves.len(), legal_moves_count);
+        assert!(
+            result
+                .root_moves
+                .iter()
+                .all(|root| root.score <= result.score)
+        );
+        assert!(result.root_moves.contains(&RootMoveScore {
+            mv: result.best_move,
+            score: result.score,
+            bound: ScoreBound::Exact,
+        }));
+    }
+
+    #[test]
+    fn test_search_moves() {
+        let search = |search_moves: &[&str]| {
+            // Ra8 mates
+            let mut board =
+                fen_parser::parse_fen_string("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+            let search_moves: Vec<Move> = search_moves
+                .iter()
+                .filter_map(|mv| Move::parse(mv, &mut board))
+                .collect();
+
+            search_bestmove_with_options(
+                &mut board,
+                2,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                &EngineOptions::default(),
+                SearchLimits::default(),
+                &search_moves,
+            )
+            .unwrap()
+        };
+
+        let result = search(&["a1a7", "g1f1"]);
+        assert_eq!(
+            result
+                .root_moves
+                .iter()
+                .map(|root| root.mv.to_string())
+                .collect::<Vec<_>>(),
+            ["a1a7", "g1f1"]
+        );
+
+        assert_eq!(search(&["a1a8"]).best_move.to_string(), "a1a8");
+        assert_eq!(search(&[]).best_move.to_string(), "a1a8");
+    }
+
+    #[test]
+    fn test_iterative_deepening() {
+        let root_moves = RefCell::new(Vec::new());
+        let search = |max_depth: u32, limits: SearchLimits| {
+            let mut board =
+                fen_parser::parse_fen_string(chess_consts::fen_strings::TRICKY_POS_FEN).unwrap();
+            let mut depths = Vec::new();
+
+            let results = search_iterative(
+                &mut board,
+                max_depth,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+                &EngineOptions::default(),
+                limits,
+                &[],
+                |results: &[SearchResult]| depths.push(results[0].depth),
+                |depth, _, number| root_moves.borrow_mut().push((depth, number)),
+            );
+
+            (results, depths)
+        };
+
+        let (results, depths) = search(4, SearchLimits::default());
+        assert_eq!(depths, [1, 2, 3, 4]);
+
+        // Every iteration reports all the 48 root moves, numbered in the search order
+        let root_moves = root_moves.take();
+        assert_eq!(root_moves.len(), 4 * 48);
+        assert_eq!(root_moves[0], (1, 1));
+        assert_eq!(root_moves[48 + 47], (2, 48));
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].depth, 4);
+
+        // The iteration cut short by the node limit is dropped, a depth 10 search of this
+        // position would take minutes without it
+        let (results, depths) = search(
+            10,
+            SearchLimits {
+                nodes: Some(20_000),
+                ..SearchLimits::default()
+            },
+        );
+        assert!(!depths.is_empty() && depths.len() < 10, "{depths:?}");
+        assert_eq!(results[0].depth, *depths.last().unwrap());
+    }
+
+    #[test]
+    fn test_multipv() {
+        let search = |multi_pv: usize, search_moves: &[Move]| {
+            let mut board = Board::get_start_position();
+
+            search_multipv(
+                &mut board,
+                3,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                &EngineOptions {
+                    multi_pv,
+                    ..EngineOptions::default()
+                },
+                SearchLimits::default(),
+                search_moves,
+            )
+        };
+
+        let single = search(1, &[]);
+        let results = search(3, &[]);
+        assert_eq!(single.len(), 1);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].best_move, single[0].best_move);
+        assert_eq!(results[0].score, single[0].score);
+
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(result.pv[0], result.best_move);
+            assert!(
+                results[..i]
+                    .iter()
+                    .all(|prev| prev.best_move != result.best_move)
+            );
+            assert!(results[..i].iter().all(|prev| prev.score >= result.score));
+        }
+
+        // No more lines than the root moves
+        let mut board = Board::get_start_position();
+        let search_moves = ["a2a3", "h2h4"].map(|mv| Move::parse(mv, &mut board).unwrap());
+        let results = search(3, &search_moves);
+        assert_eq!(results.len(), 2);
+        assert!(
+            results
+                .iter()
+                .all(|result| search_moves.contains(&result.best_move))
+        );
+    }
+
+    #[test]
+    fn test_node_limited_search() {
+        const NODE_LIMIT: usize = 10_000;
+
+        let mut board =
+            fen_parser::parse_fen_string(chess_consts::fen_strings::TRICKY_POS_FEN).unwrap();
+        let legal_moves = board.generate_all_legal_moves_to_vec(board.game_state.side_to_move);
+
+        let result = search_bestmove_with_options(
+            &mut board,
+            10,
+            &StopToken::new(),
+            &mut MoveOrderingState::new(),
+            &EngineOptions::default(),
+            SearchLimits {
+                nodes: Some(NODE_LIMIT),
+                deadline: Deadline::default(),
+            },
+            &[],
+        )
+        .unwrap();
+
+        // A depth 10 search of this position would take minutes without the limit
+        assert!(legal_moves.contains(&result.best_move));
+        assert!(result.nodes >= NODE_LIMIT);
+        assert!(result.nodes < NODE_LIMIT + STOP_CHECK_INTERVAL);
+    }
+
+    #[test]
+    fn test_concurrent_searches_count_their_own_nodes() {
+        let search = || {
+            let mut board =
+                fen_parser::parse_fen_string(chess_consts::fen_strings::KILLER_POS_FEN).unwrap();
+
+            search_bestmove(
+                &mut board,
+                4,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+            )
+            .unwrap()
+            .nodes
+        };
+
+        let alone = search();
+        let handles: Vec<_> = (0..2).map(|_| std::thread::spawn(search)).collect();
 
-        println!("Nodes count: {}", NODES_COUNTER.load(Ordering::Relaxed));
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), alone);
+        }
+    }
+
+    #[test]
+    fn test_deadline_limited_search() {
+        let mut board =
+            fen_parser::parse_fen_string(chess_consts::fen_strings::TRICKY_POS_FEN).unwrap();
+        let legal_moves = board.generate_all_legal_moves_to_vec(board.game_state.side_to_move);
+
+        let start = Instant::now();
+        let result = search_bestmove_with_options(
+            &mut board,
+            10,
+            &StopToken::new(),
+            &mut MoveOrderingState::new(),
+            &EngineOptions::default(),
+            SearchLimits {
+                nodes: None,
+                deadline: Deadline::new(Some(start + std::time::Duration::from_millis(50))),
+            },
+            &[],
+        )
+        .unwrap();
+
+        assert!(start.elapsed().as_secs() < 5);
+        assert!(legal_moves.contains(&result.best_move));
+        assert_eq!(result.bound, ScoreBound::Lower);
+    }
+
+    #[test]
+    fn test_root_window_bounds() {
+        // The rook takes the queen for free, far above beta and far below alpha
+        let search = |alpha: i32, beta: i32| {
+            let mut board =
+                fen_parser::parse_fen_string("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+
+            search_root(
+                &mut board,
+                3,
+                alpha,
+                beta,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+                &SearchLimits::default(),
+                &AtomicUsize::new(0),
+                0,
+                SearchParams::default(),
+                &[],
+                &|_, _, _| {},
+            )
+            .unwrap()
+        };
+
+        let exact = search(-INFINITY, INFINITY);
+        assert_eq!(exact.best_move.to_string(), "d1d5");
+        assert_eq!(exact.bound, ScoreBound::Exact);
+
+        // The capture is searched first and cuts the root off
+        let fail_high = search(-INFINITY, 100);
+        assert_eq!(fail_high.best_move.to_string(), "d1d5");
+        assert_eq!(fail_high.bound, ScoreBound::Lower);
+        assert!(fail_high.score >= 100);
+        assert_eq!(fail_high.root_moves.len(), 1);
+        assert_eq!(fail_high.root_moves[0].bound, ScoreBound::Lower);
+
+        let fail_low = search(exact.score + 1000, INFINITY);
+        assert_eq!(fail_low.bound, ScoreBound::Upper);
+        assert!(fail_low.score <= exact.score + 1000);
+        assert!(
+            fail_low
+                .root_moves
+                .iter()
+                .all(|root| root.bound == ScoreBound::Upper)
+        );
+    }
+
+    #[test]
+    fn test_contempt_draw_score() {
+        // Every move reaches the fifty-move rule
+        let search = |contempt: i32| {
+            let mut board =
+                fen_parser::parse_fen_string("k7/8/8/8/8/8/8/2K4R w - - 99 80").unwrap();
+
+            search_bestmove_with_options(
+                &mut board,
+                2,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                &EngineOptions {
+                    contempt,
+                    ..EngineOptions::default()
+                },
+                SearchLimits::default(),
+                &[],
+            )
+            .unwrap()
+            .score
+        };
+
+        assert_eq!(search(0), 0);
+        assert_eq!(search(20), -20);
+        assert_eq!(search(-20), 20);
+    }
+
+    #[test]
+    fn test_perpetual_check() {
+        let search = |position_cmd: &str, contempt: i32| {
+            let mut board = uci::parse_uci_position_command(position_cmd, false).unwrap();
+
+            search_bestmove_with_options(
+                &mut board,
+                6,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                &EngineOptions {
+                    contempt,
+                    ..EngineOptions::default()
+                },
+                SearchLimits::default(),
+                &[],
+            )
+            .unwrap()
+        };
+
+        // White is lost unless it checks on g6 and h6 forever, the repetition inside
+        // the search is enough to see the draw
+        let lost = "position fen 5rk1/8/8/8/q7/3Q4/r7/7K w - - 0 1";
+        let result = search(lost, 0);
+        assert_eq!(result.best_move.to_string(), "d3g6");
+        assert_eq!(result.score, 0);
+        assert_eq!(search(lost, 20).score, -20);
+
+        // With the rooks white wins, even though the position occurred before and the
+        // checks could repeat it
+        let result = search(
+            "position fen 5rk1/8/8/8/8/3Q4/8/R3R2K w - - 0 1 moves d3g6 g8h8 g6d3 h8g8",
+            0,
+        );
+        assert!(evaluation::is_mate_score(result.score), "{}", result.score);
+        assert!(result.score > 0);
+    }
+
+    #[test]
+    fn test_mate_wins_over_fifty_move_rule() {
+        // Rh8# is the hundredth half-move without a capture or a pawn move
+        let mut board = fen_parser::parse_fen_string("k7/8/1K6/8/8/8/8/7R w - - 99 80").unwrap();
+
+        let result = search_bestmove(
+            &mut board,
+            2,
+            &StopToken::new(),
+            &mut MoveOrderingState::new(),
+            &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(result.best_move.to_string(), "h1h8");
+        assert_eq!(result.score, evaluation::MATE_EVALUATION - 1);
+    }
+
+    #[test]
+    fn test_shortest_mate() {
+        // 1. Nf6+ gxf6 2. Bxf7#, a depth 4 search sees longer mates too
+        let mut board = fen_parser::parse_fen_string(
+            "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1",
+        )
+        .unwrap();
+
+        let search = |board: &mut Board| {
+            search_bestmove(
+                board,
+                4,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                &TranspositionTable::new(TEST_HASH_MB).unwrap(),
+            )
+            .unwrap()
+        };
+
+        let result = search(&mut board);
+        assert_eq!(result.best_move.to_string(), "d5f6");
+        assert_eq!(result.score, evaluation::MATE_EVALUATION - 3);
+        assert_eq!(
+            uci::serialize_score_to_uci_str(result.score, result.bound),
+            "mate 2"
+        );
+
+        // The defending side sees the same mate from one move closer
+        let mv = result.best_move;
+        board.make_move(mv);
+        let result = search(&mut board);
+        assert_eq!(result.score, evaluation::mated_in(2));
+        assert_eq!(
+            uci::serialize_score_to_uci_str(result.score, result.bound),
+            "mate -1"
+        );
+    }
+
+    #[test]
+    fn test_mate_search() {
+        // 1. Nf6+ gxf6 2. Bxf7#
+        let mut board = fen_parser::parse_fen_string(
+            "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1",
+        )
+        .unwrap();
+
+        let search = |board: &mut Board, moves: u32| {
+            search_mate(
+                board,
+                moves,
+                &StopToken::new(),
+                &mut MoveOrderingState::new(),
+                SearchLimits::default(),
+                &[],
+            )
+        };
+
+        assert_eq!(search(&mut board, 1), None);
+
+        let result = search(&mut board, 3).unwrap();
+        assert_eq!(result.score, evaluation::MATE_EVALUATION - 3);
+        assert_eq!(result.best_move.to_string(), "d5f6");
+        assert_eq!(result.pv.len(), 3);
+
+        // 1... Bc5+ 2. Kxc5 Qb6+ 3. Kd5 Qd6#
+        let mut board = fen_parser::parse_fen_string(
+            "r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1",
+        )
+        .unwrap();
+
+        let result = search(&mut board, 3).unwrap();
+        assert_eq!(result.score, evaluation::MATE_EVALUATION - 5);
+        assert_eq!(result.best_move.to_string(), "f8c5");
+
+        // Without the mating move at the root there is no mate
+        let other = Move::parse("f6f2", &mut board).unwrap();
+        let restricted = search_mate(
+            &mut board,
+            3,
+            &StopToken::new(),
+            &mut MoveOrderingState::new(),
+            SearchLimits::default(),
+            &[other],
+        );
+        assert_eq!(restricted, None);
+
+        // The limits end the search too
+        let limits = SearchLimits {
+            nodes: Some(1),
+            ..SearchLimits::default()
+        };
+        let limited = search_mate(
+            &mut board,
+            3,
+            &StopToken::new(),
+            &mut MoveOrderingState::new(),
+            limits,
+            &[],
+        );
+        assert_eq!(limited, None);
     }
 }
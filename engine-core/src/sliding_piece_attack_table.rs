@@ -1,11 +1,6 @@
 use std::sync::LazyLock;
 
-use crate::{
-    chess_consts,
-    enums::{Piece, Square},
-    helpers,
-    random_generator::XorShift64Star,
-};
+use crate::{chess_consts, enums::Square, helpers};
 
 const BISHOP_RELEVANT_OCCUPANCY_MASKS: [u64; chess_consts::SQUARES_COUNT] = {
     let mut relevant_masks = [0u64; chess_consts::SQUARES_COUNT];
@@ -67,60 +62,226 @@ const ROOK_RELEVANT_BIT_COUNTS: [u8; chess_consts::SQUARES_COUNT] = {
     counts
 };
 
-static BISHOP_MAGIC_NUMBERS: LazyLock<[u64; chess_consts::SQUARES_COUNT]> = LazyLock::new(|| {
-    let mut magic_numbers = [0u64; chess_consts::SQUARES_COUNT];
+/// Magic numbers found by find_magic_number, indexed by square. They are kept as constants
+/// so the first search doesn't pay for the magic search, test_precomputed_magic_numbers
+/// checks that they are still reproduced by it
+const BISHOP_MAGIC_NUMBERS: [u64; chess_consts::SQUARES_COUNT] = [
+    0x10102002004A1420,
+    0x0408014102060800,
+    0x200840851A000000,
+    0x04020A0201600081,
+    0x8181104000202050,
+    0x8C01100210802300,
+    0x0C01080110080004,
+    0x0028208200A02020,
+    0x0100429204050204,
+    0x0100429204050204,
+    0x01820800C4048011,
+    0x004B024081010103,
+    0x0030240420040080,
+    0x404001100210004A,
+    0x4B00009419284080,
+    0x0000008200922108,
+    0x0008104002240408,
+    0x0020881042020440,
+    0x9609001001120090,
+    0x0001213404008224,
+    0x0A09002820080082,
+    0x800E001D08900400,
+    0x08C480040084D018,
+    0x0620848044040984,
+    0x0610120038667000,
+    0x0304504082020800,
+    0x0001491090040181,
+    0x0002040018010821,
+    0x80F084000A802000,
+    0x20048200010110A0,
+    0x01040060E1080208,
+    0x0201084000222800,
+    0x0201100901408820,
+    0x808084A401101000,
+    0x2200280112480200,
+    0x0000202020080080,
+    0x8009010400060020,
+    0x80040800210A0084,
+    0xC004550400020880,
+    0x0A00942242108200,
+    0x0221010860004100,
+    0x001C020202C09000,
+    0x0AC4084048219000,
+    0x1804004010408A04,
+    0x041C6000A4024080,
+    0x08400080A1000080,
+    0x4C60211202001081,
+    0xD10C80A08A020100,
+    0x0C01080110080004,
+    0x018C404E08210022,
+    0x0200820084048044,
+    0x9422000484040458,
+    0x40004020220480C0,
+    0x0001086048208601,
+    0x0044103208010700,
+    0x0408014102060800,
+    0x0028208200A02020,
+    0x0000008200922108,
+    0x2200080022111008,
+    0xC510048000411081,
+    0x00A0544004505400,
+    0x000020090208020E,
+    0x0100429204050204,
+    0x10102002004A1420,
+];
+
+const ROOK_MAGIC_NUMBERS: [u64; chess_consts::SQUARES_COUNT] = [
+    0x1080004008801020,
+    0xA200201080410200,
+    0x3100090020001043,
+    0x410004100100200A,
+    0x0100080010050002,
+    0x8180010200801400,
+    0x440001440800A210,
+    0x61000208842E4300,
+    0x0002002600844101,
+    0x0825400020085000,
+    0x8000802000801001,
+    0x0016004022000810,
+    0x0C04800802040081,
+    0x0204804400020080,
+    0x0111000200010004,
+    0x000200022100804C,
+    0x828000C000200040,
+    0x9010004040002010,
+    0x0102020014204081,
+    0x0C000A0040220010,
+    0x4900510005480100,
+    0x1004004040020100,
+    0x0010040002100148,
+    0x0102020014204081,
+    0x2500400080008020,
+    0x4020100040004020,
+    0x8442200480100080,
+    0xC848090100100020,
+    0x4228011100080500,
+    0x4410020080800400,
+    0x0180100400020148,
+    0x1250350200008044,
+    0x80800220044000D0,
+    0x0880400081002100,
+    0x8000802000801001,
+    0x0020080080801000,
+    0x1007800400800800,
+    0x4410020080800400,
+    0x0020018804002250,
+    0x0000800040800100,
+    0x2500400080008020,
+    0xA030201000404001,
+    0x0100200041010010,
+    0x0C000A0040220010,
+    0x4210080004008080,
+    0x0402040002008080,
+    0x0111000200010004,
+    0x00000084005A0001,
+    0x8920410028820A00,
+    0x0240004020100840,
+    0x0080402000110100,
+    0x0020081040220200,
+    0x0138001005000900,
+    0x0006008810441200,
+    0x0040812210084400,
+    0x0203108044211200,
+    0x0446210010800041,
+    0x0100190020804001,
+    0x001110802202400A,
+    0x00C0042010010009,
+    0x00AB000800100205,
+    0x024100040008A251,
+    0x9080183009008604,
+    0x0004022185040042,
+];
 
-    let mut sq = 0;
+static BISHOP_ATTACKS_TABLE: LazyLock<[[u64; 512]; chess_consts::SQUARES_COUNT]> =
+    LazyLock::new(|| {
+        let mut attacks_table = [[0; 512]; chess_consts::SQUARES_COUNT];
 
-    while sq < chess_consts::SQUARES_COUNT {
-        let square = unsafe { Square::from_u8_unchecked(sq as u8) };
+        for square in Square::all() {
+            let sq_index = square.index() as usize;
+            let relevant_bits_count = BISHOP_RELEVANT_BIT_COUNTS[sq_index];
+            let relevant_occupancy_mask = BISHOP_RELEVANT_OCCUPANCY_MASKS[sq_index];
 
-        let magic_number = find_magic_number(square, Piece::Bishop);
+            let occupancy_indicies = 2u32.pow(relevant_bits_count as u32);
 
-        magic_numbers[sq] = magic_number.unwrap();
+            for occupancy_index in 0..occupancy_indicies {
+                let blocker_mask = build_blocker_mask(occupancy_index, relevant_occupancy_mask);
 
-        sq += 1;
-    }
+                let shift = 64u32 - (relevant_bits_count as u32);
+                let magic_index =
+                    blocker_mask.wrapping_mul(BISHOP_MAGIC_NUMBERS[sq_index]) >> shift;
+                attacks_table[sq_index][magic_index as usize] =
+                    generate_bishop_attacks_mask(square, blocker_mask);
+            }
+        }
 
-    magic_numbers
-});
+        attacks_table
+    });
 
-static ROOK_MAGIC_NUMBERS: LazyLock<[u64; chess_consts::SQUARES_COUNT]> = LazyLock::new(|| {
-    let mut magic_numbers = [0u64; chess_consts::SQUARES_COUNT];
+static ROOK_ATTACKS_TABLE: LazyLock<Box<[[u64; 4096]; chess_consts::SQUARES_COUNT]>> =
+    LazyLock::new(|| {
+        let flat: Box<[u64]> = vec![0u64; 4096 * chess_consts::SQUARES_COUNT].into_boxed_slice();
+        let ptr = Box::into_raw(flat) as *mut [[u64; 4096]; chess_consts::SQUARES_COUNT];
+        let mut attacks_table: Box<[[u64; 4096]; chess_consts::SQUARES_COUNT]> =
+            unsafe { Box::from_raw(ptr) };
 
-    let mut sq = 0;
+        for square in Square::all() {
+            let sq_index = square.index() as usize;
+            let relevant_bits_count = ROOK_RELEVANT_BIT_COUNTS[sq_index];
+            let relevant_occupancy_mask = ROOK_RELEVANT_OCCUPANCY_MASKS[sq_index];
 
-    while sq < chess_consts::SQUARES_COUNT {
-        let square = unsafe { Square::from_u8_unchecked(sq as u8) };
+            let occupancy_indicies = 2u32.pow(relevant_bits_count as u32);
 
-        let magic_number = find_magic_number(square, Piece::Rook);
+            for occupancy_index in 0..occupancy_indicies {
+                let blocker_mask = build_blocker_mask(occupancy_index, relevant_occupancy_mask);
 
-        magic_numbers[sq] = magic_number.unwrap();
+                let shift = 64u32 - (relevant_bits_count as u32);
+                let magic_index = blocker_mask.wrapping_mul(ROOK_MAGIC_NUMBERS[sq_index]) >> shift;
 
-        sq += 1;
+                attacks_table[sq_index][magic_index as usize] =
+                    generate_rook_attacks_mask(square, blocker_mask);
+            }
+        }
+
+        attacks_table
+    });
+
+/// Whether the CPU has the BMI2 PEXT instruction, it replaces the magic multiplication
+/// when the attack tables are indexed
+static PEXT_AVAILABLE: LazyLock<bool> = LazyLock::new(|| {
+    #[cfg(target_arch = "x86_64")]
+    {
+        std::is_x86_feature_detected!("bmi2")
     }
 
-    magic_numbers
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
 });
 
-static BISHOP_ATTACKS_TABLE: LazyLock<[[u64; 512]; chess_consts::SQUARES_COUNT]> =
+/// Same as BISHOP_ATTACKS_TABLE, but indexed by PEXT of the occupancy, that is by the
+/// index build_blocker_mask takes
+static BISHOP_PEXT_ATTACKS_TABLE: LazyLock<[[u64; 512]; chess_consts::SQUARES_COUNT]> =
     LazyLock::new(|| {
         let mut attacks_table = [[0; 512]; chess_consts::SQUARES_COUNT];
 
         for square in Square::all() {
             let sq_index = square.index() as usize;
-            let relevant_bits_count = BISHOP_RELEVANT_BIT_COUNTS[sq_index];
             let relevant_occupancy_mask = BISHOP_RELEVANT_OCCUPANCY_MASKS[sq_index];
 
-            let occupancy_indicies = 2u32.pow(relevant_bits_count as u32);
+            let occupancy_indicies = 2u32.pow(BISHOP_RELEVANT_BIT_COUNTS[sq_index] as u32);
 
             for occupancy_index in 0..occupancy_indicies {
                 let blocker_mask = build_blocker_mask(occupancy_index, relevant_occupancy_mask);
 
-                let shift = 64u32 - (relevant_bits_count as u32);
-                let magic_index =
-                    blocker_mask.wrapping_mul(BISHOP_MAGIC_NUMBERS[sq_index]) >> shift;
-                attacks_table[sq_index][magic_index as usize] =
+                attacks_table[sq_index][occupancy_index as usize] =
                     generate_bishop_attacks_mask(square, blocker_mask);
             }
         }
@@ -128,7 +289,8 @@ static BISHOP_ATTACKS_TABLE: LazyLock<[[u64; 512]; chess_consts::SQUARES_COUNT]>
         attacks_table
     });
 
-static ROOK_ATTACKS_TABLE: LazyLock<Box<[[u64; 4096]; chess_consts::SQUARES_COUNT]>> =
+/// Same as ROOK_ATTACKS_TABLE, but indexed by PEXT of the occupancy
+static ROOK_PEXT_ATTACKS_TABLE: LazyLock<Box<[[u64; 4096]; chess_consts::SQUARES_COUNT]>> =
     LazyLock::new(|| {
         let flat: Box<[u64]> = vec![0u64; 4096 * chess_consts::SQUARES_COUNT].into_boxed_slice();
         let ptr = Box::into_raw(flat) as *mut [[u64; 4096]; chess_consts::SQUARES_COUNT];
@@ -137,18 +299,14 @@ static ROOK_ATTACKS_TABLE: LazyLock<Box<[[u64; 4096]; chess_consts::SQUARES_COUN
 
         for square in Square::all() {
             let sq_index = square.index() as usize;
-            let relevant_bits_count = ROOK_RELEVANT_BIT_COUNTS[sq_index];
             let relevant_occupancy_mask = ROOK_RELEVANT_OCCUPANCY_MASKS[sq_index];
 
-            let occupancy_indicies = 2u32.pow(relevant_bits_count as u32);
+            let occupancy_indicies = 2u32.pow(ROOK_RELEVANT_BIT_COUNTS[sq_index] as u32);
 
             for occupancy_index in 0..occupancy_indicies {
                 let blocker_mask = build_blocker_mask(occupancy_index, relevant_occupancy_mask);
 
-                let shift = 64u32 - (relevant_bits_count as u32);
-                let magic_index = blocker_mask.wrapping_mul(ROOK_MAGIC_NUMBERS[sq_index]) >> shift;
-
-                attacks_table[sq_index][magic_index as usize] =
+                attacks_table[sq_index][occupancy_index as usize] =
                     generate_rook_attacks_mask(square, blocker_mask);
             }
         }
@@ -156,7 +314,23 @@ static ROOK_ATTACKS_TABLE: LazyLock<Box<[[u64; 4096]; chess_consts::SQUARES_COUN
         attacks_table
     });
 
-pub(crate) fn get_bishop_attacks_mask(square: Square, mut occupancy: u64) -> u64 {
+pub(crate) fn get_bishop_attacks_mask(square: Square, occupancy: u64) -> u64 {
+    if *PEXT_AVAILABLE {
+        get_bishop_attacks_mask_pext(square, occupancy)
+    } else {
+        get_bishop_attacks_mask_magic(square, occupancy)
+    }
+}
+
+pub(crate) fn get_rook_attacks_mask(square: Square, occupancy: u64) -> u64 {
+    if *PEXT_AVAILABLE {
+        get_rook_attacks_mask_pext(square, occupancy)
+    } else {
+        get_rook_attacks_mask_magic(square, occupancy)
+    }
+}
+
+fn get_bishop_attacks_mask_magic(square: Square, mut occupancy: u64) -> u64 {
     let square_index = square.index() as usize;
     occupancy &= BISHOP_RELEVANT_OCCUPANCY_MASKS[square_index];
 
@@ -166,7 +340,7 @@ pub(crate) fn get_bishop_attacks_mask(square: Square, mut occupancy: u64) -> u64
     BISHOP_ATTACKS_TABLE[square_index][magic_index as usize]
 }
 
-pub(crate) fn get_rook_attacks_mask(square: Square, mut occupancy: u64) -> u64 {
+fn get_rook_attacks_mask_magic(square: Square, mut occupancy: u64) -> u64 {
     let square_index = square.index() as usize;
     occupancy &= ROOK_RELEVANT_OCCUPANCY_MASKS[square_index];
 
@@ -176,6 +350,44 @@ pub(crate) fn get_rook_attacks_mask(square: Square, mut occupancy: u64) -> u64 {
     ROOK_ATTACKS_TABLE[square_index][magic_index as usize]
 }
 
+fn get_bishop_attacks_mask_pext(square: Square, occupancy: u64) -> u64 {
+    let square_index = square.index() as usize;
+    let pext_index = pext(occupancy, BISHOP_RELEVANT_OCCUPANCY_MASKS[square_index]);
+
+    BISHOP_PEXT_ATTACKS_TABLE[square_index][pext_index as usize]
+}
+
+fn get_rook_attacks_mask_pext(square: Square, occupancy: u64) -> u64 {
+    let square_index = square.index() as usize;
+    let pext_index = pext(occupancy, ROOK_RELEVANT_OCCUPANCY_MASKS[square_index]);
+
+    ROOK_PEXT_ATTACKS_TABLE[square_index][pext_index as usize]
+}
+
+/// Packs the bits of value selected by mask into the low bits, must only be called
+/// when PEXT_AVAILABLE is set
+#[inline(always)]
+fn pext(value: u64, mask: u64) -> u64 {
+    debug_assert!(*PEXT_AVAILABLE);
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        #[target_feature(enable = "bmi2")]
+        fn pext_bmi2(value: u64, mask: u64) -> u64 {
+            std::arch::x86_64::_pext_u64(value, mask)
+        }
+
+        // Safety: the callers check that the CPU supports BMI2
+        unsafe { pext_bmi2(value, mask) }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let _ = (value, mask);
+        unreachable!("PEXT is only available on x86_64")
+    }
+}
+
 pub(crate) fn get_queen_attacks_mask(square: Square, occupancy: u64) -> u64 {
     get_bishop_attacks_mask(square, occupancy) | get_rook_attacks_mask(square, occupancy)
 }
@@ -273,7 +485,7 @@ const fn generate_relevant_rook_occupancy_mask(square: Square) -> u64 {
     attacks_bb
 }
 
-const fn generate_bishop_attacks_mask(square: Square, blockers: u64) -> u64 {
+pub(crate) const fn generate_bishop_attacks_mask(square: Square, blockers: u64) -> u64 {
     let mut attacks_bb = chess_consts::EMPTY_BB;
 
     let (target_rank, target_file) = (square.rank(), square.file());
@@ -345,7 +557,7 @@ const fn generate_bishop_attacks_mask(square: Square, blockers: u64) -> u64 {
     attacks_bb
 }
 
-const fn generate_rook_attacks_mask(square: Square, blockers: u64) -> u64 {
+pub(crate) const fn generate_rook_attacks_mask(square: Square, blockers: u64) -> u64 {
     let mut attacks_bb = chess_consts::EMPTY_BB;
 
     let (target_rank, target_file) = (square.rank(), square.file());
@@ -432,91 +644,91 @@ const fn build_blocker_mask(index: u32, mut relevant_mask: u64) -> u64 {
     blocker
 }
 
-const fn find_magic_number(square: Square, piece: Piece) -> Option<u64> {
-    match piece {
-        Piece::Bishop | Piece::Rook => {}
-        _ => panic!("find_magic_number function works only with bishop or rook piece types"),
-    }
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
 
-    let mut occupancies = [0u64; 4096];
-    let mut attacks = [0u64; 4096];
-    let mut used_attacks;
+    use crate::{enums::Piece, helpers, random_generator::XorShift64Star};
 
-    let relevant_occupancy_mask = match piece {
-        Piece::Bishop => generate_relevant_bishop_occupancy_mask(square),
-        Piece::Rook => generate_relevant_rook_occupancy_mask(square),
-        _ => unreachable!(),
-    };
+    use super::*;
 
-    let relevant_bits_count = relevant_occupancy_mask.count_ones();
-    let occupancy_indicies = 2u64.pow(relevant_bits_count);
+    const fn find_magic_number(square: Square, piece: Piece) -> Option<u64> {
+        match piece {
+            Piece::Bishop | Piece::Rook => {}
+            _ => panic!("find_magic_number function works only with bishop or rook piece types"),
+        }
 
-    let mut index = 0;
-    while index < occupancy_indicies as usize {
-        occupancies[index] = build_blocker_mask(index as u32, relevant_occupancy_mask);
+        let mut occupancies = [0u64; 4096];
+        let mut attacks = [0u64; 4096];
+        let mut used_attacks;
 
-        attacks[index] = match piece {
-            Piece::Bishop => generate_bishop_attacks_mask(square, occupancies[index]),
-            Piece::Rook => generate_rook_attacks_mask(square, occupancies[index]),
+        let relevant_occupancy_mask = match piece {
+            Piece::Bishop => generate_relevant_bishop_occupancy_mask(square),
+            Piece::Rook => generate_relevant_rook_occupancy_mask(square),
             _ => unreachable!(),
         };
 
-        index += 1;
-    }
-
-    let mut rng_generator = XorShift64Star::new();
-    let mut random_index = 0;
-    while random_index < 100_000_000 {
-        random_index += 1;
-        let magic_number = rng_generator.generate_magic_number_candidate();
+        let relevant_bits_count = relevant_occupancy_mask.count_ones();
+        let occupancy_indicies = 2u64.pow(relevant_bits_count);
 
-        // Check that first 8 bits contain at least MIN_HIGH_BITS_SET to remove "mostly-zero" magics
-        const HIGH_8_BITS_MASK: u64 = 0xFF00_0000_0000_0000;
-        const MIN_HIGH_BITS_SET: u32 = 6;
+        let mut index = 0;
+        while index < occupancy_indicies as usize {
+            occupancies[index] = build_blocker_mask(index as u32, relevant_occupancy_mask);
 
-        let mixed = relevant_occupancy_mask.wrapping_mul(magic_number);
-        let high_bits = (mixed & HIGH_8_BITS_MASK).count_ones();
+            attacks[index] = match piece {
+                Piece::Bishop => generate_bishop_attacks_mask(square, occupancies[index]),
+                Piece::Rook => generate_rook_attacks_mask(square, occupancies[index]),
+                _ => unreachable!(),
+            };
 
-        if high_bits < MIN_HIGH_BITS_SET {
-            continue;
+            index += 1;
         }
 
-        used_attacks = [0u64; 4096];
-        let mut index = 0usize;
+        let mut rng_generator = XorShift64Star::new();
+        let mut random_index = 0;
+        while random_index < 100_000_000 {
+            random_index += 1;
+            let magic_number = rng_generator.generate_magic_number_candidate();
 
-        let mut fail = false;
-        while index < occupancy_indicies as usize {
-            let shift = 64 - relevant_bits_count;
-            let magic_index = occupancies[index].wrapping_mul(magic_number) >> shift;
-
-            // If no occupancy has landed here, ok
-            if used_attacks[magic_index as usize] == 0 {
-                used_attacks[magic_index as usize] = attacks[index];
-            } else if used_attacks[magic_index as usize] == attacks[index] {
-                // If occupancy with the same attack table has landed here, it is ok too
-            } else {
-                fail = true;
-                break;
-            }
+            // Check that first 8 bits contain at least MIN_HIGH_BITS_SET to remove "mostly-zero" magics
+            const HIGH_8_BITS_MASK: u64 = 0xFF00_0000_0000_0000;
+            const MIN_HIGH_BITS_SET: u32 = 6;
 
-            index += 1;
-        }
+            let mixed = relevant_occupancy_mask.wrapping_mul(magic_number);
+            let high_bits = (mixed & HIGH_8_BITS_MASK).count_ones();
 
-        if !fail {
-            return Some(magic_number);
-        }
-    }
-
-    None
-}
+            if high_bits < MIN_HIGH_BITS_SET {
+                continue;
+            }
 
-#[cfg(test)]
-mod tests {
-    use std::time::Instant;
+            used_attacks = [0u64; 4096];
+            let mut index = 0usize;
+
+            let mut fail = false;
+            while index < occupancy_indicies as usize {
+                let shift = 64 - relevant_bits_count;
+                let magic_index = occupancies[index].wrapping_mul(magic_number) >> shift;
+
+                // If no occupancy has landed here, ok
+                if used_attacks[magic_index as usize] == 0 {
+                    used_attacks[magic_index as usize] = attacks[index];
+                } else if used_attacks[magic_index as usize] == attacks[index] {
+                    // If occupancy with the same attack table has landed here, it is ok too
+                } else {
+                    fail = true;
+                    break;
+                }
+
+                index += 1;
+            }
 
-    use crate::helpers;
+            if !fail {
+                return Some(magic_number);
+            }
+        }
 
-    use super::*;
+        None
+    }
 
     #[test]
     #[ignore]
@@ -581,6 +793,41 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_pext_and_magic_attacks_match() {
+        if !*PEXT_AVAILABLE {
+            return;
+        }
+
+        for square in Square::all() {
+            let sq_index = square.index() as usize;
+
+            let bishop_mask = BISHOP_RELEVANT_OCCUPANCY_MASKS[sq_index];
+            for occupancy_index in 0..2u32.pow(bishop_mask.count_ones()) {
+                let occupancy = build_blocker_mask(occupancy_index, bishop_mask);
+                assert_eq!(
+                    get_bishop_attacks_mask_pext(square, occupancy),
+                    get_bishop_attacks_mask_magic(square, occupancy)
+                );
+            }
+
+            let rook_mask = ROOK_RELEVANT_OCCUPANCY_MASKS[sq_index];
+            for occupancy_index in 0..2u32.pow(rook_mask.count_ones()) {
+                let occupancy = build_blocker_mask(occupancy_index, rook_mask);
+                assert_eq!(
+                    get_rook_attacks_mask_pext(square, occupancy),
+                    get_rook_attacks_mask_magic(square, occupancy)
+                );
+            }
+
+            // Pieces outside of the relevant masks don't change the attacks
+            assert_eq!(
+                get_rook_attacks_mask_pext(square, u64::MAX),
+                get_rook_attacks_mask_magic(square, u64::MAX)
+            );
+        }
+    }
+
     #[test]
     #[ignore]
     fn test_system_supports_pext_operation() {
@@ -606,8 +853,8 @@ mod tests {
     #[ignore]
     fn test_bishop_and_rook_relevant_bit_counts_tables() {
         println!("Bishop relevant bit counts table");
-        for i in 0..chess_consts::SQUARES_COUNT {
-            print!("{} ", BISHOP_RELEVANT_BIT_COUNTS[i]);
+        for (i, count) in BISHOP_RELEVANT_BIT_COUNTS.iter().enumerate() {
+            print!("{count} ");
             if i % chess_consts::BOARD_SIZE == 7 {
                 println!();
             }
@@ -616,27 +863,39 @@ mod tests {
         println!();
 
         println!("Rook relevant bit counts table");
-        for i in 0..chess_consts::SQUARES_COUNT {
-            print!("{} ", ROOK_RELEVANT_BIT_COUNTS[i]);
+        for (i, count) in ROOK_RELEVANT_BIT_COUNTS.iter().enumerate() {
+            print!("{count} ");
             if i % chess_consts::BOARD_SIZE == 7 {
                 println!();
             }
         }
     }
 
+    #[test]
+    fn test_precomputed_magic_numbers() {
+        for sq in Square::all() {
+            assert_eq!(
+                find_magic_number(sq, Piece::Bishop),
+                Some(BISHOP_MAGIC_NUMBERS[sq.index() as usize])
+            );
+            assert_eq!(
+                find_magic_number(sq, Piece::Rook),
+                Some(ROOK_MAGIC_NUMBERS[sq.index() as usize])
+            );
+        }
+    }
+
     #[test]
     #[ignore]
     fn test_find_magic_number() {
         let start = Instant::now();
 
-        for sq in Square::all() {
-            let bishop_magic_number = BISHOP_MAGIC_NUMBERS[sq.index() as usize];
-            let rook_magic_number = ROOK_MAGIC_NUMBERS[sq.index() as usize];
-
-            println!(
-                "Square: {sq}, Bishop magic number: {:?}, rook magic number: {:?}",
-                bishop_magic_number, rook_magic_number
-            );
+        // Prints the tables in the format of BISHOP_MAGIC_NUMBERS and ROOK_MAGIC_NUMBERS
+        for piece in [Piece::Bishop, Piece::Rook] {
+            println!("{piece:?}");
+            for sq in Square::all() {
+                println!("    0x{:016X},", find_magic_number(sq, piece).unwrap());
+            }
         }
 
         println!("Elapsed: {:?}", start.elapsed().as_millis());
@@ -0,0 +1,356 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    enums::{CastlingSide, Move, MoveFlags, Piece, Square},
+    evaluation,
+    searching::ScoreBound,
+};
+
+/// Size of the table before the Hash option sets it
+pub(crate) const DEFAULT_HASH_MB: usize = 16;
+
+/// Bit layout of the entry data: the score, the depth, the bound and the move
+const SCORE_BITS: u32 = 16;
+const DEPTH_SHIFT: u32 = SCORE_BITS;
+const BOUND_SHIFT: u32 = DEPTH_SHIFT + 8;
+const MOVE_SHIFT: u32 = BOUND_SHIFT + 2;
+
+/// Number of entries looked at for the hashfull estimate
+const HASHFULL_SAMPLE: usize = 1000;
+
+/// The table of the searches that don't keep one, e.g. the mate search
+pub(crate) static NO_TABLE: TranspositionTable = TranspositionTable { slots: Vec::new() };
+
+/// What a search learned about a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TtEntry {
+    /// The score as seen from the node it is probed from, mates included
+    pub(crate) score: i32,
+    pub(crate) depth: u32,
+    pub(crate) bound: ScoreBound,
+    /// The best move, unknown when no move beat alpha
+    pub(crate) mv: Option<Move>,
+}
+
+/// The key is stored xored with the data, an entry torn by two threads writing it at
+/// the same time no longer matches its key and is taken for a miss
+#[derive(Debug, Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Transposition table shared by all the search threads, keyed on the Zobrist hash of
+/// the position. A slot holds the last position stored in it, except that a deeper
+/// entry of the same position is not replaced by a bound of a shallower search.
+/// A table of 0 entries stores nothing
+#[derive(Debug, Default)]
+pub(crate) struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+impl TranspositionTable {
+    /// The largest power of two of entries fitting in size_mb megabytes, 0 MB gives the
+    /// empty table
+    /// # Examples
+    /// 16 MB -> 1 << 20 entries of 16 bytes
+    pub(crate) fn new(size_mb: usize) -> Result<Self, &'static str> {
+        let error = "Not enough memory for the hash table";
+
+        let bytes = size_mb.checked_mul(1024 * 1024).ok_or(error)?;
+        let count = bytes / size_of::<Slot>();
+        let count = if count == 0 { 0 } else { 1 << count.ilog2() };
+
+        let mut slots = Vec::new();
+        slots.try_reserve_exact(count).map_err(|_| error)?;
+        slots.resize_with(count, Slot::default);
+
+        Ok(Self { slots })
+    }
+
+    /// Empties every entry
+    pub(crate) fn clear(&self) {
+        for slot in &self.slots {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Per mille of the entries in use, estimated from the first entries of the table
+    pub(crate) fn hashfull(&self) -> usize {
+        let sample = &self.slots[..self.slots.len().min(HASHFULL_SAMPLE)];
+        if sample.is_empty() {
+            return 0;
+        }
+
+        let used = sample
+            .iter()
+            .filter(|slot| slot.data.load(Ordering::Relaxed) != 0)
+            .count();
+
+        used * 1000 / sample.len()
+    }
+
+    fn slot(&self, key: u64) -> Option<&Slot> {
+        if self.slots.is_empty() {
+            return None;
+        }
+
+        Some(&self.slots[key as usize & (self.slots.len() - 1)])
+    }
+
+    /// The entry of the position, its mate scores made relative to the ply it is
+    /// probed from
+    pub(crate) fn probe(&self, key: u64, ply: u32) -> Option<TtEntry> {
+        let slot = self.slot(key)?;
+        let data = slot.data.load(Ordering::Relaxed);
+
+        if data == 0 || slot.key.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+
+        let entry = unpack(data)?;
+
+        Some(TtEntry {
+            score: score_from_tt(entry.score, ply),
+            ..entry
+        })
+    }
+
+    /// Stores the result of a node searched at the given ply. A bound without a move
+    /// keeps the move already known for the position
+    pub(crate) fn store(
+        &self,
+        key: u64,
+        ply: u32,
+        depth: u32,
+        score: i32,
+        bound: ScoreBound,
+        mv: Option<Move>,
+    ) {
+        let Some(slot) = self.slot(key) else {
+            return;
+        };
+
+        let old_data = slot.data.load(Ordering::Relaxed);
+        let old = (old_data != 0 && slot.key.load(Ordering::Relaxed) ^ old_data == key)
+            .then(|| unpack(old_data))
+            .flatten();
+
+        if let Some(old) = old
+            && old.depth > depth
+            && bound != ScoreBound::Exact
+        {
+            return;
+        }
+
+        let entry = TtEntry {
+            score: score_to_tt(score, ply),
+            depth,
+            bound,
+            mv: mv.or(old.and_then(|old| old.mv)),
+        };
+        let data = pack(&entry);
+
+        slot.key.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+}
+
+/// Mates are stored as distances from the node rather than from the root, the same
+/// position can be reached at another ply
+fn score_to_tt(score: i32, ply: u32) -> i32 {
+    if score >= evaluation::MATE_THRESHOLD {
+        score + ply as i32
+    } else if score <= -evaluation::MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: u32) -> i32 {
+    if score >= evaluation::MATE_THRESHOLD {
+        score - ply as i32
+    } else if score <= -evaluation::MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// The bound is never 0, so the data of a stored entry is never 0 either
+fn pack(entry: &TtEntry) -> u64 {
+    let bound: u64 = match entry.bound {
+        ScoreBound::Exact => 1,
+        ScoreBound::Lower => 2,
+        ScoreBound::Upper => 3,
+    };
+
+    (entry.score as i16 as u16 as u64)
+        | (entry.depth.min(u8::MAX as u32) as u64) << DEPTH_SHIFT
+        | bound << BOUND_SHIFT
+        | entry.mv.map_or(0, pack_move) << MOVE_SHIFT
+}
+
+fn unpack(data: u64) -> Option<TtEntry> {
+    let bound = match (data >> BOUND_SHIFT) & 0b11 {
+        1 => ScoreBound::Exact,
+        2 => ScoreBound::Lower,
+        3 => ScoreBound::Upper,
+        _ => return None,
+    };
+
+    Some(TtEntry {
+        score: data as u16 as i16 as i32,
+        depth: ((data >> DEPTH_SHIFT) & 0xff) as u32,
+        bound,
+        mv: unpack_move(data >> MOVE_SHIFT),
+    })
+}
+
+/// Packs the move into 25 bits: the kind, the squares and, for the normal moves, the
+/// pieces and the flags. The pieces that may be absent are stored one above their index
+fn pack_move(mv: Move) -> u64 {
+    match mv {
+        Move::Normal {
+            from,
+            to,
+            piece,
+            captured,
+            promo,
+            flags,
+        } => {
+            let optional_piece = |piece: Option<Piece>| piece.map_or(0, |p| p.index() as u64 + 1);
+
+            1 | (from.index() as u64) << 2
+                | (to.index() as u64) << 8
+                | (piece.index() as u64) << 14
+                | optional_piece(captured) << 17
+                | optional_piece(promo) << 20
+                | (flags.bits() as u64) << 23
+        }
+        Move::Castle { from, to, side } => {
+            2 | (from.index() as u64) << 2 | (to.index() as u64) << 8 | (side.index() as u64) << 14
+        }
+    }
+}
+
+/// None for an empty move and for bits no move packs to
+fn unpack_move(bits: u64) -> Option<Move> {
+    let from = Square::try_from(((bits >> 2) & 0x3f) as u8).ok()?;
+    let to = Square::try_from(((bits >> 8) & 0x3f) as u8).ok()?;
+    let piece = |index: u64| Piece::all().nth(index as usize);
+    let optional_piece = |bits: u64| match bits {
+        0 => Some(None),
+        _ => piece(bits - 1).map(Some),
+    };
+
+    match bits & 0b11 {
+        1 => Some(Move::Normal {
+            from,
+            to,
+            piece: piece((bits >> 14) & 0b111)?,
+            captured: optional_piece((bits >> 17) & 0b111)?,
+            promo: optional_piece((bits >> 20) & 0b111)?,
+            flags: MoveFlags::from_bits(((bits >> 23) & 0b11) as u8)?,
+        }),
+        2 => Some(Move::Castle {
+            from,
+            to,
+            side: CastlingSide::all().nth(((bits >> 14) & 1) as usize)?,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{chess_consts, fen_parser};
+
+    use super::*;
+
+    #[test]
+    fn test_table_size() {
+        assert_eq!(TranspositionTable::new(16).unwrap().slots.len(), 1 << 20);
+        assert_eq!(TranspositionTable::new(3).unwrap().slots.len(), 1 << 17);
+        assert_eq!(TranspositionTable::new(0).unwrap().slots.len(), 0);
+        assert!(TranspositionTable::new(usize::MAX).is_err());
+
+        // The empty table stores nothing
+        let table = TranspositionTable::new(0).unwrap();
+        table.store(12345, 0, 3, 50, ScoreBound::Exact, None);
+        assert_eq!(table.probe(12345, 0), None);
+        assert_eq!(table.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_hashfull() {
+        let table = TranspositionTable::new(1).unwrap();
+        assert_eq!(table.hashfull(), 0);
+
+        // Every other entry of the sample
+        for key in (0..HASHFULL_SAMPLE as u64).step_by(2) {
+            table.store(key, 0, 1, 0, ScoreBound::Exact, None);
+        }
+        assert_eq!(table.hashfull(), 500);
+
+        table.clear();
+        assert_eq!(table.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_store_and_probe() {
+        let table = TranspositionTable::new(1).unwrap();
+        let key = 0x1234_5678_9abc_def0;
+
+        assert_eq!(table.probe(key, 0), None);
+
+        table.store(key, 2, 5, -35, ScoreBound::Upper, None);
+        let entry = table.probe(key, 2).unwrap();
+        assert_eq!(
+            (entry.score, entry.depth, entry.bound, entry.mv),
+            (-35, 5, ScoreBound::Upper, None)
+        );
+
+        // Another key of the same slot is a miss
+        assert_eq!(table.probe(key + (1 << 40), 2), None);
+
+        // A shallower bound doesn't replace the entry, an exact score does
+        table.store(key, 2, 3, 10, ScoreBound::Lower, None);
+        assert_eq!(table.probe(key, 2).unwrap().depth, 5);
+        table.store(key, 2, 3, 10, ScoreBound::Exact, None);
+        assert_eq!(table.probe(key, 2).unwrap().score, 10);
+
+        // Mates are stored relative to the node and read back relative to the ply
+        let mate = evaluation::MATE_EVALUATION - 7;
+        table.store(key, 4, 6, mate, ScoreBound::Exact, None);
+        assert_eq!(table.probe(key, 4).unwrap().score, mate);
+        assert_eq!(table.probe(key, 2).unwrap().score, mate + 2);
+        table.store(key, 4, 6, -mate, ScoreBound::Exact, None);
+        assert_eq!(table.probe(key, 6).unwrap().score, -mate + 2);
+    }
+
+    #[test]
+    fn test_moves_are_kept_whole() {
+        for fen in [
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            chess_consts::fen_strings::KILLER_POS_FEN,
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ] {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            let table = TranspositionTable::new(1).unwrap();
+
+            for mv in board.generate_all_legal_moves_to_vec(board.game_state.side_to_move) {
+                table.store(7, 0, 1, 0, ScoreBound::Lower, Some(mv));
+                assert_eq!(table.probe(7, 0).unwrap().mv, Some(mv), "{fen}");
+            }
+
+            // A bound without a move keeps the known one
+            let known = table.probe(7, 0).unwrap().mv;
+            table.store(7, 0, 2, 0, ScoreBound::Upper, None);
+            assert_eq!(table.probe(7, 0).unwrap().mv, known);
+        }
+    }
+}
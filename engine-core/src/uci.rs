@@ -1,49 +1,205 @@
+use std::fmt::Display;
+
 use crate::{
     board::Board,
-    enums::{Move, Piece},
-    fen_parser,
+    enums::{CastlingSide, Move, MoveFlags, Piece, Side, Square},
+    evaluation, fen_parser,
+    searching::{MAX_SEARCH_DEPTH, ScoreBound},
 };
 
-pub(crate) fn serialize_move_to_uci_str(mv: Move) -> String {
+/// Same as the Move's Display, but in Chess960 mode castlings are written
+/// as the king capturing its own rook
+/// # Examples
+/// O-O from e1, standard -> e1g1
+/// O-O from b1 with the rook on f1, Chess960 -> b1f1
+pub(crate) fn serialize_board_move_to_uci_str(board: &Board, mv: Move) -> String {
     match mv {
-        Move::Normal {
-            from, to, promo, ..
-        } => {
-            let mut mv_str = format!("{}{}", from, to);
-
-            if let Some(promo_piece) = promo {
-                let promo_cg = match promo_piece {
-                    Piece::Knight => 'n',
-                    Piece::Bishop => 'b',
-                    Piece::Rook => 'r',
-                    Piece::Queen => 'q',
-                    _ => unreachable!(),
-                };
-                mv_str.push(promo_cg);
-            }
-            return mv_str;
+        Move::Castle { from, side, .. } if board.chess960 => {
+            // Taken from the king's square rather than the side to move, so the moves
+            // of a line can be written without playing them
+            let moving_side = if from.rank() == Side::White.get_back_rank() {
+                Side::White
+            } else {
+                Side::Black
+            };
+            let rook_sq = board.castling_config.get_rook_square(moving_side, side);
+
+            format!("{from}{rook_sq}")
         }
-        Move::Castle { from, to, .. } => {
-            let mv_str = format!("{from}{to}");
-            return mv_str;
+        _ => mv.to_string(),
+    }
+}
+
+/// Formats an internal score as the UCI "score" value
+/// # Examples
+/// 35 Exact -> cp 35
+/// MATE_EVALUATION - 3 Lower -> mate 2 lowerbound
+/// -MATE_EVALUATION + 2 Exact -> mate -1
+pub(crate) fn serialize_score_to_uci_str(score: i32, bound: ScoreBound) -> String {
+    let mut score_str = if !evaluation::is_mate_score(score) {
+        format!("cp {score}")
+    } else if score > 0 {
+        format!("mate {}", (evaluation::MATE_EVALUATION - score + 1) / 2)
+    } else {
+        format!("mate {}", -(evaluation::MATE_EVALUATION + score) / 2)
+    };
+
+    match bound {
+        ScoreBound::Exact => {}
+        ScoreBound::Lower => score_str.push_str(" lowerbound"),
+        ScoreBound::Upper => score_str.push_str(" upperbound"),
+    }
+
+    score_str
+}
+
+/// Why a move written in UCI notation was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ParseMoveError {
+    /// Not two squares and an optional promotion piece
+    InvalidFormat,
+    InvalidSquare,
+    /// The promotion piece is unknown, given for a move that doesn't promote or
+    /// missing for one that does
+    InvalidPromotion,
+    /// Well formed, but the side to move has no such legal move
+    IllegalMove,
+}
+
+impl ParseMoveError {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            ParseMoveError::InvalidFormat => "The move is not in the UCI notation",
+            ParseMoveError::InvalidSquare => "The move has an unknown square",
+            ParseMoveError::InvalidPromotion => {
+                "The promotion piece of the move doesn't match a legal promotion"
+            }
+            ParseMoveError::IllegalMove => "The move is not legal in the position",
         }
     }
 }
 
-pub(crate) fn parse_uci_move(move_str: &str, board: &mut Board) -> Option<Move> {
-    let moving_side = board.game_state.side_to_move;
-    let moves = board.generate_all_legal_moves_to_vec(moving_side);
+impl Display for ParseMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Move {
+    /// Finds the legal move written in UCI notation for the side to move, the board
+    /// fills in the moved and captured pieces and tells how castlings are written
+    /// # Examples
+    /// "e2e4" on the start position -> the double pawn push
+    /// "e2e5" on the start position -> None
+    pub(crate) fn parse(move_str: &str, board: &mut Board) -> Option<Move> {
+        Move::try_parse(move_str, board).ok()
+    }
+
+    /// Same as parse, but tells why the move was rejected. The move is built from its
+    /// squares and checked for legality, no moves are generated
+    /// # Examples
+    /// "e2e4" on the start position -> the double pawn push
+    /// "e2x4" -> InvalidSquare
+    /// "e7e8k" with a pawn on e7 -> InvalidPromotion
+    pub(crate) fn try_parse(move_str: &str, board: &mut Board) -> Result<Move, ParseMoveError> {
+        if !move_str.is_ascii() || !(4..=5).contains(&move_str.len()) {
+            return Err(ParseMoveError::InvalidFormat);
+        }
+
+        let from = Square::try_from(&move_str[0..2]).map_err(|_| ParseMoveError::InvalidSquare)?;
+        let to = Square::try_from(&move_str[2..4]).map_err(|_| ParseMoveError::InvalidSquare)?;
+        let promo = match move_str.as_bytes().get(4) {
+            None => None,
+            Some(b'q') => Some(Piece::Queen),
+            Some(b'r') => Some(Piece::Rook),
+            Some(b'b') => Some(Piece::Bishop),
+            Some(b'n') => Some(Piece::Knight),
+            Some(_) => return Err(ParseMoveError::InvalidPromotion),
+        };
+
+        let side = board.game_state.side_to_move;
+        let piece = board
+            .get_occupancy_piece(side, from)
+            .ok_or(ParseMoveError::IllegalMove)?;
+
+        if piece == Piece::King
+            && promo.is_none()
+            && let Some(castle) = find_castle(board, from, to)
+            && is_legal(board, castle)
+        {
+            return Ok(castle);
+        }
 
-    for mv in moves {
-        if move_str == &serialize_move_to_uci_str(mv) {
-            return Some(mv);
+        if promo.is_some() != (piece == Piece::Pawn && to.rank() == side.get_promotion_rank()) {
+            return Err(ParseMoveError::InvalidPromotion);
+        }
+
+        let mut captured = board.get_occupancy_piece(side.opposite(), to);
+        let mut flags = MoveFlags::empty();
+
+        if piece == Piece::Pawn {
+            if captured.is_none()
+                && from.file() != to.file()
+                && board.game_state.en_passant_square == Some(to)
+            {
+                captured = Some(Piece::Pawn);
+                flags = MoveFlags::EN_PASSANT;
+            } else if from.rank().index().abs_diff(to.rank().index()) == 2 {
+                flags = MoveFlags::DOUBLE_PUSH;
+            }
         }
+
+        let mv = Move::Normal {
+            from,
+            to,
+            piece,
+            captured,
+            promo,
+            flags,
+        };
+
+        if is_legal(board, mv) {
+            Ok(mv)
+        } else {
+            Err(ParseMoveError::IllegalMove)
+        }
+    }
+}
+
+/// The castling of the side to move written with the squares, the king goes to the
+/// target square, or in Chess960 mode captures its own rook there
+fn find_castle(board: &Board, from: Square, to: Square) -> Option<Move> {
+    let side = board.game_state.side_to_move;
+
+    CastlingSide::all()
+        .map(|castling_side| {
+            let castle = board.castling_config.get_castling_move(side, castling_side);
+            let (king_from, king_to) = castle.get_from_to();
+            let target = if board.chess960 {
+                board.castling_config.get_rook_square(side, castling_side)
+            } else {
+                king_to
+            };
+
+            (castle, king_from, target)
+        })
+        .find(|&(_, king_from, target)| king_from == from && target == to)
+        .map(|(castle, _, _)| castle)
+}
+
+fn is_legal(board: &mut Board, mv: Move) -> bool {
+    if board.make_move_checked(mv).is_err() {
+        return false;
     }
 
-    None
+    board.unmake_move();
+    true
 }
 
-pub fn parse_uci_position_command(position_str: &str) -> Result<Board, &'static str> {
+pub fn parse_uci_position_command(
+    position_str: &str,
+    chess960: bool,
+) -> Result<Board, &'static str> {
     let parts: Vec<_> = position_str.split_whitespace().collect();
 
     if [0, 1].contains(&parts.len()) || parts[0] != "position" {
@@ -53,25 +209,33 @@ pub fn parse_uci_position_command(position_str: &str) -> Result<Board, &'static
     let (mut board, moves_index) = if parts[1] == "startpos" {
         (Board::get_start_position(), 2)
     } else if parts[1] == "fen" {
-        if parts.len() < 8 {
+        // The move counters may be left out, the FEN ends where the moves start
+        let fen_end = parts
+            .iter()
+            .position(|&part| part == "moves")
+            .unwrap_or(parts.len());
+
+        if fen_end < 6 {
             return Err("The fen position was incorrect");
         }
 
-        let fen_str = parts[2..=7].join(" ");
+        let fen_str = parts[2..fen_end].join(" ");
         (
             fen_parser::parse_fen_string(&fen_str)
                 .map_err(|_| "An error occured during parsing the fen string")?,
-            8,
+            fen_end,
         )
     } else {
         return Err("The string is not a valid position command");
     };
 
+    board.chess960 = chess960;
+
     if parts.len() == moves_index {
         return Ok(board);
     }
 
-    if !(parts[moves_index] == "moves") {
+    if parts[moves_index] != "moves" {
         return Err("The string is not a valid position command");
     }
 
@@ -80,101 +244,214 @@ pub fn parse_uci_position_command(position_str: &str) -> Result<Board, &'static
     }
 
     for &mv in &parts[moves_index + 1..] {
-        if let Some(mv) = parse_uci_move(mv, &mut board) {
-            board.make_move(mv);
-        } else {
-            return Err("The move in the move section was invalid");
-        }
+        let mv = Move::try_parse(mv, &mut board).map_err(ParseMoveError::as_str)?;
+        board.make_move(mv);
     }
 
     Ok(board)
 }
 
-pub(crate) fn parse_uci_go_commmand(command: &str) -> Result<UciGoCommand, &'static str> {
-    let error = "The string is not a valid go command";
+/// Applies a position command to the board set up by the previous one. In a game the
+/// GUI resends the same command with the new moves appended, only those moves are
+/// played then, any other command rebuilds the board from scratch. The board is left
+/// untouched on errors
+pub(crate) fn update_board_from_position_command(
+    board: &mut Board,
+    prev_position_str: Option<&str>,
+    position_str: &str,
+    chess960: bool,
+) -> Result<(), &'static str> {
+    let parts: Vec<_> = position_str.split_whitespace().collect();
+
+    let new_moves = prev_position_str
+        .filter(|_| board.chess960 == chess960)
+        .and_then(|prev_position_str| {
+            let prev_parts: Vec<_> = prev_position_str.split_whitespace().collect();
+            let new_parts = parts.strip_prefix(prev_parts.as_slice())?;
+
+            if prev_parts.contains(&"moves") {
+                Some(new_parts)
+            } else {
+                match new_parts {
+                    [] => Some(new_parts),
+                    ["moves", new_moves @ ..] => Some(new_moves),
+                    _ => None,
+                }
+            }
+        });
+
+    let Some(new_moves) = new_moves else {
+        *board = parse_uci_position_command(position_str, chess960)?;
+        return Ok(());
+    };
+
+    let mut new_board = board.clone();
+    for &mv in new_moves {
+        let mv = Move::try_parse(mv, &mut new_board).map_err(ParseMoveError::as_str)?;
+        new_board.make_move(mv);
+    }
+
+    *board = new_board;
+    Ok(())
+}
+
+/// Splits a "setoption name <name> [value <value>]" command into the option name
+/// and its value, both may contain spaces
+pub(crate) fn parse_uci_setoption_command(
+    command: &str,
+) -> Result<(String, Option<String>), &'static str> {
+    let error = "The string is not a valid setoption command";
     let parts: Vec<_> = command.split_whitespace().collect();
 
-    if parts.len() == 0 {
+    if parts.len() < 3 || parts[0] != "setoption" || parts[1] != "name" {
         return Err(error);
     }
 
-    if parts.len() == 1 {
-        return Ok(UciGoCommand {
-            mode: GoMode::Infinite,
-            tc: TimeControl::default(),
-            search_moves: None,
-            nodes: None,
-            mate: None,
-        });
+    let value_index = parts.iter().position(|&part| part == "value");
+    let name_parts = &parts[2..value_index.unwrap_or(parts.len())];
+
+    if name_parts.is_empty() {
+        return Err(error);
     }
 
-    match parts[1] {
-        "depth" => {
-            if parts.len() < 3 {
-                return Err(error);
-            }
+    let value = value_index.map(|index| parts[index + 1..].join(" "));
 
-            let depth = parts[2]
-                .parse::<u32>()
-                .map_err(|_| "Failed to parse depth")?;
-            return Ok(UciGoCommand {
-                mode: GoMode::Depth(depth),
-                tc: TimeControl::default(),
-                search_moves: None,
-                nodes: None,
-                mate: None,
-            });
-        }
-        "movetime" => {
-            if parts.len() < 3 {
-                return Err(error);
-            }
-            let search_time = parts[2]
-                .parse::<u64>()
-                .map_err(|_| "Failed to parse search time")?;
-
-            return Ok(UciGoCommand {
-                mode: GoMode::MoveTime(search_time),
-                tc: TimeControl::default(),
-                search_moves: None,
-                nodes: None,
-                mate: None,
-            });
-        }
-        "infinite" => {
-            return Ok(UciGoCommand {
-                mode: GoMode::Infinite,
-                tc: TimeControl::default(),
-                search_moves: None,
-                nodes: None,
-                mate: None,
-            });
-        }
-        _ => Ok(UciGoCommand {
-            mode: GoMode::Infinite,
-            tc: TimeControl::default(),
-            search_moves: None,
-            nodes: None,
-            mate: None,
-        }),
+    Ok((name_parts.join(" "), value))
+}
+
+/// Keywords of the go command, they end the move list of "searchmoves"
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves",
+    "ponder",
+    "wtime",
+    "btime",
+    "winc",
+    "binc",
+    "movestogo",
+    "depth",
+    "nodes",
+    "mate",
+    "movetime",
+    "infinite",
+];
+
+/// The moves of "searchmoves" are parsed on the board the search will start from
+pub(crate) fn parse_uci_go_commmand(
+    command: &str,
+    board: &mut Board,
+) -> Result<UciGoCommand, &'static str> {
+    let error = "The string is not a valid go command";
+    let mut parts: Vec<_> = command.split_whitespace().collect();
+
+    // "ponder" and "infinite" may stand anywhere after "go"
+    let ponder = parts.contains(&"ponder");
+    let infinite = parts.contains(&"infinite");
+    parts.retain(|&part| part != "ponder" && part != "infinite");
+
+    let search_moves = take_search_moves(&mut parts, board);
+
+    // Every limit is optional and independent of the others
+    // Too large depths and mates are clamped rather than wrapped
+    let depth = take_go_argument(&mut parts, "depth")?.map(|depth| {
+        u32::try_from(depth)
+            .unwrap_or(u32::MAX)
+            .min(MAX_SEARCH_DEPTH)
+    });
+    let movetime = take_go_argument(&mut parts, "movetime")?;
+    let nodes = take_go_argument(&mut parts, "nodes")?;
+    let mate =
+        take_go_argument(&mut parts, "mate")?.map(|moves| u32::try_from(moves).unwrap_or(u32::MAX));
+    let tc = TimeControl {
+        wtime: take_clock_argument(&mut parts, "wtime")?,
+        btime: take_clock_argument(&mut parts, "btime")?,
+        winc: take_go_argument(&mut parts, "winc")?,
+        binc: take_go_argument(&mut parts, "binc")?,
+        movestogo: take_go_argument(&mut parts, "movestogo")?,
+    };
+
+    // The tokens the command doesn't know are skipped, so a bare "go" or one with only
+    // unknown tokens searches until "stop"
+    if parts.first() != Some(&"go") {
+        return Err(error);
     }
+
+    // Searching until "stop" leaves the clock out
+    let tc = if infinite { TimeControl::default() } else { tc };
+
+    Ok(UciGoCommand {
+        depth,
+        movetime,
+        infinite,
+        tc,
+        search_moves,
+        nodes,
+        mate,
+        ponder,
+    })
+}
+
+/// Removes "searchmoves <move>..." from the go command parts and returns the legal
+/// moves of the list, None if there is no list or none of its moves is legal
+fn take_search_moves(parts: &mut Vec<&str>, board: &mut Board) -> Option<Vec<Move>> {
+    let index = parts.iter().position(|&part| part == "searchmoves")?;
+
+    let end = parts[index + 1..]
+        .iter()
+        .position(|part| GO_KEYWORDS.contains(part))
+        .map_or(parts.len(), |offset| index + 1 + offset);
+
+    let moves: Vec<Move> = parts
+        .drain(index..end)
+        .skip(1)
+        .filter_map(|move_str| Move::parse(move_str, board))
+        .collect();
+
+    (!moves.is_empty()).then_some(moves)
+}
+
+/// Removes "<name> <value>" from the go command parts and returns the value
+fn take_go_argument(parts: &mut Vec<&str>, name: &str) -> Result<Option<u64>, &'static str> {
+    let Some(index) = parts.iter().position(|&part| part == name) else {
+        return Ok(None);
+    };
+
+    let value = parts
+        .get(index + 1)
+        .and_then(|value| value.parse::<u64>().ok())
+        .ok_or("Failed to parse the go command argument")?;
+    parts.drain(index..=index + 1);
+
+    Ok(Some(value))
+}
+
+/// take_go_argument for the clock times, a GUI may send a negative time once the clock
+/// ran out, it counts as no time left
+fn take_clock_argument(parts: &mut Vec<&str>, name: &str) -> Result<Option<u64>, &'static str> {
+    let Some(index) = parts.iter().position(|&part| part == name) else {
+        return Ok(None);
+    };
+
+    let value = parts
+        .get(index + 1)
+        .and_then(|value| value.parse::<i64>().ok())
+        .ok_or("Failed to parse the go command argument")?;
+    parts.drain(index..=index + 1);
+
+    Ok(Some(value.max(0) as u64))
 }
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub(crate) struct UciGoCommand {
-    pub(crate) mode: GoMode,
+    pub(crate) depth: Option<u32>,
+    /// Search time in milliseconds
+    pub(crate) movetime: Option<u64>,
+    pub(crate) infinite: bool,
     pub(crate) tc: TimeControl,
     pub(crate) search_moves: Option<Vec<Move>>,
     pub(crate) nodes: Option<u64>,
     pub(crate) mate: Option<u32>,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum GoMode {
-    Depth(u32),
-    MoveTime(u64),
-    Infinite,
+    pub(crate) ponder: bool,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -184,13 +461,18 @@ pub(crate) struct TimeControl {
     pub(crate) btime: Option<u64>,
     pub(crate) winc: Option<u64>,
     pub(crate) binc: Option<u64>,
+    /// Moves until the next time control, None for sudden death
+    pub(crate) movestogo: Option<u64>,
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        enums::{CastlingSide, MoveFlags, Side, Square},
-        fen_parser,
+        board::{CastlingConfig, CastlingState},
+        chess_consts, fen_parser,
+        move_ordering::MoveOrderingState,
+        searching,
+        transposition_table::{DEFAULT_HASH_MB, TranspositionTable},
     };
 
     use super::*;
@@ -205,7 +487,7 @@ mod tests {
             promo: None,
             flags: MoveFlags::empty(),
         };
-        assert_eq!("a2a4", serialize_move_to_uci_str(mv));
+        assert_eq!("a2a4", mv.to_string());
 
         let mv = Move::Normal {
             from: Square::A7,
@@ -215,7 +497,7 @@ mod tests {
             promo: Some(Piece::Queen),
             flags: MoveFlags::empty(),
         };
-        assert_eq!("a7a8q", serialize_move_to_uci_str(mv));
+        assert_eq!("a7a8q", mv.to_string());
 
         let mv = Move::Normal {
             from: Square::A7,
@@ -225,7 +507,7 @@ mod tests {
             promo: None,
             flags: MoveFlags::empty(),
         };
-        assert_eq!("a7a5", serialize_move_to_uci_str(mv));
+        assert_eq!("a7a5", mv.to_string());
 
         let mv = Move::Normal {
             from: Square::A2,
@@ -235,27 +517,176 @@ mod tests {
             promo: Some(Piece::Rook),
             flags: MoveFlags::empty(),
         };
-        assert_eq!("a2a1r", serialize_move_to_uci_str(mv));
+        assert_eq!("a2a1r", mv.to_string());
     }
 
     #[test]
     fn test_castling_moves_serialization() {
-        let king_side_castle = Move::get_castling_move(Side::White, CastlingSide::KingSide);
-        assert_eq!("e1g1", serialize_move_to_uci_str(king_side_castle));
-        let queen_side_castle = Move::get_castling_move(Side::White, CastlingSide::QueenSide);
-        assert_eq!("e1c1", serialize_move_to_uci_str(queen_side_castle));
-
-        let king_side_castle = Move::get_castling_move(Side::Black, CastlingSide::KingSide);
-        assert_eq!("e8g8", serialize_move_to_uci_str(king_side_castle));
-        let queen_side_castle = Move::get_castling_move(Side::Black, CastlingSide::QueenSide);
-        assert_eq!("e8c8", serialize_move_to_uci_str(queen_side_castle));
+        let king_side_castle =
+            CastlingConfig::default().get_castling_move(Side::White, CastlingSide::KingSide);
+        assert_eq!("e1g1", king_side_castle.to_string());
+        let queen_side_castle =
+            CastlingConfig::default().get_castling_move(Side::White, CastlingSide::QueenSide);
+        assert_eq!("e1c1", queen_side_castle.to_string());
+
+        let king_side_castle =
+            CastlingConfig::default().get_castling_move(Side::Black, CastlingSide::KingSide);
+        assert_eq!("e8g8", king_side_castle.to_string());
+        let queen_side_castle =
+            CastlingConfig::default().get_castling_move(Side::Black, CastlingSide::QueenSide);
+        assert_eq!("e8c8", queen_side_castle.to_string());
+    }
+
+    #[test]
+    fn test_score_serialization() {
+        assert_eq!("cp 35", serialize_score_to_uci_str(35, ScoreBound::Exact));
+        assert_eq!(
+            "cp -120",
+            serialize_score_to_uci_str(-120, ScoreBound::Exact)
+        );
+        assert_eq!(
+            "cp 50 upperbound",
+            serialize_score_to_uci_str(50, ScoreBound::Upper)
+        );
+
+        // Mate in one move is found at ply 1, the side to move gets mated at ply 2
+        let mate = evaluation::MATE_EVALUATION;
+        assert_eq!(
+            "mate 1",
+            serialize_score_to_uci_str(mate - 1, ScoreBound::Exact)
+        );
+        assert_eq!(
+            "mate 2 lowerbound",
+            serialize_score_to_uci_str(mate - 3, ScoreBound::Lower)
+        );
+        assert_eq!(
+            "mate -1",
+            serialize_score_to_uci_str(-mate + 2, ScoreBound::Exact)
+        );
+        assert_eq!(
+            "mate -2",
+            serialize_score_to_uci_str(-mate + 4, ScoreBound::Exact)
+        );
+    }
+
+    #[test]
+    fn test_forced_mate_score_serialization() {
+        // White mates with Ra8#
+        let mut board = fen_parser::parse_fen_string("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = searching::search_bestmove(
+            &mut board,
+            3,
+            &searching::StopToken::new(),
+            &mut MoveOrderingState::new(),
+            &TranspositionTable::new(DEFAULT_HASH_MB).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!("a1a8", result.best_move.to_string());
+        assert_eq!(
+            "mate 1",
+            serialize_score_to_uci_str(result.score, result.bound)
+        );
+
+        // Black has only Kb8, after which Rh8 is mate
+        let mut board = fen_parser::parse_fen_string("k7/8/1K6/8/8/8/8/7R b - - 0 1").unwrap();
+        let result = searching::search_bestmove(
+            &mut board,
+            4,
+            &searching::StopToken::new(),
+            &mut MoveOrderingState::new(),
+            &TranspositionTable::new(DEFAULT_HASH_MB).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "mate -1",
+            serialize_score_to_uci_str(result.score, result.bound)
+        );
+    }
+
+    #[test]
+    fn test_try_parse_matches_the_legal_moves() {
+        let fens = [
+            chess_consts::fen_strings::START_POS_FEN,
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w GBgb - 0 1",
+        ];
+
+        for fen in fens {
+            for chess960 in [false, true] {
+                let mut board = fen_parser::parse_fen_string(fen).unwrap();
+                board.chess960 = chess960;
+
+                for mv in board.generate_all_legal_moves_to_vec(board.game_state.side_to_move) {
+                    let move_str = serialize_board_move_to_uci_str(&board, mv);
+                    assert_eq!(Move::try_parse(&move_str, &mut board), Ok(mv), "{fen}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_parse_errors() {
+        let mut board = fen_parser::parse_fen_string(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        )
+        .unwrap();
+
+        let parse = |move_str: &str, board: &mut Board| Move::try_parse(move_str, board);
+
+        assert_eq!(parse("e2", &mut board), Err(ParseMoveError::InvalidFormat));
+        assert_eq!(
+            parse("d7c8qq", &mut board),
+            Err(ParseMoveError::InvalidFormat)
+        );
+        assert_eq!(
+            parse("e2x4", &mut board),
+            Err(ParseMoveError::InvalidSquare)
+        );
+        assert_eq!(
+            parse("i2e4", &mut board),
+            Err(ParseMoveError::InvalidSquare)
+        );
+        assert_eq!(
+            parse("d7c8k", &mut board),
+            Err(ParseMoveError::InvalidPromotion)
+        );
+        assert_eq!(
+            parse("d7c8Q", &mut board),
+            Err(ParseMoveError::InvalidPromotion)
+        );
+        assert_eq!(
+            parse("d7c8", &mut board),
+            Err(ParseMoveError::InvalidPromotion)
+        );
+        assert_eq!(
+            parse("a2a3q", &mut board),
+            Err(ParseMoveError::InvalidPromotion)
+        );
+        assert_eq!(parse("a2a5", &mut board), Err(ParseMoveError::IllegalMove));
+        assert_eq!(parse("a7a6", &mut board), Err(ParseMoveError::IllegalMove));
+        assert!(parse("d7c8n", &mut board).is_ok());
+
+        // The error reaches the position command
+        assert_eq!(
+            parse_uci_position_command("position startpos moves e2e4 e7e9", false).err(),
+            Some(ParseMoveError::InvalidSquare.as_str())
+        );
     }
 
     #[test]
     fn test_parsing_moves_normal_promo_moves() {
         let mut board = Board::get_start_position();
 
-        let mv = parse_uci_move("a2a3", &mut board);
+        assert_eq!(Move::parse("e2e5", &mut board), None);
+        assert_eq!(Move::parse("e7e5", &mut board), None);
+        assert_eq!(Move::parse("e2", &mut board), None);
+
+        let mv = Move::parse("a2a3", &mut board);
         assert_eq!(
             mv,
             Some(Move::Normal {
@@ -268,7 +699,7 @@ mod tests {
             })
         );
 
-        let mv = parse_uci_move("a2a4", &mut board);
+        let mv = Move::parse("a2a4", &mut board);
         assert_eq!(
             mv,
             Some(Move::Normal {
@@ -281,7 +712,7 @@ mod tests {
             })
         );
 
-        let mv = parse_uci_move("b1c3", &mut board);
+        let mv = Move::parse("b1c3", &mut board);
         assert_eq!(
             mv,
             Some(Move::Normal {
@@ -294,9 +725,10 @@ mod tests {
             })
         );
 
-        let mut board = fen_parser::parse_fen_string("2q5/1P6/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let mut board =
+            fen_parser::parse_fen_string_unchecked("2q5/1P6/8/8/8/8/8/K7 w - - 0 1").unwrap();
 
-        let mv = parse_uci_move("b7b8q", &mut board);
+        let mv = Move::parse("b7b8q", &mut board);
         assert_eq!(
             mv,
             Some(Move::Normal {
@@ -309,7 +741,7 @@ mod tests {
             })
         );
 
-        let mv = parse_uci_move("b7c8n", &mut board);
+        let mv = Move::parse("b7c8n", &mut board);
         assert_eq!(
             mv,
             Some(Move::Normal {
@@ -322,9 +754,10 @@ mod tests {
             })
         );
 
-        let mut board = fen_parser::parse_fen_string("2q4k/p7/8/8/8/8/6p1/5R2 b - - 0 1").unwrap();
+        let mut board =
+            fen_parser::parse_fen_string_unchecked("2q4k/p7/8/8/8/8/6p1/5R2 b - - 0 1").unwrap();
 
-        let mv = parse_uci_move("g2g1b", &mut board);
+        let mv = Move::parse("g2g1b", &mut board);
         assert_eq!(
             mv,
             Some(Move::Normal {
@@ -337,7 +770,7 @@ mod tests {
             })
         );
 
-        let mv = parse_uci_move("g2f1q", &mut board);
+        let mv = Move::parse("g2f1q", &mut board);
         assert_eq!(
             mv,
             Some(Move::Normal {
@@ -350,7 +783,7 @@ mod tests {
             })
         );
 
-        let mv = parse_uci_move("c8a8", &mut board);
+        let mv = Move::parse("c8a8", &mut board);
         assert_eq!(
             mv,
             Some(Move::Normal {
@@ -366,113 +799,323 @@ mod tests {
 
     #[test]
     fn test_parse_castling_moves() {
-        let mut board = fen_parser::parse_fen_string("8/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let mut board =
+            fen_parser::parse_fen_string_unchecked("8/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
 
-        let mv = parse_uci_move("e1g1", &mut board);
+        let mv = Move::parse("e1g1", &mut board);
         assert_eq!(
             mv,
-            Some(Move::get_castling_move(Side::White, CastlingSide::KingSide))
+            Some(CastlingConfig::default().get_castling_move(Side::White, CastlingSide::KingSide))
         );
 
-        let mv = parse_uci_move("e1c1", &mut board);
+        let mv = Move::parse("e1c1", &mut board);
         assert_eq!(
             mv,
-            Some(Move::get_castling_move(
-                Side::White,
-                CastlingSide::QueenSide
-            ))
+            Some(CastlingConfig::default().get_castling_move(Side::White, CastlingSide::QueenSide))
         );
 
-        let mut board = fen_parser::parse_fen_string("r3k2r/8/8/8/8/8/8/8 b kq - 0 1").unwrap();
+        let mut board =
+            fen_parser::parse_fen_string_unchecked("r3k2r/8/8/8/8/8/8/8 b kq - 0 1").unwrap();
 
-        let mv = parse_uci_move("e8g8", &mut board);
+        let mv = Move::parse("e8g8", &mut board);
         assert_eq!(
             mv,
-            Some(Move::get_castling_move(Side::Black, CastlingSide::KingSide))
+            Some(CastlingConfig::default().get_castling_move(Side::Black, CastlingSide::KingSide))
         );
 
-        let mv = parse_uci_move("e8c8", &mut board);
+        let mv = Move::parse("e8c8", &mut board);
         assert_eq!(
             mv,
-            Some(Move::get_castling_move(
-                Side::Black,
-                CastlingSide::QueenSide
-            ))
+            Some(CastlingConfig::default().get_castling_move(Side::Black, CastlingSide::QueenSide))
+        );
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+        let fen = "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w GBgb - 0 1";
+
+        let board =
+            parse_uci_position_command(&format!("position fen {fen} moves e1b1"), true).unwrap();
+        assert_eq!(board.get_king_square(Side::White), Square::C1);
+        assert_eq!(
+            board.get_occupancy_piece(Side::White, Square::D1),
+            Some(Piece::Rook)
+        );
+        assert!(
+            !board
+                .game_state
+                .castling_state
+                .contains(CastlingState::WHITE_QUEENSIDE)
+        );
+
+        let mut board = fen_parser::parse_fen_string(fen).unwrap();
+        let castle = board
+            .castling_config
+            .get_castling_move(Side::White, CastlingSide::QueenSide);
+        assert_eq!("e1c1", serialize_board_move_to_uci_str(&board, castle));
+
+        board.chess960 = true;
+        assert_eq!("e1b1", serialize_board_move_to_uci_str(&board, castle));
+        assert_eq!(Move::parse("e1b1", &mut board), Some(castle));
+    }
+
+    #[test]
+    fn test_parse_setoption_command() {
+        assert_eq!(
+            parse_uci_setoption_command("setoption name UCI_Chess960 value true"),
+            Ok(("UCI_Chess960".to_string(), Some("true".to_string())))
+        );
+        assert_eq!(
+            parse_uci_setoption_command("setoption name Clear Hash"),
+            Ok(("Clear Hash".to_string(), None))
+        );
+        assert!(parse_uci_setoption_command("setoption value 1").is_err());
+    }
+
+    #[test]
+    fn test_en_passant_square_set_only_when_capturable() {
+        let board = parse_uci_position_command("position startpos moves e2e4", false).unwrap();
+        assert_eq!(board.game_state.en_passant_square, None);
+
+        let board =
+            parse_uci_position_command("position startpos moves e2e4 a7a6 e4e5 d7d5", false)
+                .unwrap();
+        assert_eq!(board.game_state.en_passant_square, Some(Square::D6));
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/1pp1pppp/p7/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3"
         );
     }
 
     #[test]
     fn test_parse_position_function() {
-        assert!(matches!(
-            parse_uci_position_command("position startpos"),
-            Ok(_)
-        ));
+        assert!(parse_uci_position_command("position startpos", false).is_ok());
         assert!(
-            matches!(parse_uci_position_command("position startpos moves e2e4"), Ok(board) if board.history.len() == 1 && board.game_state.side_to_move == Side::Black)
+            matches!(parse_uci_position_command("position startpos moves e2e4", false), Ok(board) if board.history.len() == 1 && board.game_state.side_to_move == Side::Black)
         );
         assert!(
-            matches!(parse_uci_position_command("position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Ok(board) if board.game_state.side_to_move == Side::White && board.game_state.full_moves_count == 1)
+            matches!(parse_uci_position_command("position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", false), Ok(board) if board.game_state.side_to_move == Side::White && board.game_state.full_moves_count == 1)
         );
         assert!(
-            matches!(parse_uci_position_command("position fen rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 moves c7c5"), Ok(board) if board.history.len() == 1)
+            matches!(parse_uci_position_command("position fen rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 moves c7c5", false), Ok(board) if board.history.len() == 1)
         );
 
+        // Without the move counters, or only without the full move number
         assert!(
-            matches!(parse_uci_position_command("position startpos moves"), Ok(board) if board.history.len() == 0)
+            matches!(parse_uci_position_command("position fen rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - moves c7c5", false), Ok(board) if board.history.len() == 1 && board.game_state.full_moves_count == 2)
         );
         assert!(
-            matches!(parse_uci_position_command("position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves"), Ok(board) if board.history.len() == 0)
+            matches!(parse_uci_position_command("position fen 4k3/8/8/8/8/8/8/4K2R w K - 7", false), Ok(board) if board.game_state.half_move_clock == 7 && board.game_state.full_moves_count == 1)
         );
+
         assert!(
-            matches!(parse_uci_position_command("position startpos moves e2e4 e7e5"), Ok(board) if board.history.len() == 2)
+            matches!(parse_uci_position_command("position startpos moves", false), Ok(board) if board.history.len() == 0)
+        );
+        assert!(
+            matches!(parse_uci_position_command("position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves", false), Ok(board) if board.history.len() == 0)
+        );
+        assert!(
+            matches!(parse_uci_position_command("position startpos moves e2e4 e7e5", false), Ok(board) if board.history.len() == 2)
         );
 
-        assert!(matches!(parse_uci_position_command("position"), Err(_)));
-        assert!(matches!(
+        assert!(parse_uci_position_command("position", false).is_err());
+        assert!(
+            parse_uci_position_command(
+                "position startpos fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                false
+            )
+            .is_err()
+        );
+        assert!(
+            parse_uci_position_command(
+                "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
+                false
+            )
+            .is_err()
+        );
+        assert!(
             parse_uci_position_command(
-                "position startpos fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
-            ),
-            Err(_)
+                "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 extra",
+                false
+            )
+            .is_err()
+        );
+        assert!(parse_uci_position_command("position startpos moves e4", false).is_err());
+    }
+
+    #[test]
+    fn test_update_board_from_position_command() {
+        let commands = [
+            "position startpos",
+            "position startpos moves e2e4",
+            "position startpos moves e2e4 e7e5 g1f3",
+            "position startpos moves d2d4 d7d5",
+            "position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1g1",
+            "position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1g1 e8d7",
+        ];
+
+        let mut board = Board::get_start_position();
+        let mut prev_command = None;
+
+        for command in commands {
+            update_board_from_position_command(&mut board, prev_command, command, false).unwrap();
+            assert_eq!(board, parse_uci_position_command(command, false).unwrap());
+
+            prev_command = Some(command);
+        }
+
+        // Only the appended moves are played, the board is trusted to match the previous command
+        let mut board = Board::get_start_position();
+        assert!(
+            update_board_from_position_command(
+                &mut board,
+                Some("position startpos moves e2e4"),
+                "position startpos moves e2e4 e7e5",
+                false
+            )
+            .is_err()
+        );
+        assert_eq!(board, Board::get_start_position());
+    }
+
+    #[test]
+    fn test_parse_uci_go_command() {
+        let board = &mut Board::get_start_position();
+
+        assert!(parse_uci_go_commmand("go", board).is_ok());
+        assert!(matches!(
+            parse_uci_go_commmand("go depth 3", board),
+            Ok(UciGoCommand {
+                depth: Some(3),
+                movetime: None,
+                infinite: false,
+                ..
+            })
         ));
         assert!(matches!(
-            parse_uci_position_command("position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"),
-            Err(_)
+            parse_uci_go_commmand("go movetime 10000", board),
+            Ok(UciGoCommand {
+                depth: None,
+                movetime: Some(10000),
+                ..
+            })
         ));
         assert!(matches!(
-            parse_uci_position_command(
-                "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 extra"
-            ),
-            Err(_)
+            parse_uci_go_commmand("go infinite", board),
+            Ok(UciGoCommand {
+                infinite: true,
+                ponder: false,
+                ..
+            })
         ));
         assert!(matches!(
-            parse_uci_position_command("position startpos moves e4"),
-            Err(_)
+            parse_uci_go_commmand("go ponder depth 4", board),
+            Ok(UciGoCommand {
+                depth: Some(4),
+                ponder: true,
+                ..
+            })
         ));
-    }
-
-    #[test]
-    fn test_parse_uci_go_command() {
-        assert!(parse_uci_go_commmand("go").is_ok());
         assert!(matches!(
-            parse_uci_go_commmand("go depth 3"),
+            parse_uci_go_commmand("go nodes 20000", board),
             Ok(UciGoCommand {
-                mode: GoMode::Depth(_),
+                depth: None,
+                nodes: Some(20000),
                 ..
             })
         ));
         assert!(matches!(
-            parse_uci_go_commmand("go movetime 10000"),
+            parse_uci_go_commmand("go depth 8 nodes 500", board),
             Ok(UciGoCommand {
-                mode: GoMode::MoveTime(_),
+                depth: Some(8),
+                nodes: Some(500),
                 ..
             })
         ));
         assert!(matches!(
-            parse_uci_go_commmand("go infinite"),
+            parse_uci_go_commmand("go mate 3", board),
             Ok(UciGoCommand {
-                mode: GoMode::Infinite,
+                depth: None,
+                mate: Some(3),
                 ..
             })
-        ))
+        ));
+
+        // The limits don't exclude each other, whichever comes first
+        for command in ["go depth 10 movetime 1000", "go movetime 1000 depth 10"] {
+            let go_cmd = parse_uci_go_commmand(command, board).unwrap();
+            assert_eq!((go_cmd.depth, go_cmd.movetime), (Some(10), Some(1000)));
+        }
+        assert!(parse_uci_go_commmand("go nodes", board).is_err());
+        assert!(parse_uci_go_commmand("go nodes many", board).is_err());
+
+        // The clock goes with any limit, only an infinite search leaves it out
+        let go_cmd =
+            parse_uci_go_commmand("go wtime 60000 btime 50000 winc 1000 movestogo 20", board)
+                .unwrap();
+        assert!(!go_cmd.infinite);
+        assert_eq!(go_cmd.tc.wtime, Some(60000));
+        assert_eq!(go_cmd.tc.btime, Some(50000));
+        assert_eq!(go_cmd.tc.winc, Some(1000));
+        assert_eq!(go_cmd.tc.binc, None);
+        assert_eq!(go_cmd.tc.movestogo, Some(20));
+
+        let go_cmd = parse_uci_go_commmand("go depth 6 wtime 1000 btime 1000", board).unwrap();
+        assert_eq!(go_cmd.depth, Some(6));
+        assert_eq!(go_cmd.tc.wtime, Some(1000));
+
+        let go_cmd = parse_uci_go_commmand("go infinite wtime 1000 btime 1000", board).unwrap();
+        assert!(go_cmd.infinite);
+        assert_eq!(go_cmd.tc.wtime, None);
+
+        // The moves of the list that are not legal are dropped
+        let go_cmd = parse_uci_go_commmand("go searchmoves e2e4 e2e5 g1f3 depth 4", board).unwrap();
+        assert_eq!(go_cmd.depth, Some(4));
+        assert_eq!(
+            go_cmd
+                .search_moves
+                .unwrap()
+                .iter()
+                .map(|mv| mv.to_string())
+                .collect::<Vec<_>>(),
+            ["e2e4", "g1f3"]
+        );
+
+        let go_cmd = parse_uci_go_commmand("go depth 4 searchmoves e7e5", board).unwrap();
+        assert_eq!(go_cmd.depth, Some(4));
+        assert_eq!(go_cmd.search_moves, None);
+
+        // The unknown tokens are skipped, the known ones around them still count
+        let go_cmd = parse_uci_go_commmand("go wtime 1000 garbage btime 1000", board).unwrap();
+        assert_eq!(go_cmd.depth, None);
+        assert_eq!((go_cmd.tc.wtime, go_cmd.tc.btime), (Some(1000), Some(1000)));
+
+        let go_cmd = parse_uci_go_commmand("go nonsense depth 7", board).unwrap();
+        assert_eq!(go_cmd.depth, Some(7));
+
+        let go_cmd = parse_uci_go_commmand("go movetime 500 extra depth 3", board).unwrap();
+        assert_eq!((go_cmd.depth, go_cmd.movetime), (Some(3), Some(500)));
+
+        let go_cmd = parse_uci_go_commmand("go nonsense", board).unwrap();
+        assert_eq!((go_cmd.depth, go_cmd.movetime), (None, None));
+        assert!(!go_cmd.infinite);
+        assert_eq!(go_cmd.tc.wtime, None);
+
+        // Too large values are clamped, not wrapped to small ones
+        let go_cmd = parse_uci_go_commmand("go depth 4294967297", board).unwrap();
+        assert_eq!(go_cmd.depth, Some(MAX_SEARCH_DEPTH));
+        let go_cmd = parse_uci_go_commmand("go depth 100", board).unwrap();
+        assert_eq!(go_cmd.depth, Some(MAX_SEARCH_DEPTH));
+        let go_cmd = parse_uci_go_commmand("go mate 4294967297", board).unwrap();
+        assert_eq!(go_cmd.mate, Some(u32::MAX));
+
+        assert!(parse_uci_go_commmand("go depth abc", board).is_err());
+        assert!(parse_uci_go_commmand("go garbage movetime", board).is_err());
+        assert!(parse_uci_go_commmand("depth 3", board).is_err());
+
+        // A clock that ran out may be sent negative
+        let go_cmd = parse_uci_go_commmand("go wtime -120 btime 3000", board).unwrap();
+        assert_eq!(go_cmd.tc.wtime, Some(0));
+        assert_eq!(go_cmd.tc.btime, Some(3000));
     }
 }
@@ -0,0 +1,324 @@
+use crate::{
+    board::{Board, CastlingState},
+    chess_consts,
+    enums::{Piece, Side, Square},
+    helpers,
+    random_generator::XorShift64Star,
+};
+
+const CASTLING_STATES_COUNT: usize = 16;
+
+struct ZobristKeys {
+    pieces: [[u64; chess_consts::SQUARES_COUNT]; chess_consts::PIECE_TYPES_COUNT * 2],
+    castling: [u64; CASTLING_STATES_COUNT],
+    en_passant_files: [u64; chess_consts::BOARD_SIZE],
+    black_to_move: u64,
+}
+
+/// Random keys of the position features, generated at compile time from a fixed seed so
+/// that the hashes are the same on every run
+static ZOBRIST_KEYS: ZobristKeys = {
+    let mut rnd_gen = XorShift64Star::new();
+
+    let mut pieces = [[0; chess_consts::SQUARES_COUNT]; chess_consts::PIECE_TYPES_COUNT * 2];
+    let mut i = 0;
+    while i < pieces.len() {
+        let mut sq = 0;
+        while sq < chess_consts::SQUARES_COUNT {
+            pieces[i][sq] = rnd_gen.next_u64();
+            sq += 1;
+        }
+        i += 1;
+    }
+
+    // Every combination of the rights gets its own key, so a single xor swaps them
+    let mut castling = [0; CASTLING_STATES_COUNT];
+    let mut i = 1;
+    while i < CASTLING_STATES_COUNT {
+        castling[i] = rnd_gen.next_u64();
+        i += 1;
+    }
+
+    let mut en_passant_files = [0; chess_consts::BOARD_SIZE];
+    let mut i = 0;
+    while i < chess_consts::BOARD_SIZE {
+        en_passant_files[i] = rnd_gen.next_u64();
+        i += 1;
+    }
+
+    ZobristKeys {
+        pieces,
+        castling,
+        en_passant_files,
+        black_to_move: rnd_gen.next_u64(),
+    }
+};
+
+pub(crate) fn get_piece_key(side: Side, piece: Piece, square: Square) -> u64 {
+    ZOBRIST_KEYS.pieces
+        [side.index() as usize * chess_consts::PIECE_TYPES_COUNT + piece.index() as usize]
+        [square.index() as usize]
+}
+
+/// 0 when no castling is possible
+pub(crate) fn get_castling_key(castling_state: CastlingState) -> u64 {
+    ZOBRIST_KEYS.castling[castling_state.bits() as usize]
+}
+
+pub(crate) fn get_en_passant_key(en_passant_square: Option<Square>) -> u64 {
+    en_passant_square.map_or(0, |square| {
+        ZOBRIST_KEYS.en_passant_files[square.file().index() as usize]
+    })
+}
+
+pub(crate) fn get_side_key(side: Side) -> u64 {
+    match side {
+        Side::White => 0,
+        Side::Black => ZOBRIST_KEYS.black_to_move,
+    }
+}
+
+impl Board {
+    /// Computes the hash of the position from scratch, make_move keeps it up to date
+    /// incrementally afterwards
+    pub(crate) fn calc_hash(&self) -> u64 {
+        let mut hash = get_side_key(self.game_state.side_to_move)
+            ^ get_castling_key(self.game_state.castling_state)
+            ^ get_en_passant_key(self.game_state.en_passant_square);
+
+        for side in Side::all() {
+            for piece in Piece::all() {
+                let mut bb = self.get_bb(side, piece);
+
+                while bb != 0 {
+                    let square = unsafe { Square::from_u8_unchecked(bb.trailing_zeros() as u8) };
+                    hash ^= get_piece_key(side, piece, square);
+                    bb &= bb - 1;
+                }
+            }
+        }
+
+        hash
+    }
+
+    /// Computes the hash of the pawns from scratch, the pieces keys of the pawns only
+    pub(crate) fn calc_pawn_hash(&self) -> u64 {
+        Side::all()
+            .flat_map(|side| {
+                helpers::get_squares_iter(self.get_bb(side, Piece::Pawn))
+                    .map(move |square| get_piece_key(side, Piece::Pawn, square))
+            })
+            .fold(0, |hash, key| hash ^ key)
+    }
+
+    /// Tells whether the position counts as a draw by repetition in a search, ply is
+    /// the distance to the search root. A position repeated inside the search is a draw
+    /// already, as the side that repeated it could not do better, while a position
+    /// from the game before the root must have occurred twice to make it threefold
+    pub(crate) fn is_repetition(&self, ply: u32) -> bool {
+        let hash = self.game_state.hash;
+        let max_distance = self.game_state.half_move_clock as usize;
+
+        let mut game_repetitions = 0;
+
+        // The entries hold the states before the moves, the last one is one ply back,
+        // and only positions with the same side to move can repeat. A position before
+        // a null move was not really repeated
+        for (entry, distance) in self.history.iter().rev().zip(1usize..) {
+            if distance > max_distance || entry.mv.is_none() {
+                break;
+            }
+
+            if distance % 2 != 0 || entry.game_state.hash != hash {
+                continue;
+            }
+
+            if distance <= ply as usize {
+                return true;
+            }
+
+            game_repetitions += 1;
+            if game_repetitions >= 2 {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Tells whether the position occurred twice before, which makes a threefold
+    /// repetition the players can claim. Unlike is_repetition it ignores the search
+    pub(crate) fn can_claim_threefold(&self) -> bool {
+        let hash = self.game_state.hash;
+
+        self.history
+            .iter()
+            .rev()
+            .zip(1usize..)
+            .take(self.game_state.half_move_clock as usize)
+            .take_while(|(entry, _)| entry.mv.is_some())
+            .filter(|(entry, distance)| distance % 2 == 0 && entry.game_state.hash == hash)
+            .count()
+            >= 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{enums::Move, fen_parser, uci};
+
+    use super::*;
+
+    #[test]
+    fn test_incremental_hash() {
+        let fens = [
+            chess_consts::fen_strings::START_POS_FEN,
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ];
+
+        for fen in fens {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            let hash = board.game_state.hash;
+            assert_eq!(hash, board.calc_hash());
+
+            for mv in board.generate_all_legal_moves_to_vec(board.game_state.side_to_move) {
+                board.make_move(mv);
+                assert_eq!(board.game_state.hash, board.calc_hash(), "{fen}: {mv}");
+                assert_ne!(board.game_state.hash, hash);
+
+                board.unmake_move();
+                assert_eq!(board.game_state.hash, hash);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_games_hash() {
+        // Random walks through the legal moves, the seed keeps the games the same
+        let mut rnd_gen = XorShift64Star::with_seed(0x5eed);
+
+        for fen in [
+            chess_consts::fen_strings::START_POS_FEN,
+            chess_consts::fen_strings::TRICKY_POS_FEN,
+            "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1",
+        ] {
+            for _ in 0..20 {
+                let mut board = fen_parser::parse_fen_string(fen).unwrap();
+                let original = board.clone();
+                let mut hashes = Vec::new();
+
+                for _ in 0..100 {
+                    let moves =
+                        board.generate_all_legal_moves_to_vec(board.game_state.side_to_move);
+                    if moves.is_empty() {
+                        break;
+                    }
+
+                    hashes.push(board.game_state.hash);
+                    let mv = moves[(rnd_gen.next_u64() % moves.len() as u64) as usize];
+                    board.make_move(mv);
+                    assert_eq!(board.game_state.hash, board.calc_hash(), "{fen}: {mv}");
+                    assert_eq!(board.game_state.pawn_hash, board.calc_pawn_hash());
+                }
+
+                while let Some(hash) = hashes.pop() {
+                    board.unmake_move();
+                    assert_eq!(board.game_state.hash, hash, "{fen}");
+                }
+                assert_eq!(board, original);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rook_captured_on_home_square() {
+        // The capture takes away the castling right of the captured rook, the hash must
+        // be the one of the position set up with the remaining rights
+        let cases = [
+            (
+                "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+                "a1a8",
+                "R3k2r/8/8/8/8/8/8/4K2R b Kk - 0 1",
+            ),
+            (
+                "r3k2r/8/6N1/8/8/8/8/4K3 w kq - 0 1",
+                "g6h8",
+                "r3k2N/8/8/8/8/8/8/4K3 b q - 0 1",
+            ),
+            (
+                "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w GBgb - 0 1",
+                "b1b8",
+                "1R2k1r1/8/8/8/8/8/8/4K1R1 b Gg - 0 1",
+            ),
+        ];
+
+        for (fen, mv_str, expected_fen) in cases {
+            let mut board = fen_parser::parse_fen_string(fen).unwrap();
+            let original = board.clone();
+
+            let mv = Move::parse(mv_str, &mut board).unwrap();
+            board.make_move(mv);
+            assert_eq!(board.game_state.hash, board.calc_hash(), "{fen}");
+
+            let expected = fen_parser::parse_fen_string(expected_fen).unwrap();
+            assert_eq!(board.game_state.hash, expected.game_state.hash, "{fen}");
+            assert_eq!(
+                board.game_state.castling_state,
+                expected.game_state.castling_state
+            );
+
+            board.unmake_move();
+            assert_eq!(board, original, "{fen}");
+        }
+    }
+
+    #[test]
+    fn test_transposition_hashes_match() {
+        let a = uci::parse_uci_position_command("position startpos moves e2e4 e7e5 g1f3", false)
+            .unwrap();
+        let b = uci::parse_uci_position_command("position startpos moves g1f3 e7e5 e2e4", false)
+            .unwrap();
+
+        // The en passant square after e2e4 is not set, no black pawn can take
+        assert_eq!(a.game_state.hash, b.game_state.hash);
+    }
+
+    #[test]
+    fn test_repetition_with_game_history() {
+        let mut board = uci::parse_uci_position_command(
+            "position startpos moves g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1",
+            false,
+        )
+        .unwrap();
+
+        // Repeating the start position once more inside the search makes it threefold
+        let mv = Move::parse("f6g8", &mut board).unwrap();
+        board.make_move(mv);
+        assert!(board.is_repetition(1));
+
+        // The start position occurred only once before, it is not a draw yet
+        let mut board =
+            uci::parse_uci_position_command("position startpos moves g1f3 g8f6 f3g1", false)
+                .unwrap();
+        let mv = Move::parse("f6g8", &mut board).unwrap();
+        board.make_move(mv);
+        assert!(!board.is_repetition(1));
+
+        // Unless it is repeated inside the search
+        let mv = Move::parse("g1f3", &mut board).unwrap();
+        board.make_move(mv);
+        assert!(board.is_repetition(4));
+        assert!(!board.is_repetition(2));
+
+        // Passing the turn twice doesn't repeat the position
+        board.make_null_move();
+        board.make_null_move();
+        assert_eq!(
+            board.game_state.hash,
+            board.history.iter().nth_back(1).unwrap().game_state.hash
+        );
+        assert!(!board.is_repetition(6));
+    }
+}